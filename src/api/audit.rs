@@ -0,0 +1,29 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::api::auth::authorize_bearer;
+use crate::api::webhooks::AppState;
+use crate::audit::AuditQuery;
+
+pub async fn list_audit_records(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> impl IntoResponse {
+    if let Err(reason) = authorize_bearer(&headers, &state.config) {
+        tracing::warn!(%reason, "audit.rejected");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match state.audit_log.query(&query) {
+        Ok(records) => Json(records).into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, "audit.query_failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use axum::http::{HeaderMap, header::AUTHORIZATION};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+use crate::config::Config;
+
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AuthError {
+    #[error("missing_authorization")]
+    MissingAuthorization,
+    #[error("malformed_authorization")]
+    MalformedAuthorization,
+    #[error("invalid_token")]
+    InvalidToken,
+    #[error("missing_signature")]
+    MissingSignature,
+    #[error("malformed_signature")]
+    MalformedSignature,
+    #[error("invalid_signature")]
+    InvalidSignature,
+    #[error("missing_timestamp")]
+    MissingTimestamp,
+    #[error("malformed_timestamp")]
+    MalformedTimestamp,
+    #[error("stale_timestamp")]
+    StaleTimestamp,
+}
+
+/// Checks the bearer token and, when a signing secret is configured, the
+/// HMAC-SHA256 signature over `"{timestamp}.{body}"`.
+pub fn authenticate_request(
+    headers: &HeaderMap,
+    body: &[u8],
+    config: &Config,
+    now_unix: i64,
+) -> Result<(), AuthError> {
+    verify_bearer_token(headers, &config.webhook_tokens)?;
+
+    if let Some(secret) = &config.webhook_signing_secret {
+        verify_signature(
+            headers,
+            body,
+            secret,
+            config.webhook_signature_tolerance_secs,
+            now_unix,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Bearer-token check for the operational endpoints, which aren't signed.
+pub fn authorize_bearer(headers: &HeaderMap, config: &Config) -> Result<(), AuthError> {
+    verify_bearer_token(headers, &config.webhook_tokens)
+}
+
+fn verify_bearer_token(headers: &HeaderMap, tokens: &[String]) -> Result<(), AuthError> {
+    let auth_header = headers
+        .get(AUTHORIZATION)
+        .ok_or(AuthError::MissingAuthorization)?;
+
+    let auth_str = auth_header
+        .to_str()
+        .map_err(|_| AuthError::MalformedAuthorization)?;
+
+    let token = auth_str
+        .strip_prefix("Bearer ")
+        .ok_or(AuthError::MalformedAuthorization)?;
+
+    // Compare against every configured token so the time taken doesn't reveal
+    // which one (if any) matched.
+    let matched = tokens
+        .iter()
+        .fold(subtle::Choice::from(0), |acc, expected| {
+            acc | token.as_bytes().ct_eq(expected.as_bytes())
+        });
+
+    if bool::from(matched) {
+        Ok(())
+    } else {
+        Err(AuthError::InvalidToken)
+    }
+}
+
+fn verify_signature(
+    headers: &HeaderMap,
+    body: &[u8],
+    secret: &str,
+    tolerance_secs: i64,
+    now_unix: i64,
+) -> Result<(), AuthError> {
+    let timestamp_str = headers
+        .get(TIMESTAMP_HEADER)
+        .ok_or(AuthError::MissingTimestamp)?
+        .to_str()
+        .map_err(|_| AuthError::MalformedTimestamp)?;
+
+    let timestamp: i64 = timestamp_str
+        .trim()
+        .parse()
+        .map_err(|_| AuthError::MalformedTimestamp)?;
+
+    if (now_unix - timestamp).abs() > tolerance_secs {
+        return Err(AuthError::StaleTimestamp);
+    }
+
+    let signature_str = headers
+        .get(SIGNATURE_HEADER)
+        .ok_or(AuthError::MissingSignature)?
+        .to_str()
+        .map_err(|_| AuthError::MalformedSignature)?;
+
+    let signature_hex = signature_str
+        .trim()
+        .strip_prefix(SIGNATURE_PREFIX)
+        .unwrap_or(signature_str.trim());
+
+    let signature = hex::decode(signature_hex).map_err(|_| AuthError::MalformedSignature)?;
+
+    signing_mac(secret, timestamp_str.trim(), body)
+        .verify_slice(&signature)
+        .map_err(|_| AuthError::InvalidSignature)
+}
+
+fn signing_mac(secret: &str, timestamp: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const NOW: i64 = 1_700_000_000;
+    const BODY: &[u8] = br#"{"payload":{"workoutId":"abc"}}"#;
+
+    /// The `X-Webhook-Signature` header value a client sends.
+    fn sign_payload(secret: &str, timestamp: &str, body: &[u8]) -> String {
+        let digest = signing_mac(secret, timestamp, body).finalize().into_bytes();
+        format!("{}{}", SIGNATURE_PREFIX, hex::encode(digest))
+    }
+
+    fn config(signing_secret: Option<&str>) -> Config {
+        Config {
+            webhook_tokens: vec!["old-token".to_string(), "new-token".to_string()],
+            webhook_signing_secret: signing_secret.map(str::to_string),
+            webhook_signature_tolerance_secs: 300,
+            ..Config::default()
+        }
+    }
+
+    fn headers(token: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {}", token)).unwrap(),
+        );
+        headers
+    }
+
+    fn signed_headers(token: &str, secret: &str, timestamp: i64, body: &[u8]) -> HeaderMap {
+        let mut headers = headers(token);
+        let timestamp = timestamp.to_string();
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from_str(&timestamp).unwrap());
+        headers.insert(
+            SIGNATURE_HEADER,
+            HeaderValue::from_str(&sign_payload(secret, &timestamp, body)).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn test_accepts_any_configured_token() {
+        let config = config(None);
+        assert_eq!(
+            authenticate_request(&headers("old-token"), BODY, &config, NOW),
+            Ok(())
+        );
+        assert_eq!(
+            authenticate_request(&headers("new-token"), BODY, &config, NOW),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_bearer_tokens() {
+        let config = config(None);
+        assert_eq!(
+            authenticate_request(&HeaderMap::new(), BODY, &config, NOW),
+            Err(AuthError::MissingAuthorization)
+        );
+        assert_eq!(
+            authenticate_request(&headers("new-toke"), BODY, &config, NOW),
+            Err(AuthError::InvalidToken)
+        );
+
+        let mut basic = HeaderMap::new();
+        basic.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(
+            authenticate_request(&basic, BODY, &config, NOW),
+            Err(AuthError::MalformedAuthorization)
+        );
+    }
+
+    #[test]
+    fn test_accepts_valid_signature() {
+        let config = config(Some("secret"));
+        let headers = signed_headers("new-token", "secret", NOW - 10, BODY);
+        assert_eq!(authenticate_request(&headers, BODY, &config, NOW), Ok(()));
+    }
+
+    #[test]
+    fn test_rejects_bad_signatures() {
+        let config = config(Some("secret"));
+
+        assert_eq!(
+            authenticate_request(&headers("new-token"), BODY, &config, NOW),
+            Err(AuthError::MissingTimestamp)
+        );
+
+        let wrong_secret = signed_headers("new-token", "other", NOW, BODY);
+        assert_eq!(
+            authenticate_request(&wrong_secret, BODY, &config, NOW),
+            Err(AuthError::InvalidSignature)
+        );
+
+        let tampered = signed_headers("new-token", "secret", NOW, BODY);
+        assert_eq!(
+            authenticate_request(&tampered, b"{}", &config, NOW),
+            Err(AuthError::InvalidSignature)
+        );
+
+        let replayed = signed_headers("new-token", "secret", NOW - 301, BODY);
+        assert_eq!(
+            authenticate_request(&replayed, BODY, &config, NOW),
+            Err(AuthError::StaleTimestamp)
+        );
+    }
+}
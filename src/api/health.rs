@@ -0,0 +1,68 @@
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde_json::json;
+
+use crate::api::auth::authorize_bearer;
+use crate::api::webhooks::AppState;
+use crate::store;
+
+pub async fn handle_healthz() -> impl IntoResponse {
+    (StatusCode::OK, "ok")
+}
+
+pub async fn handle_readyz(State(state): State<AppState>) -> impl IntoResponse {
+    let store_check = store::check_writable(&state.config.data_dir);
+
+    let scheduler_check = if !state.config.sync.enabled {
+        "disabled"
+    } else if state.status.scheduler_running() {
+        "ok"
+    } else {
+        "not_running"
+    };
+
+    let ready = store_check.is_ok() && scheduler_check != "not_running";
+
+    let body = json!({
+        "ready": ready,
+        "checks": {
+            "config": "ok",
+            "store": store_check.map_or_else(|e| format!("error: {:#}", e), |_| "ok".to_string()),
+            "scheduler": scheduler_check,
+        }
+    });
+
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, Json(body))
+}
+
+pub async fn handle_status(State(state): State<AppState>, headers: HeaderMap) -> impl IntoResponse {
+    if let Err(reason) = authorize_bearer(&headers, &state.config) {
+        tracing::warn!(%reason, "status.rejected");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let snapshot = state.status.snapshot();
+
+    Json(json!({
+        "started_at": state.status.started_at(),
+        "scheduler": {
+            "enabled": state.config.sync.enabled,
+            "running": state.status.scheduler_running(),
+        },
+        "last_sync": snapshot.last_sync,
+        "queue": state.job_queue.depth(),
+        "last_processed_by_routine": snapshot.last_processed_by_routine,
+        "last_error": snapshot.last_error,
+    }))
+    .into_response()
+}
@@ -0,0 +1,44 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::api::auth::authorize_bearer;
+use crate::api::webhooks::AppState;
+
+pub async fn list_dead_letters(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if let Err(reason) = authorize_bearer(&headers, &state.config) {
+        tracing::warn!(%reason, "jobs.rejected");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    Json(state.job_queue.dead_letters()).into_response()
+}
+
+pub async fn retry_dead_letter(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(workout_id): Path<String>,
+) -> impl IntoResponse {
+    if let Err(reason) = authorize_bearer(&headers, &state.config) {
+        tracing::warn!(%reason, "jobs.rejected");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    match state.job_queue.requeue_dead(&workout_id) {
+        Ok(true) => {
+            tracing::info!(%workout_id, "job.requeued");
+            StatusCode::ACCEPTED.into_response()
+        }
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => {
+            tracing::error!(error = %e, %workout_id, "job.requeue_failed");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
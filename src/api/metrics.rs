@@ -0,0 +1,10 @@
+use axum::{http::header::CONTENT_TYPE, response::IntoResponse};
+
+use crate::metrics::metrics;
+
+pub async fn handle_metrics() -> impl IntoResponse {
+    (
+        [(CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
+}
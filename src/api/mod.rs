@@ -1 +1,6 @@
+pub mod audit;
+pub mod auth;
+pub mod health;
+pub mod jobs;
+pub mod metrics;
 pub mod webhooks;
@@ -1,27 +1,50 @@
-use axum::Json;
+use anyhow::Context;
 use axum::{
+    body::Bytes,
     extract::State,
-    http::{HeaderMap, StatusCode, header::AUTHORIZATION},
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header::USER_AGENT},
+    response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
-use std::collections::HashSet;
-use std::result::Result;
-use std::sync::Arc;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
 
+use crate::api::auth::authenticate_request;
+use crate::audit::{AuditLog, AuditRecord};
 use crate::clients::hevy::HevyClient;
-use crate::clients::models::common::ExerciseForUpdate;
+use crate::clients::models::common::{Exercise, ExerciseForUpdate};
+use crate::clients::models::requests::RoutineUpdate;
+use crate::clients::models::responses::WorkoutResponse;
 use crate::config::Config;
+use crate::history::HistoryStore;
+use crate::jobs::queue::{JobQueue, JobSource};
+use crate::jobs::routine_lock::RoutineLocks;
+use crate::metrics::metrics;
+use crate::prescription_store::PrescriptionStore;
+use crate::program_store::ProgramStore;
+use crate::services::ai_prompt::PROMPT_VERSION;
+use crate::services::exercise_kinds::ExerciseKinds;
+use crate::services::notes;
 use crate::services::progressive_overload::{
-    ProgressiveOverloadRequest, ProgressiveOverloadService,
+    ProgressiveOverloadRequest, ProgressiveOverloadRun, ProgressiveOverloadService,
 };
+use crate::services::substitutions::{RoutineMismatch, SubstitutionAction, Substitutions};
+use crate::services::update_mode;
+use crate::status::ServiceStatus;
+use crate::telemetry;
 
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub hevy_client: HevyClient,
     pub progressive_overload_service: ProgressiveOverloadService,
-    pub processed_workout_ids: Arc<std::sync::Mutex<HashSet<String>>>,
+    pub job_queue: JobQueue,
+    pub routine_locks: RoutineLocks,
+    pub status: ServiceStatus,
+    pub audit_log: AuditLog,
+    pub history: HistoryStore,
+    pub programs: ProgramStore,
+    pub prescriptions: PrescriptionStore,
 }
 
 #[derive(Deserialize)]
@@ -35,88 +58,225 @@ pub struct WorkoutIdPayload {
     pub workout_id: String,
 }
 
-fn authenticate_request(headers: &HeaderMap, state: &AppState) -> Result<(), StatusCode> {
-    let auth_header = match headers.get(AUTHORIZATION) {
-        Some(header) => header,
-        None => return Err(StatusCode::UNAUTHORIZED),
-    };
-
-    let auth_str = match auth_header.to_str() {
-        Ok(s) => s,
-        Err(_) => return Err(StatusCode::UNAUTHORIZED),
-    };
-
-    if !auth_str.starts_with("Bearer ") {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
-
-    let token = &auth_str[7..];
-
-    if token != state.config.webhook_token {
-        return Err(StatusCode::UNAUTHORIZED);
-    }
-
-    Ok(())
-}
-
 pub async fn handle_workout_completion(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<WebhookPayload>,
+    body: Bytes,
 ) -> impl IntoResponse {
-    if let Err(response) = authenticate_request(&headers, &state) {
-        return response.into_response();
+    // Continue the caller's trace if it sent a traceparent header
+    let span = tracing::info_span!("webhook.receive", workout_id = tracing::field::Empty);
+    if let Err(e) = span.set_parent(telemetry::context_from_headers(&headers)) {
+        tracing::debug!(error = %e, "webhook.trace_context_ignored");
     }
 
-    // Extract identifiers needed for background processing and acknowledge immediately
-    let workout_id = payload.payload.workout_id.clone();
-    let state_for_task = state.clone();
+    span.in_scope(|| receive_workout_webhook(&state, &headers, &body))
+}
+
+fn receive_workout_webhook(state: &AppState, headers: &HeaderMap, body: &[u8]) -> Response {
+    metrics().webhooks_received.inc();
 
+    if let Err(reason) = authenticate_request(headers, body, &state.config, Utc::now().timestamp())
+    {
+        metrics()
+            .webhooks_rejected
+            .with_label_values(&[&reason.to_string()])
+            .inc();
+        tracing::warn!(
+            %reason,
+            user_agent = headers
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or(""),
+            body_bytes = body.len(),
+            "webhook.rejected"
+        );
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    let payload: WebhookPayload = match serde_json::from_slice(body) {
+        Ok(payload) => payload,
+        Err(e) => {
+            metrics()
+                .webhooks_rejected
+                .with_label_values(&["invalid_payload"])
+                .inc();
+            tracing::warn!(error = %e, "webhook.invalid_payload");
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    let workout_id = payload.payload.workout_id;
+
+    let span = tracing::Span::current();
+    span.record("workout_id", workout_id.as_str());
     tracing::info!(%workout_id, "webhook.received");
 
-    // Offload heavy work to a background task so we can return 200 quickly
+    // Persist the job and hand it to the workers so we can return 200 quickly
     // According to hevy api docs:
     // "Your endpoint must respond with a 200 OK status within 5 seconds, otherwise the delivery will be retried"
-    tokio::spawn(async move {
-        process_single_workout(&state_for_task, workout_id).await;
-    });
+    // If the job can't be persisted we let Hevy retry the delivery.
+    if let Err(e) = state.job_queue.enqueue_traced(
+        &workout_id,
+        JobSource::Webhook,
+        telemetry::span_trace_context(&span),
+    ) {
+        tracing::error!(error = %e, %workout_id, "webhook.enqueue_failed");
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
 
     // Acknowledge receipt to prevent retries
     StatusCode::OK.into_response()
 }
 
-pub async fn process_single_workout(state: &AppState, workout_id: String) {
-    tracing::info!(%workout_id, "workout.processing");
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessOutcome {
+    Updated,
+    NoRoutine,
+    Superseded,
+}
 
-    let workout = match state.hevy_client.get_workout(&workout_id).await {
-        Ok(workout) => workout,
-        Err(e) => {
-            tracing::error!(error = %e, %workout_id, "failed to fetch workout");
-            return;
+impl ProcessOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessOutcome::Updated => "updated",
+            ProcessOutcome::NoRoutine => "no_routine",
+            ProcessOutcome::Superseded => "superseded",
         }
-    };
+    }
+}
+
+pub struct RoutineUpdatePlan {
+    pub run: ProgressiveOverloadRun,
+    pub mismatch: RoutineMismatch,
+    pub routine_update: RoutineUpdate,
+    pub suggestion_count: usize,
+}
+
+pub async fn process_single_workout(
+    state: &AppState,
+    workout_id: String,
+) -> anyhow::Result<ProcessOutcome> {
+    tracing::info!(%workout_id, "workout.processing");
+
+    let workout = state
+        .hevy_client
+        .get_workout(&workout_id)
+        .await
+        .context("failed to fetch workout")?;
 
     tracing::info!(workout_title = %workout.title, "workout.retrieved");
 
+    let kinds = ExerciseKinds::lookup(&state.hevy_client, &workout.exercises).await;
+    if let Err(e) = state
+        .history
+        .record_workouts(std::slice::from_ref(&workout), &kinds.load)
+    {
+        tracing::warn!(error = %e, workout_id = %workout.id, "history.record_failed");
+    }
+
     if workout.routine_id.is_empty() || workout.routine_id == "null" {
         tracing::info!("workout.no_routine_associated");
-        // Mark as processed even if no routine
-        state
-            .processed_workout_ids
-            .lock()
-            .unwrap()
-            .insert(workout_id);
-        return;
+        return Ok(ProcessOutcome::NoRoutine);
     }
 
-    let routine = match state.hevy_client.get_routine(&workout.routine_id).await {
-        Ok(routine) => routine,
-        Err(e) => {
-            tracing::error!(error = %e, routine_id = %workout.routine_id, "failed to fetch routine");
-            return;
-        }
+    // Hold the routine for the rest of the run so overlapping workouts for the
+    // same routine can't interleave their reads and writes
+    let ticket = state
+        .routine_locks
+        .register(&workout.routine_id, workout_end_time(&workout));
+    let Some(routine_guard) = ticket.acquire().await else {
+        tracing::info!(
+            workout_id = %workout.id,
+            routine_id = %workout.routine_id,
+            "workout.superseded_by_later_workout"
+        );
+        state.status.record_workout(
+            &workout.routine_id,
+            &workout.id,
+            &workout.title,
+            ProcessOutcome::Superseded.as_str(),
+        );
+        return Ok(ProcessOutcome::Superseded);
     };
 
+    let RoutineUpdatePlan {
+        run,
+        mismatch,
+        routine_update,
+        suggestion_count,
+    } = plan_routine_update(state, &workout).await?;
+
+    let update_result = state
+        .hevy_client
+        .update_routine(&workout.routine_id, routine_update.clone())
+        .await;
+
+    let audit_record = AuditRecord {
+        recorded_at: Utc::now(),
+        workout_id: workout.id.clone(),
+        workout_title: workout.title.clone(),
+        routine_id: workout.routine_id.clone(),
+        engine: run.engine.clone(),
+        model: run.model.clone(),
+        prompt_version: PROMPT_VERSION.to_string(),
+        prompt: Some(run.prompt.clone()),
+        raw_response: Some(run.raw_response.clone()),
+        parsed_response: run.response.clone(),
+        adherence: run.adherence.clone(),
+        mismatch,
+        routine_update,
+        hevy_response: update_result.as_ref().ok().cloned(),
+        error: update_result.as_ref().err().map(|e| format!("{:#}", e)),
+    };
+    if let Err(e) = state.audit_log.append(audit_record) {
+        tracing::error!(error = %e, workout_id = %workout.id, "audit.append_failed");
+    }
+
+    update_result.context("failed to update routine")?;
+
+    // Program state and stall tracking only move on once Hevy holds the
+    // prescription, so a failed update is retried from the same point
+    if let Some(update) = &run.program_update
+        && let Err(e) = state.programs.apply(update)
+    {
+        tracing::error!(error = %e, routine_id = %workout.routine_id, "program.state_save_failed");
+    }
+    if let Some(record) = &run.prescription_record
+        && let Err(e) = state.prescriptions.record(record)
+    {
+        tracing::error!(error = %e, routine_id = %workout.routine_id, "prescription.save_failed");
+    }
+
+    routine_guard.mark_applied();
+    state.status.record_workout(
+        &workout.routine_id,
+        &workout.id,
+        &workout.title,
+        ProcessOutcome::Updated.as_str(),
+    );
+
+    tracing::info!(
+        workout_id = %workout.id,
+        routine_id = %workout.routine_id,
+        suggestion_count,
+        "routine.update_success"
+    );
+
+    Ok(ProcessOutcome::Updated)
+}
+
+/// Fetches the routine, asks the engine for the next session and builds the
+/// update that would be sent to Hevy, without sending it.
+pub async fn plan_routine_update(
+    state: &AppState,
+    workout: &WorkoutResponse,
+) -> anyhow::Result<RoutineUpdatePlan> {
+    let routine = state
+        .hevy_client
+        .get_routine(&workout.routine_id)
+        .await
+        .with_context(|| format!("failed to fetch routine {}", workout.routine_id))?;
+
     let routine_exercises_for_update: Vec<ExerciseForUpdate> = routine
         .exercises
         .iter()
@@ -124,23 +284,41 @@ pub async fn process_single_workout(state: &AppState, workout_id: String) {
         .collect();
 
     let existing_exercise_count = routine_exercises_for_update.len();
+    let existing_routine_notes = routine.notes.clone();
+
+    let substitutions = Substitutions {
+        policy: state.config.substitution_policy,
+        equivalences: state.config.exercise_equivalences.clone(),
+    };
+    let mismatch = substitutions.detect(&routine, workout);
+    for substitution in &mismatch.substituted {
+        tracing::info!(
+            routine_exercise = %substitution.routine_template_id,
+            workout_exercise = %substitution.workout_template_id,
+            equivalent = substitution.equivalent,
+            action = substitution.action.as_str(),
+            "routine.exercise_substituted"
+        );
+    }
+    if !mismatch.added.is_empty() || !mismatch.removed.is_empty() {
+        tracing::info!(
+            added = ?mismatch.added,
+            removed = ?mismatch.removed,
+            "routine.exercises_mismatched"
+        );
+    }
 
     let request = ProgressiveOverloadRequest {
         current_workout: workout.clone(),
         routine,
     };
 
-    let response = match state
+    let run = state
         .progressive_overload_service
         .process_workout_completion(request)
         .await
-    {
-        Ok(response) => response,
-        Err(e) => {
-            tracing::error!(error = %e, "failed to process progressive overload");
-            return;
-        }
-    };
+        .context("failed to process progressive overload")?;
+    let response = &run.response;
 
     tracing::info!(
         next_week = %response.week_number,
@@ -150,7 +328,7 @@ pub async fn process_single_workout(state: &AppState, workout_id: String) {
 
     let exercise_suggestions = state
         .progressive_overload_service
-        .build_exercise_suggestions(&response);
+        .build_exercise_suggestions(response);
 
     let suggestion_count = exercise_suggestions.len();
 
@@ -178,55 +356,111 @@ pub async fn process_single_workout(state: &AppState, workout_id: String) {
         );
     }
 
-    let routine_notes_value = None;
+    let update_mode = state.config.update_mode;
+    // Only the marked section is the service's; the lifter's own notes and
+    // cues around it are kept
+    let routine_notes_value = update_mode.writes_notes().then(|| {
+        notes::merge_section(
+            existing_routine_notes.as_deref(),
+            &state
+                .progressive_overload_service
+                .build_routine_summary(response),
+        )
+    });
 
     let updated_exercises = routine_exercises_for_update
         .into_iter()
         .map(|mut exercise| {
-            if let Some(new_notes) = exercise_suggestions.get(&exercise.exercise_template_id) {
-                exercise.notes = Some(new_notes.clone());
+            let rest = response
+                .rest_adjustments
+                .iter()
+                .find(|adjustment| adjustment.exercise_template_id == exercise.exercise_template_id)
+                .map(|adjustment| adjustment.to_seconds);
+
+            // A swapped exercise's suggestion is filed under the substitute,
+            // and only reaches the routine when the policy says so
+            let substitution = mismatch.substitution_for(&exercise.exercise_template_id);
+            let source_template_id = match substitution {
+                Some(substitution) if substitution.action != SubstitutionAction::Kept => {
+                    substitution.workout_template_id.clone()
+                }
+                _ => exercise.exercise_template_id.clone(),
+            };
+
+            if substitution
+                .is_some_and(|substitution| substitution.action == SubstitutionAction::Adopted)
+                && let Some(performed) = workout
+                    .exercises
+                    .iter()
+                    .find(|performed| performed.exercise_template_id == source_template_id)
+            {
+                exercise = ExerciseForUpdate {
+                    superset_id: exercise.superset_id,
+                    rest_seconds: exercise.rest_seconds,
+                    notes: exercise.notes,
+                    ..performed.to_update_format()
+                };
+            }
+
+            if update_mode.writes_notes()
+                && let Some(new_notes) = exercise_suggestions.get(&source_template_id)
+            {
+                exercise.notes = Some(notes::merge_section(exercise.notes.as_deref(), new_notes));
+            }
+            if let Some(prescribed) = response
+                .updated_exercises
+                .iter()
+                .find(|prescribed| prescribed.exercise_template_id == source_template_id)
+            {
+                if update_mode.writes_sets() {
+                    update_mode::write_sets(&mut exercise, prescribed);
+                } else {
+                    apply_rep_ranges(&mut exercise, prescribed);
+                }
+            }
+            if rest.is_some() {
+                exercise.rest_seconds = rest;
             }
             exercise
         })
         .collect();
 
-    let update_result = state
-        .hevy_client
-        .update_routine(
-            &workout.routine_id,
-            crate::clients::models::requests::RoutineUpdate {
-                title: Some(response.routine_title.clone()),
-                notes: routine_notes_value,
-                exercises: Some(updated_exercises),
-                folder_id: None,
-            },
-        )
-        .await;
+    let routine_update = RoutineUpdate {
+        title: Some(response.routine_title.clone()),
+        notes: routine_notes_value,
+        exercises: Some(updated_exercises),
+        folder_id: None,
+    };
 
-    match update_result {
-        Ok(_) => {
-            tracing::info!(
-                workout_id = %workout.id,
-                routine_id = %workout.routine_id,
-                suggestion_count,
-                "routine.update_success"
-            );
-        }
-        Err(e) => {
-            tracing::error!(
-                error = %e,
-                workout_id = %workout.id,
-                routine_id = %workout.routine_id,
-                suggestion_count,
-                "failed to update routine"
-            );
+    Ok(RoutineUpdatePlan {
+        run,
+        mismatch,
+        routine_update,
+        suggestion_count,
+    })
+}
+
+/// Copies prescribed rep ranges onto the routine's working sets, in order,
+/// so double progression shows up as a range in Hevy and not only in notes.
+fn apply_rep_ranges(exercise: &mut ExerciseForUpdate, prescribed: &Exercise) {
+    let ranges = prescribed.working_sets().map(|set| set.rep_range.clone());
+
+    for (set, range) in exercise
+        .sets
+        .iter_mut()
+        .filter(|set| !set.is_warmup())
+        .zip(ranges)
+    {
+        if range.is_some() {
+            set.rep_range = range;
         }
     }
+}
 
-    // Mark as processed
-    state
-        .processed_workout_ids
-        .lock()
-        .unwrap()
-        .insert(workout_id);
+fn workout_end_time(workout: &WorkoutResponse) -> DateTime<Utc> {
+    [&workout.end_time, &workout.created_at]
+        .into_iter()
+        .find_map(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(Utc::now)
 }
@@ -0,0 +1,215 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::clients::models::{requests::RoutineUpdate, responses::RoutineResponse};
+use crate::services::adherence::AdherenceReport;
+use crate::services::progressive_overload::ProgressiveOverloadResponse;
+use crate::services::substitutions::RoutineMismatch;
+
+/// Everything that went into one routine change, so a prescription can be
+/// traced back to the workout, prompt and model output that produced it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub recorded_at: DateTime<Utc>,
+    pub workout_id: String,
+    pub workout_title: String,
+    pub routine_id: String,
+    pub engine: String,
+    pub model: String,
+    pub prompt_version: String,
+    /// `None` when LLM text redaction is enabled.
+    pub prompt: Option<String>,
+    pub raw_response: Option<String>,
+    pub parsed_response: ProgressiveOverloadResponse,
+    /// How the workout compared to the previous prescription, when there
+    /// was one.
+    #[serde(default)]
+    pub adherence: Option<AdherenceReport>,
+    /// Exercises added, removed or swapped in the workout compared to the
+    /// routine, and how each swap was handled.
+    #[serde(default)]
+    pub mismatch: RoutineMismatch,
+    pub routine_update: RoutineUpdate,
+    pub hevy_response: Option<RoutineResponse>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AuditQuery {
+    pub routine_id: Option<String>,
+    /// Inclusive UTC dates (`YYYY-MM-DD`).
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        let date = record.recorded_at.date_naive();
+        self.routine_id
+            .as_ref()
+            .is_none_or(|routine_id| *routine_id == record.routine_id)
+            && self.from.is_none_or(|from| date >= from)
+            && self.to.is_none_or(|to| date <= to)
+    }
+}
+
+/// Append-only JSON Lines log of routine changes. Records are never rewritten,
+/// so the file doubles as a history of every update the service has made.
+#[derive(Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    redact_llm_text: bool,
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    pub fn new(path: &Path, redact_llm_text: bool) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            redact_llm_text,
+            write_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub fn append(&self, mut record: AuditRecord) -> Result<()> {
+        if self.redact_llm_text {
+            record.prompt = None;
+            record.raw_response = None;
+        }
+
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        let _guard = self.write_lock.lock().unwrap();
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        file.write_all(&line)
+            .with_context(|| format!("Failed to append to {}", self.path.display()))?;
+
+        Ok(())
+    }
+
+    /// Matching records, newest first.
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", self.path.display()));
+            }
+        };
+
+        let mut records: Vec<AuditRecord> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    tracing::warn!(error = %e, "audit.unreadable_record");
+                    None
+                }
+            })
+            .filter(|record| query.matches(record))
+            .collect();
+
+        records.reverse();
+        if let Some(limit) = query.limit {
+            records.truncate(limit);
+        }
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn record(routine_id: &str, workout_id: &str, recorded_at: &str) -> AuditRecord {
+        AuditRecord {
+            recorded_at: DateTime::parse_from_rfc3339(recorded_at)
+                .unwrap()
+                .with_timezone(&Utc),
+            workout_id: workout_id.to_string(),
+            workout_title: "Day 1 - Week 2".to_string(),
+            routine_id: routine_id.to_string(),
+            engine: "gemini".to_string(),
+            model: "gemini-2.5-pro".to_string(),
+            prompt_version: "1".to_string(),
+            prompt: Some("prompt".to_string()),
+            raw_response: Some("{}".to_string()),
+            parsed_response: ProgressiveOverloadResponse {
+                updated_exercises: Vec::new(),
+                week_number: 3,
+                routine_title: "Day 1 - Week 3".to_string(),
+                stall_adjustments: Vec::new(),
+                bodyweight_exercises: HashMap::new(),
+                rest_adjustments: Vec::new(),
+                parse_warnings: Vec::new(),
+            },
+            adherence: None,
+            mismatch: RoutineMismatch::default(),
+            routine_update: RoutineUpdate {
+                title: Some("Day 1 - Week 3".to_string()),
+                notes: None,
+                folder_id: None,
+                exercises: Some(Vec::new()),
+            },
+            hevy_response: None,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_query_filters_by_routine_and_date() {
+        let path = std::env::temp_dir().join(format!("hevy-audit-{}.jsonl", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let log = AuditLog::new(&path, true);
+        log.append(record("r1", "w1", "2025-03-01T10:00:00Z"))
+            .unwrap();
+        log.append(record("r2", "w2", "2025-03-02T10:00:00Z"))
+            .unwrap();
+        log.append(record("r1", "w3", "2025-03-03T10:00:00Z"))
+            .unwrap();
+
+        let all_r1 = log
+            .query(&AuditQuery {
+                routine_id: Some("r1".to_string()),
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        assert_eq!(
+            all_r1
+                .iter()
+                .map(|r| r.workout_id.as_str())
+                .collect::<Vec<_>>(),
+            ["w3", "w1"]
+        );
+        assert!(all_r1[0].prompt.is_none());
+
+        let early = log
+            .query(&AuditQuery {
+                to: NaiveDate::from_ymd_opt(2025, 3, 2),
+                ..AuditQuery::default()
+            })
+            .unwrap();
+        assert_eq!(early.len(), 2);
+
+        let _ = fs::remove_file(path);
+    }
+}
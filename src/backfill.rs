@@ -0,0 +1,197 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::api::webhooks::AppState;
+use crate::clients::models::responses::{RoutineResponse, WorkoutResponse};
+use crate::history::performed_at;
+use crate::services::exercise_kinds::ExerciseKinds;
+use crate::services::progressive_overload::ProgressiveOverloadRequest;
+
+const PAGE_SIZE: i32 = 10;
+
+#[derive(Debug, Default)]
+pub struct BackfillOptions {
+    /// Only replay workouts started on or after this UTC date.
+    pub since: Option<NaiveDate>,
+    /// Ask the engine what it would have prescribed after each workout.
+    pub simulate: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulatedStep {
+    pub workout_id: String,
+    pub workout_title: String,
+    pub performed_at: String,
+    pub routine_id: String,
+    pub next_routine_title: String,
+    pub prescriptions: Vec<SimulatedPrescription>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulatedPrescription {
+    pub exercise_title: String,
+    /// Working sets as `weight x reps`, e.g. `100kg x 5`.
+    pub sets: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    pub workouts_fetched: usize,
+    pub sessions_recorded: usize,
+    pub steps: Vec<SimulatedStep>,
+}
+
+/// Replays the account's workouts oldest first into the local exercise
+/// history. Never writes to Hevy.
+pub async fn run_backfill(
+    state: &AppState,
+    options: &BackfillOptions,
+) -> anyhow::Result<BackfillSummary> {
+    let workouts = fetch_workouts(state, options.since).await?;
+    tracing::info!(workout_count = workouts.len(), "backfill.workouts_fetched");
+
+    let exercises: Vec<_> = workouts
+        .iter()
+        .flat_map(|workout| workout.exercises.iter().cloned())
+        .collect();
+    let kinds = ExerciseKinds::lookup(&state.hevy_client, &exercises).await;
+    let sessions_recorded = state.history.record_workouts(&workouts, &kinds.load)?;
+    tracing::info!(sessions_recorded, "backfill.history_recorded");
+
+    let mut summary = BackfillSummary {
+        workouts_fetched: workouts.len(),
+        sessions_recorded,
+        steps: Vec::new(),
+    };
+
+    if options.simulate {
+        summary.steps = simulate(state, &workouts).await;
+    }
+
+    Ok(summary)
+}
+
+/// Every workout since `since`, oldest first.
+async fn fetch_workouts(
+    state: &AppState,
+    since: Option<NaiveDate>,
+) -> anyhow::Result<Vec<WorkoutResponse>> {
+    let mut workouts = Vec::new();
+    let mut page = 1;
+
+    // Pages come back newest first, so stop once a page reaches past `since`
+    loop {
+        let response = state
+            .hevy_client
+            .get_workouts(page, PAGE_SIZE)
+            .await
+            .with_context(|| format!("failed to fetch workouts page {}", page))?;
+
+        let mut reached_since = false;
+        for workout in response.workouts {
+            match (since, performed_at(&workout)) {
+                (Some(since), Some(at)) if at.date_naive() < since => reached_since = true,
+                _ => workouts.push(workout),
+            }
+        }
+
+        if reached_since || page >= response.page_count {
+            break;
+        }
+        page += 1;
+    }
+
+    workouts.sort_by_key(performed_at);
+    Ok(workouts)
+}
+
+/// Runs the engine on each workout in turn. Each routine starts from its
+/// current state in Hevy and then carries the previous simulated prescription
+/// forward, so every step sees what the engine itself would have set up.
+async fn simulate(state: &AppState, workouts: &[WorkoutResponse]) -> Vec<SimulatedStep> {
+    let mut routines: HashMap<String, RoutineResponse> = HashMap::new();
+    let mut steps = Vec::new();
+
+    for workout in workouts {
+        if workout.routine_id.is_empty() || workout.routine_id == "null" {
+            continue;
+        }
+
+        let routine = match routines.get(&workout.routine_id) {
+            Some(routine) => routine.clone(),
+            None => match state.hevy_client.get_routine(&workout.routine_id).await {
+                Ok(routine) => routine,
+                Err(e) => {
+                    tracing::warn!(
+                        error = %e,
+                        routine_id = %workout.routine_id,
+                        "backfill.routine_unavailable"
+                    );
+                    continue;
+                }
+            },
+        };
+
+        let mut step = SimulatedStep {
+            workout_id: workout.id.clone(),
+            workout_title: workout.title.clone(),
+            performed_at: workout.start_time.clone(),
+            routine_id: workout.routine_id.clone(),
+            next_routine_title: routine.title.clone(),
+            prescriptions: Vec::new(),
+            error: None,
+        };
+
+        let request = ProgressiveOverloadRequest {
+            current_workout: workout.clone(),
+            routine: routine.clone(),
+        };
+        match state
+            .progressive_overload_service
+            .process_workout_completion(request)
+            .await
+        {
+            Ok(run) => {
+                let response = run.response;
+                step.next_routine_title = response.routine_title.clone();
+                step.prescriptions = response
+                    .updated_exercises
+                    .iter()
+                    .map(|exercise| SimulatedPrescription {
+                        exercise_title: exercise.title.clone(),
+                        sets: exercise
+                            .working_sets()
+                            .map(|set| match (set.weight_kg, set.reps) {
+                                (Some(weight), Some(reps)) => format!("{}kg x {}", weight, reps),
+                                (None, Some(reps)) => format!("{} reps", reps),
+                                _ => "-".to_string(),
+                            })
+                            .collect(),
+                    })
+                    .collect();
+
+                let mut next_routine = routine;
+                next_routine.title = response.routine_title;
+                for prescribed in response.updated_exercises {
+                    if let Some(existing) = next_routine.exercises.iter_mut().find(|exercise| {
+                        exercise.exercise_template_id == prescribed.exercise_template_id
+                    }) {
+                        *existing = prescribed;
+                    }
+                }
+                routines.insert(workout.routine_id.clone(), next_routine);
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, workout_id = %workout.id, "backfill.simulation_failed");
+                step.error = Some(format!("{:#}", e));
+            }
+        }
+
+        steps.push(step);
+    }
+
+    steps
+}
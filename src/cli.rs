@@ -0,0 +1,477 @@
+use anyhow::Context;
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::api::webhooks::{
+    AppState, RoutineUpdatePlan, plan_routine_update, process_single_workout,
+};
+use crate::audit::AuditQuery;
+use crate::backfill::{BackfillOptions, run_backfill};
+use crate::jobs::queue::JobSource;
+use crate::jobs::worker::spawn_workers;
+use crate::scheduler::run_sync;
+use crate::services::engine::Engine;
+use crate::services::programs::Program;
+use crate::services::stalls::StallResponse;
+use crate::shutdown;
+use crate::simulation::{SimulationFixture, load_fixtures, render_table, run_simulation};
+
+const ROUTINES_PAGE_SIZE: i32 = 10;
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Progressive overload suggestions for Hevy routines")]
+pub struct Cli {
+    /// Defaults to `serve` when omitted.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the webhook server, workers and sync scheduler.
+    Serve,
+    /// Run one sync and process whatever it queued, then exit.
+    Sync,
+    /// Process a single workout and update its routine.
+    Process {
+        workout_id: String,
+        /// Print the routine update instead of sending it to Hevy.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print the prompt that would be sent to the LLM for a workout.
+    PreviewPrompt { workout_id: String },
+    /// Inspect routines in the Hevy account.
+    Routines {
+        #[command(subcommand)]
+        command: RoutinesCommand,
+    },
+    /// Run a built-in training program on a routine instead of the engine.
+    Program {
+        #[command(subcommand)]
+        command: ProgramCommand,
+    },
+    /// List exercises that missed their prescribed reps in recent sessions.
+    Stalls,
+    /// Record your bodyweight for relative-strength e1RMs, or list what's
+    /// been recorded.
+    Bodyweight { kg: Option<f32> },
+    /// Show the changes the service has made to a routine, newest first.
+    History {
+        routine_id: String,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+        /// Print the full audit records as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Replay past workouts into the local exercise history. Never writes to Hevy.
+    Backfill {
+        /// Only replay workouts from this date (YYYY-MM-DD) onwards.
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Also run the engine on each workout and print what it would have
+        /// prescribed. Makes one LLM call per workout.
+        #[arg(long)]
+        simulate: bool,
+    },
+    /// Run recorded fixtures through one or more engines and compare the
+    /// prescriptions. Never writes to Hevy.
+    Simulate {
+        /// A fixture file or a directory of `*.json` fixtures.
+        fixtures: PathBuf,
+        /// Engine to run (`gemini` or `rule_based`). Repeat to compare engines.
+        #[arg(long = "engine", required = true)]
+        engines: Vec<Engine>,
+        /// Print the report as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Save a workout and its current routine as a simulation fixture.
+    RecordFixture { workout_id: String, output: PathBuf },
+}
+
+impl Command {
+    /// Whether the command changes anything in `DATA_DIR`. Those take the
+    /// directory's lock; the rest only read it and can run alongside a
+    /// server.
+    pub fn writes_data(&self) -> bool {
+        match self {
+            Command::Serve | Command::Sync | Command::Backfill { .. } => true,
+            Command::Process { dry_run, .. } => !dry_run,
+            Command::Program { command } => !matches!(command, ProgramCommand::List),
+            Command::Bodyweight { kg } => kg.is_some(),
+            Command::PreviewPrompt { .. }
+            | Command::Routines { .. }
+            | Command::Stalls
+            | Command::History { .. }
+            | Command::Simulate { .. }
+            | Command::RecordFixture { .. } => false,
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RoutinesCommand {
+    /// List every routine with its id.
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProgramCommand {
+    /// Show which routines run a program and each program's lift state.
+    List,
+    /// Start a routine on a program from its first week.
+    Set {
+        routine_id: String,
+        /// `531`, `gzclp`, `texas_method` or `linear`.
+        program: Program,
+    },
+    /// Hand a routine back to the engine. Lift state is kept.
+    Clear { routine_id: String },
+}
+
+pub async fn sync(state: AppState) -> anyhow::Result<()> {
+    let state = Arc::new(state);
+    run_sync(Arc::clone(&state)).await?;
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let workers = spawn_workers(Arc::clone(&state), shutdown_rx);
+
+    // Jobs that fail are left on the queue with their retry schedule for the
+    // server to pick up
+    loop {
+        let depth = state.job_queue.depth();
+        if depth.queued == 0 && depth.running == 0 {
+            break;
+        }
+        tokio::time::sleep(QUEUE_POLL_INTERVAL).await;
+    }
+
+    let timeout = Duration::from_secs(state.config.shutdown_timeout_secs);
+    shutdown::drain(&shutdown_tx, workers, timeout).await;
+
+    let depth = state.job_queue.depth();
+    println!(
+        "sync complete: {} failed (will retry), {} dead",
+        depth.failed, depth.dead
+    );
+    Ok(())
+}
+
+pub async fn process(state: AppState, workout_id: String, dry_run: bool) -> anyhow::Result<()> {
+    if !dry_run {
+        let outcome = process_single_workout(&state, workout_id.clone()).await?;
+        state
+            .job_queue
+            .record_succeeded(&workout_id, JobSource::Cli)
+            .context("failed to record the workout in the job queue")?;
+        println!("{}", outcome.as_str());
+        return Ok(());
+    }
+
+    let workout = state
+        .hevy_client
+        .get_workout(&workout_id)
+        .await
+        .context("failed to fetch workout")?;
+    if workout.routine_id.is_empty() || workout.routine_id == "null" {
+        println!("workout {} has no routine", workout.id);
+        return Ok(());
+    }
+
+    let RoutineUpdatePlan { routine_update, .. } = plan_routine_update(&state, &workout).await?;
+    println!("{}", serde_json::to_string_pretty(&routine_update)?);
+    Ok(())
+}
+
+pub async fn preview_prompt(state: AppState, workout_id: String) -> anyhow::Result<()> {
+    let workout = state
+        .hevy_client
+        .get_workout(&workout_id)
+        .await
+        .context("failed to fetch workout")?;
+    let routine = state
+        .hevy_client
+        .get_routine(&workout.routine_id)
+        .await
+        .with_context(|| format!("failed to fetch routine {}", workout.routine_id))?;
+
+    let prompt = state
+        .progressive_overload_service
+        .build_progressive_overload_prompt(&workout, &routine)
+        .await?;
+    println!("{}", prompt);
+    Ok(())
+}
+
+pub async fn routines(state: AppState, command: RoutinesCommand) -> anyhow::Result<()> {
+    match command {
+        RoutinesCommand::List => {
+            let mut page = 1;
+            loop {
+                let response = state
+                    .hevy_client
+                    .get_routines(page, ROUTINES_PAGE_SIZE)
+                    .await?;
+                for routine in &response.routines {
+                    println!("{}\t{}", routine.id, routine.title);
+                }
+                if page >= response.page_count {
+                    break;
+                }
+                page += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn program(state: AppState, command: ProgramCommand) -> anyhow::Result<()> {
+    match command {
+        ProgramCommand::List => {
+            let data = state.programs.snapshot();
+            let mut routines: Vec<_> = data.routines.iter().collect();
+            routines.sort_by_key(|(routine_id, _)| *routine_id);
+            for (routine_id, routine) in routines {
+                println!("{}\t{}\tweek {}", routine_id, routine.program, routine.week);
+            }
+
+            let mut lifts: Vec<_> = data
+                .lifts
+                .iter()
+                .flat_map(|(program, lifts)| {
+                    lifts.iter().map(move |(key, lift)| (program, key, lift))
+                })
+                .collect();
+            lifts.sort_by_key(|(program, key, _)| (program.as_str(), *key));
+            for (program, key, lift) in lifts {
+                let weight =
+                    |weight: Option<f32>| weight.map_or("-".to_string(), |kg| format!("{}kg", kg));
+                println!(
+                    "{}\t{}\ttraining max {}\tworking {}\tstage {}\t{} failures",
+                    program,
+                    key,
+                    weight(lift.training_max_kg),
+                    weight(lift.working_weight_kg),
+                    lift.stage,
+                    lift.failures
+                );
+            }
+        }
+        ProgramCommand::Set {
+            routine_id,
+            program,
+        } => {
+            state.programs.assign(&routine_id, program)?;
+            println!("{} now runs {}", routine_id, program);
+        }
+        ProgramCommand::Clear { routine_id } => {
+            if state.programs.unassign(&routine_id)? {
+                println!(
+                    "{} is back on the {} engine",
+                    routine_id, state.config.engine
+                );
+            } else {
+                println!("{} has no program", routine_id);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn stalls(state: AppState) -> anyhow::Result<()> {
+    let data = state.prescriptions.snapshot();
+    let mut misses: Vec<_> = data
+        .routines
+        .iter()
+        .flat_map(|(routine_id, routine)| {
+            routine.misses.iter().map(move |(template_id, count)| {
+                let title = routine
+                    .exercises
+                    .get(template_id)
+                    .map_or("", |prescription| prescription.exercise_title.as_str());
+                (routine_id, template_id, title, *count)
+            })
+        })
+        .collect();
+    misses.sort_by(|a, b| b.3.cmp(&a.3).then((a.0, a.1).cmp(&(b.0, b.1))));
+
+    for (routine_id, template_id, title, count) in misses {
+        let review = if state.config.stall_response == StallResponse::Flag
+            && count >= state.config.stall_threshold
+        {
+            "\tneeds review"
+        } else {
+            ""
+        };
+        println!(
+            "{}\t{}\t{}\t{} missed in a row{}",
+            routine_id, template_id, title, count, review
+        );
+    }
+    Ok(())
+}
+
+pub fn bodyweight(state: AppState, kg: Option<f32>) -> anyhow::Result<()> {
+    if let Some(kg) = kg {
+        anyhow::ensure!(kg > 0.0, "bodyweight must be positive");
+        state.history.record_bodyweight(kg, chrono::Utc::now())?;
+        println!("recorded {}kg", kg);
+        return Ok(());
+    }
+
+    for entry in state.history.snapshot().bodyweight {
+        println!(
+            "{}\t{}kg",
+            entry.recorded_at.format("%Y-%m-%d"),
+            entry.bodyweight_kg
+        );
+    }
+    Ok(())
+}
+
+pub fn history(
+    state: AppState,
+    routine_id: String,
+    limit: usize,
+    json: bool,
+) -> anyhow::Result<()> {
+    let records = state.audit_log.query(&AuditQuery {
+        routine_id: Some(routine_id),
+        limit: Some(limit),
+        ..AuditQuery::default()
+    })?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+        return Ok(());
+    }
+
+    for record in records {
+        let result = match &record.error {
+            Some(error) => format!("failed: {}", error),
+            None => "applied".to_string(),
+        };
+        let adherence = record.adherence.as_ref().map_or("-".to_string(), |report| {
+            format!(
+                "{}/{} sets ({:.0}%)",
+                report.completed_sets,
+                report.prescribed_sets,
+                report.completion() * 100.0
+            )
+        });
+        println!(
+            "{}\t{} -> {}\t{}/{} (prompt v{})\tadherence {}\t{}",
+            record.recorded_at.format("%Y-%m-%d %H:%M"),
+            record.workout_title,
+            record.parsed_response.routine_title,
+            record.engine,
+            record.model,
+            record.prompt_version,
+            adherence,
+            result
+        );
+    }
+    Ok(())
+}
+
+pub async fn backfill(
+    state: AppState,
+    since: Option<NaiveDate>,
+    simulate: bool,
+) -> anyhow::Result<()> {
+    let summary = run_backfill(&state, &BackfillOptions { since, simulate }).await?;
+
+    for step in &summary.steps {
+        println!(
+            "{}\t{} -> {}",
+            step.performed_at, step.workout_title, step.next_routine_title
+        );
+        if let Some(error) = &step.error {
+            println!("  failed: {}", error);
+        }
+        for prescription in &step.prescriptions {
+            println!(
+                "  {}: {}",
+                prescription.exercise_title,
+                prescription.sets.join(", ")
+            );
+        }
+    }
+
+    let history = state.history.snapshot();
+    let mut exercises: Vec<_> = history.exercises.values().collect();
+    exercises.sort_by(|a, b| a.title.cmp(&b.title));
+    for exercise in exercises {
+        println!(
+            "{}\t{} sessions\tblock {} week {}\tbest e1RM {}{}",
+            exercise.title,
+            exercise.sessions.len(),
+            exercise.block.block,
+            exercise.block.week,
+            exercise
+                .best_e1rm_kg
+                .map_or_else(|| "-".to_string(), |e1rm| format!("{:.1}kg", e1rm)),
+            exercise
+                .best_relative_e1rm
+                .map_or_else(String::new, |relative| format!(" ({:.2}x BW)", relative)),
+        );
+    }
+
+    println!(
+        "backfill complete: {} workouts, {} new exercise sessions",
+        summary.workouts_fetched, summary.sessions_recorded
+    );
+    Ok(())
+}
+
+pub async fn simulate(
+    state: AppState,
+    fixtures: PathBuf,
+    engines: Vec<Engine>,
+    json: bool,
+) -> anyhow::Result<()> {
+    let fixtures = load_fixtures(&fixtures)?;
+    let services: Vec<_> = engines
+        .into_iter()
+        .map(|engine| state.progressive_overload_service.with_engine(engine))
+        .collect();
+
+    let report = run_simulation(&services, &fixtures).await;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", render_table(&report));
+    }
+    Ok(())
+}
+
+pub async fn record_fixture(
+    state: AppState,
+    workout_id: String,
+    output: PathBuf,
+) -> anyhow::Result<()> {
+    let workout = state
+        .hevy_client
+        .get_workout(&workout_id)
+        .await
+        .context("failed to fetch workout")?;
+    let routine = state
+        .hevy_client
+        .get_routine(&workout.routine_id)
+        .await
+        .with_context(|| format!("failed to fetch routine {}", workout.routine_id))?;
+
+    let fixture = SimulationFixture { workout, routine };
+    std::fs::write(&output, serde_json::to_vec_pretty(&fixture)?)
+        .with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("wrote {}", output.display());
+    Ok(())
+}
@@ -1,6 +1,12 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use gemini_rust::{Gemini, Tool};
 use std::fmt;
+use std::time::Instant;
+use tracing::Instrument;
+
+use crate::clients::llm::LlmClient;
+use crate::metrics::metrics;
 
 #[derive(Clone)]
 pub struct GeminiClient {
@@ -23,6 +29,10 @@ impl GeminiClient {
         Self { client, model }
     }
 
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
     #[allow(dead_code)]
     pub async fn generate_text_with_search(&self, prompt: &str) -> Result<String> {
         let google_search_tool = Tool::google_search();
@@ -41,9 +51,57 @@ impl GeminiClient {
     pub async fn generate_text(&self, prompt: &str) -> Result<String> {
         let request_builder = self.client.generate_content().with_user_message(prompt);
 
-        let response = request_builder.execute().await?;
+        let span = tracing::info_span!(
+            "llm.generate",
+            otel.kind = "client",
+            model = %self.model,
+            prompt_chars = prompt.len(),
+            response_chars = tracing::field::Empty,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+        );
+
+        let started = Instant::now();
+        let result = request_builder.execute().instrument(span.clone()).await;
+        let status = if result.is_ok() { "ok" } else { "error" };
+        metrics().observe_llm_request(&self.model, status, started.elapsed());
+
+        let response = result?;
+
+        if let Some(usage) = &response.usage_metadata {
+            span.record("prompt_tokens", usage.prompt_token_count.unwrap_or(0));
+            span.record(
+                "completion_tokens",
+                usage.candidates_token_count.unwrap_or(0),
+            );
+
+            let metrics = metrics();
+            metrics.add_llm_tokens(&self.model, "prompt", usage.prompt_token_count.unwrap_or(0));
+            metrics.add_llm_tokens(
+                &self.model,
+                "completion",
+                usage.candidates_token_count.unwrap_or(0),
+            );
+            metrics.add_llm_tokens(
+                &self.model,
+                "thoughts",
+                usage.thoughts_token_count.unwrap_or(0),
+            );
+        }
 
         let text = response.text();
+        span.record("response_chars", text.len());
         Ok(text)
     }
 }
+
+#[async_trait]
+impl LlmClient for GeminiClient {
+    fn model(&self) -> &str {
+        GeminiClient::model(self)
+    }
+
+    async fn generate_text(&self, prompt: &str) -> Result<String> {
+        GeminiClient::generate_text(self, prompt).await
+    }
+}
@@ -1,20 +1,29 @@
 use crate::clients::models::requests::{RoutineUpdate, UpdateRoutineRequest};
 use crate::clients::models::responses::{
-    RoutineApiResponse, RoutineResponse, RoutineUpdateApiResponse, WorkoutResponse,
-    WorkoutsListResponse,
+    ExerciseTemplate, RoutineApiResponse, RoutineResponse, RoutineUpdateApiResponse,
+    RoutinesListResponse, WorkoutResponse, WorkoutsListResponse,
 };
 use crate::config::Config;
+use crate::metrics::metrics;
 use anyhow::Result;
-use reqwest::{Client, Url};
+use reqwest::{Client, RequestBuilder, Response, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tracing::Instrument;
 
 const WORKOUTS_ENDPOINT: &str = "/v1/workouts/";
 const ROUTINES_ENDPOINT: &str = "/v1/routines/";
+const EXERCISE_TEMPLATES_ENDPOINT: &str = "/v1/exercise_templates/";
 
 #[derive(Clone)]
 pub struct HevyClient {
     http: Client,
     base: Url,
     api_key: String,
+    /// Templates don't change once created, so each is fetched once per
+    /// process.
+    exercise_templates: Arc<Mutex<HashMap<String, ExerciseTemplate>>>,
 }
 
 impl HevyClient {
@@ -25,16 +34,16 @@ impl HevyClient {
                 .build()?,
             base: Url::parse(&config.hevy_api_url)?,
             api_key: config.hevy_api_key.clone(),
+            exercise_templates: Arc::default(),
         })
     }
 
     pub async fn get_workout(&self, workout_id: &str) -> Result<WorkoutResponse> {
-        let api_key = &self.api_key;
         let url = self
             .base
             .join(&format!("{}{}", WORKOUTS_ENDPOINT, workout_id))?;
 
-        let response = self.http.get(url).header("api-key", api_key).send().await?;
+        let response = self.send("get_workout", self.http.get(url)).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -53,13 +62,12 @@ impl HevyClient {
     }
 
     pub async fn get_workouts(&self, page: i32, page_size: i32) -> Result<WorkoutsListResponse> {
-        let api_key = &self.api_key;
         let mut url = self.base.join("/v1/workouts")?;
         url.query_pairs_mut()
             .append_pair("page", &page.to_string())
             .append_pair("pageSize", &page_size.to_string());
 
-        let response = self.http.get(url).header("api-key", api_key).send().await?;
+        let response = self.send("get_workouts", self.http.get(url)).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -79,12 +87,11 @@ impl HevyClient {
     }
 
     pub async fn get_routine(&self, routine_id: &str) -> Result<RoutineResponse> {
-        let api_key = &self.api_key;
         let url = self
             .base
             .join(&format!("{}{}", ROUTINES_ENDPOINT, routine_id))?;
 
-        let response = self.http.get(url).header("api-key", api_key).send().await?;
+        let response = self.send("get_routine", self.http.get(url)).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -105,12 +112,92 @@ impl HevyClient {
         Ok(routine)
     }
 
+    pub async fn get_routines(&self, page: i32, page_size: i32) -> Result<RoutinesListResponse> {
+        let mut url = self.base.join("/v1/routines")?;
+        url.query_pairs_mut()
+            .append_pair("page", &page.to_string())
+            .append_pair("pageSize", &page_size.to_string());
+
+        let response = self.send("get_routines", self.http.get(url)).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::anyhow!(
+                "API request failed with status {}: {}",
+                status,
+                body
+            ));
+        }
+
+        let body = response.text().await?;
+        let api_response: RoutinesListResponse = serde_json::from_str(&body)
+            .map_err(|e| anyhow::anyhow!("Failed to parse routines list response: {}", e))?;
+
+        Ok(api_response)
+    }
+
+    pub async fn get_exercise_template(&self, template_id: &str) -> Result<ExerciseTemplate> {
+        if let Some(template) = self.exercise_templates.lock().unwrap().get(template_id) {
+            return Ok(template.clone());
+        }
+
+        let url = self
+            .base
+            .join(&format!("{}{}", EXERCISE_TEMPLATES_ENDPOINT, template_id))?;
+
+        let response = self
+            .send("get_exercise_template", self.http.get(url))
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await?;
+            return Err(anyhow::anyhow!(
+                "API request failed with status {}: {}",
+                status,
+                body
+            ));
+        }
+
+        let body = response.text().await?;
+        let template: ExerciseTemplate = serde_json::from_str(&body)
+            .map_err(|e| anyhow::anyhow!("Failed to parse exercise template response: {}", e))?;
+
+        self.exercise_templates
+            .lock()
+            .unwrap()
+            .insert(template_id.to_string(), template.clone());
+        Ok(template)
+    }
+
+    /// Template types by id for `template_ids`. Templates that can't be
+    /// fetched are left out, so callers fall back to the logged sets.
+    pub async fn get_exercise_types(&self, template_ids: &[&str]) -> HashMap<String, String> {
+        let mut types = HashMap::new();
+        for &template_id in template_ids {
+            if types.contains_key(template_id) {
+                continue;
+            }
+            match self.get_exercise_template(template_id).await {
+                Ok(template) => {
+                    types.insert(template_id.to_string(), template.exercise_type);
+                }
+                Err(e) => tracing::debug!(
+                    exercise_template_id = %template_id,
+                    error = %e,
+                    "exercise_template.lookup_failed"
+                ),
+            }
+        }
+        types
+    }
+
     pub async fn update_routine(
         &self,
         routine_id: &str,
         request: RoutineUpdate,
     ) -> Result<RoutineResponse> {
-        let api_key = &self.api_key;
         let url = self
             .base
             .join(&format!("{}{}", ROUTINES_ENDPOINT, routine_id))?;
@@ -124,14 +211,12 @@ impl HevyClient {
             "hevy.update_routine.request"
         );
 
-        let response = self
+        let request = self
             .http
             .put(url)
-            .header("api-key", api_key)
             .header("Content-Type", "application/json")
-            .body(json_body)
-            .send()
-            .await?;
+            .body(json_body);
+        let response = self.send("update_routine", request).await?;
 
         if !response.status().is_success() {
             let status = response.status();
@@ -159,4 +244,29 @@ impl HevyClient {
             .ok_or_else(|| anyhow::anyhow!("API returned empty routine array"))?;
         Ok(routine)
     }
+
+    async fn send(&self, endpoint: &'static str, request: RequestBuilder) -> Result<Response> {
+        let span = tracing::info_span!(
+            "hevy.request",
+            endpoint,
+            otel.kind = "client",
+            http.response.status_code = tracing::field::Empty,
+        );
+
+        let started = Instant::now();
+        let result = request
+            .header("api-key", &self.api_key)
+            .send()
+            .instrument(span.clone())
+            .await;
+
+        let status = match &result {
+            Ok(response) => response.status().as_u16().to_string(),
+            Err(_) => "error".to_string(),
+        };
+        span.record("http.response.status_code", status.as_str());
+        metrics().observe_hevy_request(endpoint, &status, started.elapsed());
+
+        Ok(result?)
+    }
 }
@@ -0,0 +1,11 @@
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// Text-in, text-out model client used by the LLM engine. Implemented by
+/// `GeminiClient` and, in tests, by a scripted fake.
+#[async_trait]
+pub trait LlmClient: Send + Sync {
+    fn model(&self) -> &str;
+
+    async fn generate_text(&self, prompt: &str) -> Result<String>;
+}
@@ -1,3 +1,4 @@
 pub mod gemini;
 pub mod hevy;
+pub mod llm;
 pub mod models;
@@ -22,9 +22,12 @@ pub struct ExerciseSet {
     pub duration_seconds: Option<u32>,
     pub rpe: Option<f32>,
     pub custom_metric: Option<f32>,
+    /// Target rep range, set on routine sets that use double progression.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rep_range: Option<RepRange>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExerciseSetForUpdate {
     #[serde(rename = "type")]
     pub set_type: String, // "warmup", "normal", "failure", "dropset"
@@ -38,13 +41,13 @@ pub struct ExerciseSetForUpdate {
     // Note: rpe field intentionally omitted as it's not allowed in API updates
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RepRange {
     pub start: Option<u32>,
     pub end: Option<u32>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExerciseForUpdate {
     pub exercise_template_id: String,
     pub superset_id: Option<u32>,
@@ -54,6 +57,11 @@ pub struct ExerciseForUpdate {
 }
 
 impl Exercise {
+    /// Every set but the warmups.
+    pub fn working_sets(&self) -> impl Iterator<Item = &ExerciseSet> {
+        self.sets.iter().filter(|set| !set.is_warmup())
+    }
+
     pub fn to_update_format(&self) -> ExerciseForUpdate {
         ExerciseForUpdate {
             exercise_template_id: self.exercise_template_id.clone(),
@@ -66,6 +74,10 @@ impl Exercise {
 }
 
 impl ExerciseSet {
+    pub fn is_warmup(&self) -> bool {
+        self.set_type.eq_ignore_ascii_case("warmup")
+    }
+
     pub fn to_update_format(&self) -> ExerciseSetForUpdate {
         ExerciseSetForUpdate {
             set_type: self.set_type.clone(),
@@ -74,7 +86,13 @@ impl ExerciseSet {
             distance_meters: self.distance_meters,
             duration_seconds: self.duration_seconds,
             custom_metric: self.custom_metric,
-            rep_range: None,
+            rep_range: self.rep_range.clone(),
         }
     }
 }
+
+impl ExerciseSetForUpdate {
+    pub fn is_warmup(&self) -> bool {
+        self.set_type.eq_ignore_ascii_case("warmup")
+    }
+}
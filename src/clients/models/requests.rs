@@ -1,8 +1,8 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::clients::models::common::ExerciseForUpdate;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutineUpdate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
@@ -14,7 +14,7 @@ pub struct RoutineUpdate {
     pub exercises: Option<Vec<ExerciseForUpdate>>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct UpdateRoutineRequest {
     pub routine: RoutineUpdate,
 }
@@ -19,6 +19,8 @@ pub struct WorkoutResponse {
 pub struct RoutineResponse {
     pub id: String,
     pub title: String,
+    #[serde(default)]
+    pub notes: Option<String>,
     pub folder_id: Option<String>,
     pub updated_at: String,
     pub created_at: String,
@@ -43,6 +45,28 @@ pub struct WorkoutsListResponse {
     pub workouts: Vec<WorkoutResponse>,
     #[allow(dead_code)]
     pub page: i32,
+    pub page_count: i32,
+}
+
+// Routines list response from GET /v1/routines
+#[derive(Debug, Deserialize)]
+pub struct RoutinesListResponse {
+    pub routines: Vec<RoutineResponse>,
     #[allow(dead_code)]
+    pub page: i32,
     pub page_count: i32,
 }
+
+// Exercise template from GET /v1/exercise_templates/{id}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExerciseTemplate {
+    pub id: String,
+    pub title: String,
+    /// How sets are logged, e.g. "weight_reps", "distance_duration" or
+    /// "duration".
+    #[serde(rename = "type")]
+    pub exercise_type: String,
+    pub primary_muscle_group: Option<String>,
+    #[serde(default)]
+    pub is_custom: bool,
+}
@@ -1,33 +1,342 @@
 use std::env;
+use std::path::PathBuf;
 
-#[derive(Debug, Clone)]
+use crate::services::double_progression::RepTarget;
+use crate::services::engine::Engine;
+use crate::services::stalls::StallResponse;
+use crate::services::substitutions::{ExerciseEquivalences, SubstitutionPolicy};
+use crate::services::update_mode::UpdateMode;
+
+#[derive(Debug, Clone, Default)]
 pub struct Config {
     pub hevy_api_key: String,
     pub hevy_api_url: String,
-    pub webhook_token: String,
+    pub webhook_tokens: Vec<String>,
+    pub webhook_signing_secret: Option<String>,
+    pub webhook_signature_tolerance_secs: i64,
     pub port: String,
     pub gemini_api_key: String,
     pub gemini_model: String,
+    pub engine: Engine,
+    pub data_dir: PathBuf,
+    pub worker_concurrency: usize,
+    pub job_max_attempts: u32,
+    pub job_retry_base_secs: u64,
+    pub shutdown_timeout_secs: u64,
+    pub sync: SyncConfig,
+    pub double_progression_exercises: Vec<String>,
+    pub double_progression_rep_range: RepTarget,
+    pub double_progression_increment_kg: f32,
+    pub stall_threshold: u32,
+    pub stall_response: StallResponse,
+    pub stall_reduce_pct: f32,
+    pub conditioning_max_weekly_increase_pct: f32,
+    pub bodyweight_rep_range: RepTarget,
+    pub bodyweight_increment_kg: f32,
+    pub rest_heavy_increase_pct: f32,
+    pub rest_deload_decrease_pct: f32,
+    pub conditioning_pace_step_pct: f32,
+    pub substitution_policy: SubstitutionPolicy,
+    pub exercise_equivalences: ExerciseEquivalences,
+    pub update_mode: UpdateMode,
+    pub otel_enabled: bool,
+    pub otel_service_name: String,
+    pub audit_redact_llm_text: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncConfig {
+    pub enabled: bool,
+    pub cron_expressions: Vec<String>,
+    pub lookback_hours: i64,
+    pub page_size: i32,
+    pub run_on_startup: bool,
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// Local-time hour window (start inclusive, end exclusive) during which the
+/// periodic sync is skipped. Wraps past midnight when `start > end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start_hour: u32,
+    pub end_hour: u32,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cron_expressions: vec!["0 */15 * * * *".to_string()],
+            lookback_hours: 24,
+            page_size: 10,
+            run_on_startup: true,
+            quiet_hours: None,
+        }
+    }
+}
+
+impl SyncConfig {
+    fn from_env() -> anyhow::Result<Self> {
+        let defaults = Self::default();
+
+        let cron_expressions = match env::var("SYNC_CRON") {
+            // Cron fields are separated by spaces and may contain commas, so
+            // multiple expressions are separated by semicolons
+            Ok(value) => value
+                .split(';')
+                .map(str::trim)
+                .filter(|expression| !expression.is_empty())
+                .map(str::to_string)
+                .collect(),
+            Err(_) => defaults.cron_expressions,
+        };
+
+        let quiet_hours = env::var("SYNC_QUIET_HOURS")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .map(|value| QuietHours::parse(&value))
+            .transpose()?;
+
+        Ok(Self {
+            enabled: parse_env_or("SCHEDULER_ENABLED", defaults.enabled)?,
+            cron_expressions,
+            lookback_hours: parse_env_or("SYNC_LOOKBACK_HOURS", defaults.lookback_hours)?,
+            page_size: parse_env_or("SYNC_PAGE_SIZE", defaults.page_size)?.clamp(1, 10),
+            run_on_startup: parse_env_or("SYNC_ON_STARTUP", defaults.run_on_startup)?,
+            quiet_hours,
+        })
+    }
+}
+
+impl QuietHours {
+    /// Parses `"22-6"` style ranges of whole hours.
+    pub fn parse(value: &str) -> anyhow::Result<Self> {
+        let (start, end) = value.split_once('-').ok_or_else(|| {
+            anyhow::anyhow!("Invalid SYNC_QUIET_HOURS '{}', expected e.g. 22-6", value)
+        })?;
+
+        let parse_hour = |hour: &str| -> anyhow::Result<u32> {
+            let hour: u32 = hour
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("Invalid hour '{}' in SYNC_QUIET_HOURS", hour))?;
+            if hour > 23 {
+                return Err(anyhow::anyhow!(
+                    "Hour {} in SYNC_QUIET_HOURS is out of range",
+                    hour
+                ));
+            }
+            Ok(hour)
+        };
+
+        Ok(Self {
+            start_hour: parse_hour(start)?,
+            end_hour: parse_hour(end)?,
+        })
+    }
+
+    pub fn contains(&self, hour: u32) -> bool {
+        if self.start_hour <= self.end_hour {
+            hour >= self.start_hour && hour < self.end_hour
+        } else {
+            hour >= self.start_hour || hour < self.end_hour
+        }
+    }
 }
 
 impl Config {
     pub fn from_env() -> anyhow::Result<Self> {
         let hevy_api_key = env::var("HEVY_API_KEY")?;
-        let webhook_token = env::var("WEBHOOK_TOKEN")?;
+        let webhook_tokens = parse_webhook_tokens(
+            env::var("WEBHOOK_TOKEN").ok().as_deref(),
+            env::var("WEBHOOK_TOKENS").ok().as_deref(),
+        );
+        if webhook_tokens.is_empty() {
+            return Err(anyhow::anyhow!(
+                "WEBHOOK_TOKEN or WEBHOOK_TOKENS must contain at least one token"
+            ));
+        }
+        let webhook_signing_secret = env::var("WEBHOOK_SIGNING_SECRET")
+            .ok()
+            .filter(|secret| !secret.is_empty());
+        let webhook_signature_tolerance_secs =
+            parse_env_or("WEBHOOK_SIGNATURE_TOLERANCE_SECS", 300)?;
         let port = env::var("PORT").unwrap_or_else(|_| "3005".to_string());
         let gemini_api_key = env::var("GEMINI_API_KEY")?;
         let gemini_model =
             env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-2.5-pro".to_string());
+        let engine = parse_env_or("ENGINE", Engine::default())?;
         let hevy_api_url =
             env::var("HEVY_API_URL").unwrap_or_else(|_| "https://api.hevyapp.com".to_string());
+        let data_dir = PathBuf::from(env::var("DATA_DIR").unwrap_or_else(|_| "data".to_string()));
+        let worker_concurrency = parse_env_or("WORKER_CONCURRENCY", 2)?;
+        let job_max_attempts = parse_env_or("JOB_MAX_ATTEMPTS", 5)?;
+        let job_retry_base_secs = parse_env_or("JOB_RETRY_BASE_SECS", 30)?;
+        let shutdown_timeout_secs = parse_env_or("SHUTDOWN_TIMEOUT_SECS", 30)?;
+        let sync = SyncConfig::from_env()?;
+        let double_progression_exercises = env::var("DOUBLE_PROGRESSION_EXERCISES")
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let double_progression_rep_range =
+            parse_env_or("DOUBLE_PROGRESSION_REP_RANGE", RepTarget::default())?;
+        let double_progression_increment_kg = parse_env_or("DOUBLE_PROGRESSION_INCREMENT_KG", 2.5)?;
+        let stall_threshold = parse_env_or("STALL_THRESHOLD", 3)?;
+        let stall_response = parse_env_or("STALL_RESPONSE", StallResponse::default())?;
+        let stall_reduce_pct = parse_env_or("STALL_REDUCE_PCT", 10.0)?;
+        let conditioning_max_weekly_increase_pct =
+            parse_env_or("CONDITIONING_MAX_WEEKLY_INCREASE_PCT", 10.0)?;
+        let conditioning_pace_step_pct = parse_env_or("CONDITIONING_PACE_STEP_PCT", 2.0)?;
+        let bodyweight_rep_range =
+            parse_env_or("BODYWEIGHT_REP_RANGE", RepTarget { min: 6, max: 12 })?;
+        let bodyweight_increment_kg = parse_env_or("BODYWEIGHT_INCREMENT_KG", 2.5)?;
+        let rest_heavy_increase_pct = parse_env_or("REST_HEAVY_INCREASE_PCT", 25.0)?;
+        let rest_deload_decrease_pct = parse_env_or("REST_DELOAD_DECREASE_PCT", 25.0)?;
+        let substitution_policy =
+            parse_env_or("SUBSTITUTION_POLICY", SubstitutionPolicy::default())?;
+        let exercise_equivalences =
+            parse_env_or("EXERCISE_EQUIVALENCES", ExerciseEquivalences::default())?;
+        let update_mode = parse_env_or("UPDATE_MODE", UpdateMode::default())?;
+        // The OTLP exporter reads the endpoint itself; we only need to know
+        // whether one was configured
+        let otel_enabled = [
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+        ]
+        .iter()
+        .any(|name| env::var(name).is_ok_and(|value| !value.is_empty()));
+        let otel_service_name = env::var("OTEL_SERVICE_NAME")
+            .unwrap_or_else(|_| "hevy-progressive-overloader".to_string());
+        let audit_redact_llm_text = parse_env_or("AUDIT_REDACT_LLM_TEXT", false)?;
 
         Ok(Self {
             hevy_api_key,
-            webhook_token,
+            webhook_tokens,
+            webhook_signing_secret,
+            webhook_signature_tolerance_secs,
             port,
             hevy_api_url,
             gemini_api_key,
             gemini_model,
+            engine,
+            data_dir,
+            worker_concurrency,
+            job_max_attempts,
+            job_retry_base_secs,
+            shutdown_timeout_secs,
+            sync,
+            double_progression_exercises,
+            double_progression_rep_range,
+            double_progression_increment_kg,
+            stall_threshold,
+            stall_response,
+            stall_reduce_pct,
+            conditioning_max_weekly_increase_pct,
+            conditioning_pace_step_pct,
+            bodyweight_rep_range,
+            bodyweight_increment_kg,
+            rest_heavy_increase_pct,
+            rest_deload_decrease_pct,
+            substitution_policy,
+            exercise_equivalences,
+            update_mode,
+            otel_enabled,
+            otel_service_name,
+            audit_redact_llm_text,
         })
     }
+
+    pub fn jobs_path(&self) -> PathBuf {
+        self.data_dir.join("jobs.json")
+    }
+
+    pub fn status_path(&self) -> PathBuf {
+        self.data_dir.join("status.json")
+    }
+
+    pub fn audit_path(&self) -> PathBuf {
+        self.data_dir.join("audit.jsonl")
+    }
+
+    pub fn history_path(&self) -> PathBuf {
+        self.data_dir.join("history.json")
+    }
+
+    pub fn programs_path(&self) -> PathBuf {
+        self.data_dir.join("programs.json")
+    }
+
+    pub fn prescriptions_path(&self) -> PathBuf {
+        self.data_dir.join("prescriptions.json")
+    }
+}
+
+fn parse_env_or<T>(name: &str, default: T) -> anyhow::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    match env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid value for {}: {}", name, e)),
+        Err(_) => Ok(default),
+    }
+}
+
+// WEBHOOK_TOKENS is a comma-separated list so a new token can be rolled out
+// before the old one is retired. WEBHOOK_TOKEN is kept for existing deployments.
+fn parse_webhook_tokens(single: Option<&str>, multiple: Option<&str>) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+
+    for token in single
+        .into_iter()
+        .chain(multiple.into_iter().flat_map(|value| value.split(',')))
+    {
+        let token = token.trim();
+        if !token.is_empty() && !tokens.iter().any(|existing| existing == token) {
+            tokens.push(token.to_string());
+        }
+    }
+
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_webhook_tokens() {
+        assert_eq!(parse_webhook_tokens(Some("abc"), None), vec!["abc"]);
+        assert_eq!(
+            parse_webhook_tokens(Some("abc"), Some("new, abc ,,old")),
+            vec!["abc", "new", "old"]
+        );
+        assert!(parse_webhook_tokens(Some(""), Some(" , ")).is_empty());
+    }
+
+    #[test]
+    fn test_quiet_hours() {
+        let overnight = QuietHours::parse("22-6").unwrap();
+        assert!(overnight.contains(23));
+        assert!(overnight.contains(0));
+        assert!(overnight.contains(5));
+        assert!(!overnight.contains(6));
+        assert!(!overnight.contains(12));
+
+        let afternoon = QuietHours::parse(" 13 - 15 ").unwrap();
+        assert!(afternoon.contains(14));
+        assert!(!afternoon.contains(15));
+
+        assert!(QuietHours::parse("22").is_err());
+        assert!(QuietHours::parse("22-24").is_err());
+    }
 }
@@ -0,0 +1,446 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::clients::models::{common::Exercise, responses::WorkoutResponse};
+use crate::services::ai_parser;
+use crate::services::bodyweight::LoadKind;
+use crate::store;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordKind {
+    Weight,
+    E1rm,
+    Volume,
+}
+
+/// One exercise as performed in one workout. Warmups are left out of every
+/// figure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExerciseSession {
+    pub workout_id: String,
+    pub performed_at: DateTime<Utc>,
+    pub block: u32,
+    pub week: u32,
+    pub top_weight_kg: Option<f32>,
+    pub top_set_reps: Option<u32>,
+    pub best_e1rm_kg: Option<f32>,
+    /// The lifter's bodyweight at the time, if any had been recorded.
+    #[serde(default)]
+    pub bodyweight_kg: Option<f32>,
+    /// Best e1RM as a multiple of bodyweight.
+    #[serde(default)]
+    pub relative_e1rm: Option<f32>,
+    pub volume_kg: f32,
+    pub working_sets: u32,
+    pub records: Vec<RecordKind>,
+}
+
+/// Position in the training block, derived from the "Week N" in workout
+/// titles. A lower week than last time means a new block has started.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct BlockState {
+    pub block: u32,
+    pub week: u32,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ExerciseHistory {
+    pub title: String,
+    pub block: BlockState,
+    pub best_weight_kg: Option<f32>,
+    pub best_e1rm_kg: Option<f32>,
+    pub best_volume_kg: Option<f32>,
+    #[serde(default)]
+    pub best_relative_e1rm: Option<f32>,
+    pub sessions: Vec<ExerciseSession>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BodyweightEntry {
+    pub recorded_at: DateTime<Utc>,
+    pub bodyweight_kg: f32,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HistoryData {
+    pub exercises: HashMap<String, ExerciseHistory>,
+    /// Oldest first.
+    #[serde(default)]
+    pub bodyweight: Vec<BodyweightEntry>,
+}
+
+impl HistoryData {
+    /// The last bodyweight recorded at or before `at`, or the first one ever
+    /// recorded for workouts older than that.
+    pub fn bodyweight_at(&self, at: DateTime<Utc>) -> Option<f32> {
+        self.bodyweight
+            .iter()
+            .rev()
+            .find(|entry| entry.recorded_at <= at)
+            .or(self.bodyweight.first())
+            .map(|entry| entry.bodyweight_kg)
+    }
+}
+
+/// Per-exercise training history keyed by exercise template id, persisted so
+/// it only has to be backfilled once.
+#[derive(Clone)]
+pub struct HistoryStore {
+    path: PathBuf,
+    data: Arc<Mutex<HistoryData>>,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: Arc::new(Mutex::new(store::load_json(path)?)),
+        })
+    }
+
+    pub fn snapshot(&self) -> HistoryData {
+        self.data.lock().unwrap().clone()
+    }
+
+    /// Adds workouts in any order and persists once. Workouts already in the
+    /// history are skipped, so replays are safe.
+    /// Exercises in `load_kinds` count bodyweight towards their e1RM and
+    /// volume. Returns the number of new exercise sessions recorded.
+    pub fn record_workouts(
+        &self,
+        workouts: &[WorkoutResponse],
+        load_kinds: &HashMap<String, LoadKind>,
+    ) -> Result<usize> {
+        let mut data = self.data.lock().unwrap();
+        let recorded = workouts
+            .iter()
+            .map(|workout| record_workout(&mut data, workout, load_kinds))
+            .sum();
+
+        if recorded > 0 {
+            store::save_json(&self.path, &*data)?;
+        }
+        Ok(recorded)
+    }
+
+    pub fn record_bodyweight(&self, bodyweight_kg: f32, recorded_at: DateTime<Utc>) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.bodyweight.push(BodyweightEntry {
+            recorded_at,
+            bodyweight_kg,
+        });
+        data.bodyweight.sort_by_key(|entry| entry.recorded_at);
+        store::save_json(&self.path, &*data)
+    }
+}
+
+fn record_workout(
+    data: &mut HistoryData,
+    workout: &WorkoutResponse,
+    load_kinds: &HashMap<String, LoadKind>,
+) -> usize {
+    let Some(performed_at) = performed_at(workout) else {
+        tracing::warn!(workout_id = %workout.id, "history.workout_without_date");
+        return 0;
+    };
+    let (week, _) = ai_parser::extract_week_and_day(&workout.title);
+    let bodyweight = data.bodyweight_at(performed_at);
+
+    let mut recorded = 0;
+    for exercise in &workout.exercises {
+        let history = data
+            .exercises
+            .entry(exercise.exercise_template_id.clone())
+            .or_default();
+
+        if history
+            .sessions
+            .iter()
+            .any(|session| session.workout_id == workout.id)
+        {
+            continue;
+        }
+
+        let load_kind = load_kinds.get(&exercise.exercise_template_id).copied();
+        let Some(mut session) =
+            summarize(exercise, &workout.id, performed_at, load_kind, bodyweight)
+        else {
+            continue;
+        };
+        session.week = week;
+
+        // Workouts can arrive out of order, e.g. a backfill after the server
+        // has recorded newer ones, so sessions are kept sorted by date and
+        // everything derived from their order is worked out again
+        let position = history
+            .sessions
+            .partition_point(|existing| existing.performed_at <= performed_at);
+        if position == history.sessions.len() {
+            history.title = exercise.title.clone();
+        }
+        history.sessions.insert(position, session);
+        recompute(history);
+        recorded += 1;
+    }
+
+    recorded
+}
+
+/// Numbers blocks and marks records across the sessions in date order. A
+/// lower week than the session before starts a new block.
+fn recompute(history: &mut ExerciseHistory) {
+    history.block = BlockState::default();
+    history.best_weight_kg = None;
+    history.best_e1rm_kg = None;
+    history.best_volume_kg = None;
+    history.best_relative_e1rm = None;
+
+    for (index, session) in history.sessions.iter_mut().enumerate() {
+        if session.week < history.block.week || history.block.block == 0 {
+            history.block.block += 1;
+        }
+        history.block.week = session.week;
+        session.block = history.block.block;
+
+        session.records.clear();
+        for (kind, best, value) in [
+            (
+                RecordKind::Weight,
+                &mut history.best_weight_kg,
+                session.top_weight_kg,
+            ),
+            (
+                RecordKind::E1rm,
+                &mut history.best_e1rm_kg,
+                session.best_e1rm_kg,
+            ),
+            (
+                RecordKind::Volume,
+                &mut history.best_volume_kg,
+                Some(session.volume_kg).filter(|volume| *volume > 0.0),
+            ),
+        ] {
+            let Some(value) = value else {
+                continue;
+            };
+            if best.is_none_or(|best| value > best) {
+                // The first session only sets the baseline
+                if index > 0 {
+                    session.records.push(kind);
+                }
+                *best = Some(value);
+            }
+        }
+
+        if let Some(relative) = session.relative_e1rm
+            && history
+                .best_relative_e1rm
+                .is_none_or(|best| relative > best)
+        {
+            history.best_relative_e1rm = Some(relative);
+        }
+    }
+}
+
+pub fn performed_at(workout: &WorkoutResponse) -> Option<DateTime<Utc>> {
+    [&workout.start_time, &workout.created_at]
+        .into_iter()
+        .find_map(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn summarize(
+    exercise: &Exercise,
+    workout_id: &str,
+    performed_at: DateTime<Utc>,
+    load_kind: Option<LoadKind>,
+    bodyweight: Option<f32>,
+) -> Option<ExerciseSession> {
+    let working_sets: Vec<_> = exercise.working_sets().collect();
+    if working_sets.is_empty() {
+        return None;
+    }
+
+    let mut session = ExerciseSession {
+        workout_id: workout_id.to_string(),
+        performed_at,
+        block: 0,
+        week: 0,
+        top_weight_kg: None,
+        top_set_reps: None,
+        best_e1rm_kg: None,
+        bodyweight_kg: bodyweight,
+        relative_e1rm: None,
+        volume_kg: 0.0,
+        working_sets: working_sets.len() as u32,
+        records: Vec::new(),
+    };
+
+    for set in working_sets {
+        let Some(reps) = set.reps.filter(|reps| *reps > 0) else {
+            continue;
+        };
+
+        // Assistance isn't load, so it never counts as a top weight
+        if load_kind != Some(LoadKind::Assisted)
+            && let Some(weight) = set.weight_kg.filter(|weight| *weight > 0.0)
+            && session.top_weight_kg.is_none_or(|top| weight > top)
+        {
+            session.top_weight_kg = Some(weight);
+            session.top_set_reps = Some(reps);
+        }
+
+        // Bodyweight exercises only have a load once bodyweight is known
+        let load = match load_kind {
+            Some(kind) => {
+                bodyweight.map(|bodyweight| kind.effective_load(bodyweight, set.weight_kg))
+            }
+            None => set.weight_kg,
+        };
+        let Some(load) = load.filter(|load| *load > 0.0) else {
+            continue;
+        };
+
+        session.volume_kg += load * reps as f32;
+        let e1rm = estimated_one_rep_max(load, reps);
+        if session.best_e1rm_kg.is_none_or(|best| e1rm > best) {
+            session.best_e1rm_kg = Some(e1rm);
+        }
+    }
+
+    session.relative_e1rm = session
+        .best_e1rm_kg
+        .zip(bodyweight)
+        .map(|(e1rm, bodyweight)| e1rm / bodyweight);
+
+    Some(session)
+}
+
+/// Epley estimate. A single is taken at face value.
+pub fn estimated_one_rep_max(weight_kg: f32, reps: u32) -> f32 {
+    if reps <= 1 {
+        weight_kg
+    } else {
+        weight_kg * (1.0 + reps as f32 / 30.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{self, exercise, set};
+
+    fn workout(
+        id: &str,
+        title: &str,
+        start_time: &str,
+        sets: &[(&str, f32, u32)],
+    ) -> WorkoutResponse {
+        let sets = sets
+            .iter()
+            .map(|&(set_type, weight, reps)| set(set_type, Some(weight), reps))
+            .collect();
+        WorkoutResponse {
+            start_time: start_time.to_string(),
+            ..mock_hevy::workout(id, title, "r1", vec![exercise("squat", "Squat", sets)])
+        }
+    }
+
+    #[test]
+    fn test_records_sessions_prs_and_blocks() {
+        let mut data = HistoryData::default();
+        let workouts = [
+            workout(
+                "w1",
+                "Day 1 - Week 7",
+                "2025-01-01T10:00:00Z",
+                &[("warmup", 60.0, 5), ("normal", 100.0, 5)],
+            ),
+            workout(
+                "w2",
+                "Day 1 - Week 8",
+                "2025-01-08T10:00:00Z",
+                &[("normal", 105.0, 3), ("normal", 90.0, 5)],
+            ),
+            workout(
+                "w3",
+                "Day 1 - Week 1",
+                "2025-01-15T10:00:00Z",
+                &[("normal", 80.0, 5)],
+            ),
+        ];
+
+        for workout in &workouts {
+            assert_eq!(record_workout(&mut data, workout, &HashMap::new()), 1);
+        }
+        assert_eq!(record_workout(&mut data, &workouts[0], &HashMap::new()), 0);
+
+        let squat = &data.exercises["squat"];
+        assert_eq!(squat.sessions.len(), 3);
+        assert_eq!(squat.sessions[0].volume_kg, 500.0);
+        assert!(squat.sessions[0].records.is_empty());
+        assert_eq!(
+            squat.sessions[1].records,
+            [RecordKind::Weight, RecordKind::Volume]
+        );
+        assert_eq!(squat.best_e1rm_kg, Some(estimated_one_rep_max(100.0, 5)));
+        assert_eq!(
+            squat.sessions.iter().map(|s| s.block).collect::<Vec<_>>(),
+            [1, 1, 2]
+        );
+        assert_eq!(squat.block.week, 1);
+
+        // The same workouts arriving newest first end up the same
+        let mut reversed = HistoryData::default();
+        for workout in workouts.iter().rev() {
+            record_workout(&mut reversed, workout, &HashMap::new());
+        }
+        let replayed = &reversed.exercises["squat"];
+        for (replayed, session) in replayed.sessions.iter().zip(&squat.sessions) {
+            assert_eq!(replayed.workout_id, session.workout_id);
+            assert_eq!(replayed.block, session.block);
+            assert_eq!(replayed.records, session.records);
+        }
+        assert_eq!(replayed.best_weight_kg, squat.best_weight_kg);
+        assert_eq!(replayed.block.block, 2);
+    }
+
+    #[test]
+    fn test_bodyweight_counts_towards_e1rm() {
+        let mut data = HistoryData::default();
+        data.bodyweight.push(BodyweightEntry {
+            recorded_at: "2025-01-05T08:00:00Z".parse().unwrap(),
+            bodyweight_kg: 80.0,
+        });
+        let weighted = HashMap::from([("squat".to_string(), LoadKind::Weighted)]);
+
+        // Older than any recorded bodyweight, so the first entry is used
+        record_workout(
+            &mut data,
+            &workout(
+                "w1",
+                "Week 1",
+                "2025-01-01T10:00:00Z",
+                &[("normal", 20.0, 1)],
+            ),
+            &weighted,
+        );
+
+        let session = &data.exercises["squat"].sessions[0];
+        assert_eq!(session.top_weight_kg, Some(20.0));
+        assert_eq!(session.best_e1rm_kg, Some(100.0));
+        assert_eq!(session.relative_e1rm, Some(1.25));
+        assert_eq!(data.exercises["squat"].best_relative_e1rm, Some(1.25));
+    }
+
+    #[test]
+    fn test_estimated_one_rep_max() {
+        assert_eq!(estimated_one_rep_max(100.0, 1), 100.0);
+        assert_eq!(estimated_one_rep_max(90.0, 30), 180.0);
+    }
+}
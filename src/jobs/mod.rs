@@ -0,0 +1,3 @@
+pub mod queue;
+pub mod routine_lock;
+pub mod worker;
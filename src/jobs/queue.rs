@@ -0,0 +1,451 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::store;
+
+const MAX_RETRY_DELAY_SECS: i64 = 3600;
+const SUCCEEDED_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+    Dead,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSource {
+    Webhook,
+    Sync,
+    Cli,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub workout_id: String,
+    pub state: JobState,
+    pub source: JobSource,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub next_run_at: DateTime<Utc>,
+    /// W3C trace context of the request that queued the job, if any.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trace_context: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct QueueDepth {
+    pub queued: usize,
+    pub running: usize,
+    pub failed: usize,
+    pub dead: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JobQueueData {
+    jobs: HashMap<String, Job>,
+}
+
+/// Persistent queue of workout processing jobs, keyed by workout id so the
+/// same workout is never queued or run twice at once.
+#[derive(Clone)]
+pub struct JobQueue {
+    path: PathBuf,
+    max_attempts: u32,
+    retry_base_secs: i64,
+    data: Arc<Mutex<JobQueueData>>,
+    notify: Arc<Notify>,
+}
+
+impl JobQueue {
+    pub fn open(path: &Path, max_attempts: u32, retry_base_secs: u64) -> Result<Self> {
+        let mut data: JobQueueData = store::load_json(path)?;
+
+        // Anything still marked running was interrupted by a crash or restart.
+        for job in data.jobs.values_mut() {
+            if job.state == JobState::Running {
+                job.state = JobState::Queued;
+            }
+        }
+
+        let queue = Self {
+            path: path.to_path_buf(),
+            max_attempts: max_attempts.max(1),
+            retry_base_secs: retry_base_secs as i64,
+            data: Arc::new(Mutex::new(data)),
+            notify: Arc::new(Notify::new()),
+        };
+        queue.persist(&mut queue.data.lock().unwrap())?;
+        Ok(queue)
+    }
+
+    /// Opens the queue as it is on disk, for commands that only inspect it.
+    /// Running jobs stay running and nothing is written, so a server using
+    /// the same file isn't disturbed.
+    pub fn open_read_only(path: &Path, max_attempts: u32, retry_base_secs: u64) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_attempts: max_attempts.max(1),
+            retry_base_secs: retry_base_secs as i64,
+            data: Arc::new(Mutex::new(store::load_json(path)?)),
+            notify: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Queues a workout for processing. Returns `false` when a job for the
+    /// workout already exists in any state.
+    pub fn enqueue(&self, workout_id: &str, source: JobSource) -> Result<bool> {
+        self.enqueue_traced(workout_id, source, HashMap::new())
+    }
+
+    /// Like [`JobQueue::enqueue`], keeping the caller's trace context so the
+    /// worker's processing span joins the same trace.
+    pub fn enqueue_traced(
+        &self,
+        workout_id: &str,
+        source: JobSource,
+        trace_context: HashMap<String, String>,
+    ) -> Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        if data.jobs.contains_key(workout_id) {
+            return Ok(false);
+        }
+
+        let now = Utc::now();
+        data.jobs.insert(
+            workout_id.to_string(),
+            Job {
+                workout_id: workout_id.to_string(),
+                state: JobState::Queued,
+                source,
+                attempts: 0,
+                last_error: None,
+                created_at: now,
+                updated_at: now,
+                next_run_at: now,
+                trace_context,
+            },
+        );
+        self.persist(&mut data)?;
+        drop(data);
+
+        tracing::info!(%workout_id, ?source, "job.enqueued");
+        self.notify.notify_one();
+        Ok(true)
+    }
+
+    /// Marks the oldest due job as running and returns it.
+    pub fn claim_next(&self, now: DateTime<Utc>) -> Result<Option<Job>> {
+        let mut data = self.data.lock().unwrap();
+
+        let next_id = data
+            .jobs
+            .values()
+            .filter(|job| matches!(job.state, JobState::Queued | JobState::Failed))
+            .filter(|job| job.next_run_at <= now)
+            .min_by_key(|job| (job.next_run_at, job.created_at))
+            .map(|job| job.workout_id.clone());
+
+        let Some(workout_id) = next_id else {
+            return Ok(None);
+        };
+
+        let job = data.jobs.get_mut(&workout_id).expect("job exists");
+        job.state = JobState::Running;
+        job.attempts += 1;
+        job.updated_at = now;
+        let claimed = job.clone();
+
+        self.persist(&mut data)?;
+        Ok(Some(claimed))
+    }
+
+    pub fn complete(&self, workout_id: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(job) = data.jobs.get_mut(workout_id) {
+            job.state = JobState::Succeeded;
+            job.last_error = None;
+            job.updated_at = Utc::now();
+        }
+        self.persist(&mut data)
+    }
+
+    /// Records a workout that was processed outside the queue as succeeded, so
+    /// a later webhook or sync doesn't apply it again.
+    pub fn record_succeeded(&self, workout_id: &str, source: JobSource) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let now = Utc::now();
+        let job = data
+            .jobs
+            .entry(workout_id.to_string())
+            .or_insert_with(|| Job {
+                workout_id: workout_id.to_string(),
+                state: JobState::Succeeded,
+                source,
+                attempts: 0,
+                last_error: None,
+                created_at: now,
+                updated_at: now,
+                next_run_at: now,
+                trace_context: HashMap::new(),
+            });
+        job.state = JobState::Succeeded;
+        job.attempts += 1;
+        job.last_error = None;
+        job.updated_at = now;
+        self.persist(&mut data)
+    }
+
+    /// Records a failed attempt, scheduling a retry with exponential backoff or
+    /// moving the job to the dead-letter list once attempts are exhausted.
+    pub fn fail(&self, workout_id: &str, error: &str) -> Result<JobState> {
+        let mut data = self.data.lock().unwrap();
+        let now = Utc::now();
+
+        let Some(job) = data.jobs.get_mut(workout_id) else {
+            return Ok(JobState::Dead);
+        };
+
+        job.last_error = Some(error.to_string());
+        job.updated_at = now;
+
+        if job.attempts >= self.max_attempts {
+            job.state = JobState::Dead;
+        } else {
+            job.state = JobState::Failed;
+            job.next_run_at = now + retry_delay(self.retry_base_secs, job.attempts);
+        }
+        let state = job.state;
+
+        self.persist(&mut data)?;
+        Ok(state)
+    }
+
+    /// Returns every running job to the queue without counting the interrupted
+    /// attempt. Used when shutdown cuts processing short.
+    pub fn release_running(&self) -> Result<usize> {
+        let mut data = self.data.lock().unwrap();
+        let now = Utc::now();
+        let mut released = 0;
+
+        for job in data.jobs.values_mut() {
+            if job.state == JobState::Running {
+                job.state = JobState::Queued;
+                job.attempts = job.attempts.saturating_sub(1);
+                job.next_run_at = now;
+                job.updated_at = now;
+                released += 1;
+            }
+        }
+
+        self.persist(&mut data)?;
+        Ok(released)
+    }
+
+    /// Moves a dead job back onto the queue with a fresh attempt budget.
+    pub fn requeue_dead(&self, workout_id: &str) -> Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        let now = Utc::now();
+
+        let Some(job) = data
+            .jobs
+            .get_mut(workout_id)
+            .filter(|job| job.state == JobState::Dead)
+        else {
+            return Ok(false);
+        };
+
+        job.state = JobState::Queued;
+        job.attempts = 0;
+        job.next_run_at = now;
+        job.updated_at = now;
+
+        self.persist(&mut data)?;
+        drop(data);
+
+        self.notify.notify_one();
+        Ok(true)
+    }
+
+    pub fn dead_letters(&self) -> Vec<Job> {
+        let data = self.data.lock().unwrap();
+        let mut jobs: Vec<Job> = data
+            .jobs
+            .values()
+            .filter(|job| job.state == JobState::Dead)
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|job| job.updated_at);
+        jobs
+    }
+
+    pub fn depth(&self) -> QueueDepth {
+        let data = self.data.lock().unwrap();
+        let mut depth = QueueDepth::default();
+        for job in data.jobs.values() {
+            match job.state {
+                JobState::Queued => depth.queued += 1,
+                JobState::Running => depth.running += 1,
+                JobState::Failed => depth.failed += 1,
+                JobState::Dead => depth.dead += 1,
+                JobState::Succeeded => {}
+            }
+        }
+        depth
+    }
+
+    /// Earliest time a queued or failed job becomes due, if any.
+    pub fn next_due_at(&self) -> Option<DateTime<Utc>> {
+        let data = self.data.lock().unwrap();
+        data.jobs
+            .values()
+            .filter(|job| matches!(job.state, JobState::Queued | JobState::Failed))
+            .map(|job| job.next_run_at)
+            .min()
+    }
+
+    pub async fn wait_for_work(&self) {
+        self.notify.notified().await;
+    }
+
+    fn persist(&self, data: &mut JobQueueData) -> Result<()> {
+        let cutoff = Utc::now() - Duration::days(SUCCEEDED_RETENTION_DAYS);
+        data.jobs
+            .retain(|_, job| job.state != JobState::Succeeded || job.updated_at > cutoff);
+
+        store::save_json(&self.path, data)
+    }
+}
+
+fn retry_delay(base_secs: i64, attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    let delay = base_secs.saturating_mul(1_i64 << exponent);
+    Duration::seconds(delay.min(MAX_RETRY_DELAY_SECS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_queue(name: &str, max_attempts: u32) -> (JobQueue, PathBuf) {
+        let path =
+            std::env::temp_dir().join(format!("hevy-jobs-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        (JobQueue::open(&path, max_attempts, 0).unwrap(), path)
+    }
+
+    #[test]
+    fn test_enqueue_deduplicates_by_workout() {
+        let (queue, path) = temp_queue("dedup", 3);
+
+        assert!(queue.enqueue("w1", JobSource::Webhook).unwrap());
+        assert!(!queue.enqueue("w1", JobSource::Sync).unwrap());
+
+        let claimed = queue.claim_next(Utc::now()).unwrap().unwrap();
+        assert_eq!(claimed.workout_id, "w1");
+        assert_eq!(claimed.attempts, 1);
+        assert!(queue.claim_next(Utc::now()).unwrap().is_none());
+
+        queue.complete("w1").unwrap();
+        assert!(!queue.enqueue("w1", JobSource::Sync).unwrap());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_record_succeeded_blocks_later_enqueue() {
+        let (queue, path) = temp_queue("record", 3);
+
+        queue.record_succeeded("w1", JobSource::Cli).unwrap();
+        assert!(!queue.enqueue("w1", JobSource::Sync).unwrap());
+        assert!(queue.claim_next(Utc::now()).unwrap().is_none());
+
+        queue.enqueue("w2", JobSource::Webhook).unwrap();
+        queue.record_succeeded("w2", JobSource::Cli).unwrap();
+        assert!(queue.claim_next(Utc::now()).unwrap().is_none());
+        assert_eq!(queue.depth().queued, 0);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_failed_jobs_retry_then_go_dead() {
+        let (queue, path) = temp_queue("retry", 2);
+        queue.enqueue("w1", JobSource::Webhook).unwrap();
+
+        queue.claim_next(Utc::now()).unwrap().unwrap();
+        assert_eq!(queue.fail("w1", "boom").unwrap(), JobState::Failed);
+
+        queue.claim_next(Utc::now()).unwrap().unwrap();
+        assert_eq!(queue.fail("w1", "boom again").unwrap(), JobState::Dead);
+
+        let dead = queue.dead_letters();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].last_error.as_deref(), Some("boom again"));
+        assert!(queue.claim_next(Utc::now()).unwrap().is_none());
+
+        assert!(queue.requeue_dead("w1").unwrap());
+        assert!(queue.dead_letters().is_empty());
+        assert!(queue.claim_next(Utc::now()).unwrap().is_some());
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_running_jobs_are_requeued_on_reopen() {
+        let (queue, path) = temp_queue("reopen", 3);
+        queue.enqueue("w1", JobSource::Webhook).unwrap();
+        queue.claim_next(Utc::now()).unwrap().unwrap();
+
+        let inspected = JobQueue::open_read_only(&path, 3, 0).unwrap();
+        assert_eq!(inspected.depth().running, 1);
+        assert_eq!(
+            JobQueue::open_read_only(&path, 3, 0)
+                .unwrap()
+                .depth()
+                .running,
+            1
+        );
+
+        let reopened = JobQueue::open(&path, 3, 0).unwrap();
+        let job = reopened.claim_next(Utc::now()).unwrap().unwrap();
+        assert_eq!(job.workout_id, "w1");
+        assert_eq!(job.attempts, 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_release_running_returns_jobs_to_queue() {
+        let (queue, path) = temp_queue("release", 3);
+        queue.enqueue("w1", JobSource::Webhook).unwrap();
+        queue.enqueue("w2", JobSource::Webhook).unwrap();
+        queue.claim_next(Utc::now()).unwrap().unwrap();
+
+        assert_eq!(queue.release_running().unwrap(), 1);
+
+        let first = queue.claim_next(Utc::now()).unwrap().unwrap();
+        let second = queue.claim_next(Utc::now()).unwrap().unwrap();
+        assert_eq!(first.attempts + second.attempts, 2);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(30, 1), Duration::seconds(30));
+        assert_eq!(retry_delay(30, 3), Duration::seconds(120));
+        assert_eq!(retry_delay(30, 20), Duration::seconds(MAX_RETRY_DELAY_SECS));
+    }
+}
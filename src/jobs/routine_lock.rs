@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+#[derive(Default)]
+struct RoutineSlot {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    pending: Vec<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct RoutineSlots {
+    /// Routines with a workout waiting or running. A slot goes once its last
+    /// ticket is dropped.
+    active: HashMap<String, RoutineSlot>,
+    /// End time of the newest workout applied to each routine.
+    last_applied: HashMap<String, DateTime<Utc>>,
+}
+
+/// Serializes routine updates per routine id. Workouts for the same routine are
+/// applied one at a time in `end_time` order; a workout is skipped when a later
+/// one for the same routine is already waiting or has already been applied.
+#[derive(Clone, Default)]
+pub struct RoutineLocks {
+    slots: Arc<Mutex<RoutineSlots>>,
+}
+
+pub struct RoutineTicket {
+    locks: RoutineLocks,
+    routine_id: String,
+    end_time: DateTime<Utc>,
+}
+
+// The lock is released before the ticket drops, so a slot is never removed
+// while its lock is still held
+pub struct RoutineGuard {
+    _guard: OwnedMutexGuard<()>,
+    ticket: RoutineTicket,
+}
+
+impl RoutineLocks {
+    /// Announces that a workout for `routine_id` is about to be processed so
+    /// older workouts still waiting on the same routine can step aside.
+    pub fn register(&self, routine_id: &str, end_time: DateTime<Utc>) -> RoutineTicket {
+        let mut slots = self.slots.lock().unwrap();
+        slots
+            .active
+            .entry(routine_id.to_string())
+            .or_default()
+            .pending
+            .push(end_time);
+
+        RoutineTicket {
+            locks: self.clone(),
+            routine_id: routine_id.to_string(),
+            end_time,
+        }
+    }
+}
+
+impl RoutineTicket {
+    /// Waits for exclusive access to the routine. Returns `None` when a later
+    /// workout for the same routine supersedes this one.
+    pub async fn acquire(self) -> Option<RoutineGuard> {
+        let lock = {
+            let slots = self.locks.slots.lock().unwrap();
+            Arc::clone(
+                &slots
+                    .active
+                    .get(&self.routine_id)
+                    .expect("slot registered")
+                    .lock,
+            )
+        };
+
+        let guard = lock.lock_owned().await;
+
+        if self.is_superseded() {
+            return None;
+        }
+
+        Some(RoutineGuard {
+            _guard: guard,
+            ticket: self,
+        })
+    }
+
+    fn is_superseded(&self) -> bool {
+        let slots = self.locks.slots.lock().unwrap();
+        let slot = slots.active.get(&self.routine_id).expect("slot registered");
+
+        slots
+            .last_applied
+            .get(&self.routine_id)
+            .is_some_and(|applied| *applied >= self.end_time)
+            || slot.pending.iter().any(|pending| *pending > self.end_time)
+    }
+}
+
+impl Drop for RoutineTicket {
+    fn drop(&mut self) {
+        let mut slots = self.locks.slots.lock().unwrap();
+        let Some(slot) = slots.active.get_mut(&self.routine_id) else {
+            return;
+        };
+        if let Some(position) = slot.pending.iter().position(|p| *p == self.end_time) {
+            slot.pending.remove(position);
+        }
+        // Every waiting or running workout holds a ticket, so nothing else
+        // can be using the lock
+        if slot.pending.is_empty() {
+            slots.active.remove(&self.routine_id);
+        }
+    }
+}
+
+impl RoutineGuard {
+    /// Records that this workout's update reached Hevy, so older workouts for
+    /// the routine are ignored from now on.
+    pub fn mark_applied(&self) {
+        let mut slots = self.ticket.locks.slots.lock().unwrap();
+        let end_time = self.ticket.end_time;
+        slots
+            .last_applied
+            .entry(self.ticket.routine_id.clone())
+            .and_modify(|applied| *applied = (*applied).max(end_time))
+            .or_insert(end_time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_newer_pending_workout_supersedes_older() {
+        let locks = RoutineLocks::default();
+        let now = Utc::now();
+
+        let older = locks.register("r1", now - Duration::hours(1));
+        let newer = locks.register("r1", now);
+
+        assert!(older.acquire().await.is_none());
+        let guard = newer.acquire().await.expect("newest workout runs");
+        guard.mark_applied();
+    }
+
+    #[tokio::test]
+    async fn test_older_workout_after_apply_is_skipped() {
+        let locks = RoutineLocks::default();
+        let now = Utc::now();
+
+        let newer = locks.register("r1", now);
+        newer.acquire().await.unwrap().mark_applied();
+
+        assert!(locks.slots.lock().unwrap().active.is_empty());
+
+        let late = locks.register("r1", now - Duration::minutes(5));
+        assert!(late.acquire().await.is_none());
+
+        let other_routine = locks.register("r2", now - Duration::minutes(5));
+        assert!(other_routine.acquire().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_updates_for_same_routine_run_one_at_a_time() {
+        let locks = RoutineLocks::default();
+        let now = Utc::now();
+
+        let first = locks.register("r1", now).acquire().await.unwrap();
+        let second = locks.register("r1", now + Duration::minutes(1));
+
+        let waiting = tokio::spawn(async move { second.acquire().await.is_some() });
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+
+        first.mark_applied();
+        drop(first);
+        assert!(waiting.await.unwrap());
+        assert!(locks.slots.lock().unwrap().active.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::api::webhooks::{AppState, process_single_workout};
+use crate::jobs::queue::JobState;
+use crate::metrics::metrics;
+use crate::telemetry;
+
+const IDLE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Starts the worker pool. Workers stop claiming jobs once `shutdown` flips to
+/// `true` but finish the job they're currently running.
+pub fn spawn_workers(state: Arc<AppState>, shutdown: watch::Receiver<bool>) -> Vec<JoinHandle<()>> {
+    let concurrency = state.config.worker_concurrency.max(1);
+    tracing::info!(concurrency, "jobs.workers_started");
+
+    (0..concurrency)
+        .map(|worker_id| {
+            let state = Arc::clone(&state);
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move { run_worker(state, worker_id, shutdown).await })
+        })
+        .collect()
+}
+
+async fn run_worker(state: Arc<AppState>, worker_id: usize, mut shutdown: watch::Receiver<bool>) {
+    loop {
+        if *shutdown.borrow() {
+            tracing::debug!(worker_id, "jobs.worker_stopped");
+            return;
+        }
+
+        let job = match state.job_queue.claim_next(Utc::now()) {
+            Ok(job) => job,
+            Err(e) => {
+                tracing::error!(error = %e, worker_id, "job.claim_failed");
+                None
+            }
+        };
+
+        let Some(job) = job else {
+            let idle_for = state
+                .job_queue
+                .next_due_at()
+                .and_then(|due| (due - Utc::now()).to_std().ok())
+                .unwrap_or(IDLE_POLL_INTERVAL)
+                .min(IDLE_POLL_INTERVAL);
+
+            tokio::select! {
+                _ = state.job_queue.wait_for_work() => {}
+                _ = tokio::time::sleep(idle_for) => {}
+                _ = shutdown.changed() => {}
+            }
+            continue;
+        };
+
+        let workout_id = job.workout_id.clone();
+        let span = tracing::info_span!(
+            "workout.process",
+            %workout_id,
+            attempt = job.attempts,
+            source = ?job.source,
+            outcome = tracing::field::Empty,
+        );
+        if !job.trace_context.is_empty()
+            && let Err(e) = span.set_parent(telemetry::context_from_carrier(&job.trace_context))
+        {
+            tracing::debug!(error = %e, %workout_id, "job.trace_context_ignored");
+        }
+
+        tracing::info!(parent: &span, %workout_id, worker_id, attempt = job.attempts, "job.started");
+
+        let result = process_single_workout(&state, workout_id.clone())
+            .instrument(span.clone())
+            .await;
+        span.record(
+            "outcome",
+            result.as_ref().map_or("failed", |outcome| outcome.as_str()),
+        );
+
+        match result {
+            Ok(outcome) => {
+                metrics()
+                    .workouts_processed
+                    .with_label_values(&[outcome.as_str()])
+                    .inc();
+                if let Err(e) = state.job_queue.complete(&workout_id) {
+                    tracing::error!(error = %e, %workout_id, "job.persist_failed");
+                }
+                tracing::info!(%workout_id, worker_id, outcome = outcome.as_str(), "job.succeeded");
+            }
+            Err(error) => {
+                metrics()
+                    .workouts_processed
+                    .with_label_values(&["failed"])
+                    .inc();
+                let attempts = job.attempts;
+                state
+                    .status
+                    .record_error(Some(&workout_id), &format!("{:#}", error));
+                match state.job_queue.fail(&workout_id, &format!("{:#}", error)) {
+                    Ok(JobState::Dead) => {
+                        tracing::error!(error = %error, %workout_id, attempts, "job.dead");
+                    }
+                    Ok(_) => {
+                        tracing::warn!(error = %error, %workout_id, attempts, "job.retry_scheduled");
+                    }
+                    Err(e) => {
+                        tracing::error!(error = %e, %workout_id, "job.persist_failed");
+                    }
+                }
+            }
+        }
+    }
+}
@@ -1,67 +1,299 @@
-use axum::{Router, routing::post};
-use std::collections::HashSet;
+use axum::{
+    Router,
+    routing::{get, post},
+};
+use clap::Parser;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
 
+use crate::api::audit::list_audit_records;
+use crate::api::health::{handle_healthz, handle_readyz, handle_status};
+use crate::api::jobs::{list_dead_letters, retry_dead_letter};
+use crate::api::metrics::handle_metrics;
 use crate::api::webhooks::{AppState, handle_workout_completion};
+use crate::audit::AuditLog;
+use crate::cli::{Cli, Command};
 use crate::clients::hevy::HevyClient;
 use crate::config::Config;
+use crate::history::HistoryStore;
+use crate::jobs::queue::JobQueue;
+use crate::jobs::routine_lock::RoutineLocks;
+use crate::jobs::worker::spawn_workers;
+use crate::prescription_store::PrescriptionStore;
+use crate::program_store::ProgramStore;
 use crate::scheduler::start_scheduler;
+use crate::services::bodyweight::BodyweightProgression;
+use crate::services::conditioning::Conditioning;
+use crate::services::double_progression::DoubleProgression;
 use crate::services::progressive_overload::ProgressiveOverloadService;
+use crate::services::rest::RestPeriods;
+use crate::services::stalls::{StallDetector, StallPolicy};
+use crate::status::ServiceStatus;
+use crate::store::DataDirLock;
 
 mod api;
+mod audit;
+mod backfill;
+mod cli;
 mod clients;
 mod config;
+mod history;
+mod jobs;
+mod metrics;
+mod prescription_store;
+mod program_store;
 mod scheduler;
 mod services;
+mod shutdown;
+mod simulation;
+mod status;
+mod store;
+mod telemetry;
+#[cfg(test)]
+mod testing;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
 
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_target(false)
-        .compact()
-        .init();
-
+    let cli = Cli::parse();
     let config = Config::from_env()?;
+    let tracer_provider = telemetry::init(&config)?;
+    let command = cli.command.unwrap_or(Command::Serve);
+    // Held until the command finishes, so two writers never share DATA_DIR
+    let _data_dir_lock = if command.writes_data() {
+        Some(DataDirLock::acquire(&config.data_dir)?)
+    } else {
+        None
+    };
+    let state = if command.writes_data() {
+        build_state(&config)?
+    } else {
+        build_read_only_state(&config)?
+    };
+
+    let result = match command {
+        Command::Serve => serve(state).await,
+        Command::Sync => cli::sync(state).await,
+        Command::Process {
+            workout_id,
+            dry_run,
+        } => cli::process(state, workout_id, dry_run).await,
+        Command::PreviewPrompt { workout_id } => cli::preview_prompt(state, workout_id).await,
+        Command::Routines { command } => cli::routines(state, command).await,
+        Command::Program { command } => cli::program(state, command),
+        Command::Stalls => cli::stalls(state),
+        Command::Bodyweight { kg } => cli::bodyweight(state, kg),
+        Command::History {
+            routine_id,
+            limit,
+            json,
+        } => cli::history(state, routine_id, limit, json),
+        Command::Backfill { since, simulate } => cli::backfill(state, since, simulate).await,
+        Command::Simulate {
+            fixtures,
+            engines,
+            json,
+        } => cli::simulate(state, fixtures, engines, json).await,
+        Command::RecordFixture { workout_id, output } => {
+            cli::record_fixture(state, workout_id, output).await
+        }
+    };
+
+    telemetry::shutdown(tracer_provider).await;
+    result
+}
+
+fn double_progression(config: &Config) -> DoubleProgression {
+    DoubleProgression {
+        increment_kg: config.double_progression_increment_kg,
+        default_range: config.double_progression_rep_range,
+        exercise_template_ids: config
+            .double_progression_exercises
+            .iter()
+            .cloned()
+            .collect(),
+    }
+}
+
+fn conditioning(config: &Config) -> Conditioning {
+    Conditioning {
+        max_weekly_increase: config.conditioning_max_weekly_increase_pct / 100.0,
+        pace_step: config.conditioning_pace_step_pct / 100.0,
+    }
+}
+
+fn bodyweight_progression(config: &Config) -> BodyweightProgression {
+    BodyweightProgression {
+        increment_kg: config.bodyweight_increment_kg,
+        default_range: config.bodyweight_rep_range,
+    }
+}
+
+fn rest_periods(config: &Config) -> RestPeriods {
+    RestPeriods {
+        heavy_increase: config.rest_heavy_increase_pct / 100.0,
+        deload_decrease: config.rest_deload_decrease_pct / 100.0,
+    }
+}
+
+fn stall_detector(config: &Config, prescriptions: PrescriptionStore) -> StallDetector {
+    StallDetector {
+        policy: StallPolicy {
+            threshold: config.stall_threshold,
+            response: config.stall_response,
+            reduce_by: config.stall_reduce_pct / 100.0,
+        },
+        prescriptions,
+    }
+}
+
+fn build_state(config: &Config) -> anyhow::Result<AppState> {
+    let job_queue = JobQueue::open(
+        &config.jobs_path(),
+        config.job_max_attempts,
+        config.job_retry_base_secs,
+    )?;
+    open_state(config, job_queue)
+}
+
+/// State for commands that only inspect `DATA_DIR`. The job queue is read
+/// as it is, so running jobs of a server using the same directory aren't
+/// put back on the queue.
+fn build_read_only_state(config: &Config) -> anyhow::Result<AppState> {
+    let job_queue = JobQueue::open_read_only(
+        &config.jobs_path(),
+        config.job_max_attempts,
+        config.job_retry_base_secs,
+    )?;
+    open_state(config, job_queue)
+}
 
-    let hevy_client = HevyClient::new(&config)?;
+fn open_state(config: &Config, job_queue: JobQueue) -> anyhow::Result<AppState> {
+    let hevy_client = HevyClient::new(config)?;
     let gemini_client = crate::clients::gemini::GeminiClient::new(
         config.gemini_api_key.clone(),
         config.gemini_model.clone(),
     );
-    let progressive_overload_service =
-        ProgressiveOverloadService::new(gemini_client.clone(), hevy_client.clone());
+    let programs = ProgramStore::open(&config.programs_path())?;
+    let prescriptions = PrescriptionStore::open(&config.prescriptions_path())?;
+    let progressive_overload_service = ProgressiveOverloadService::new(
+        Arc::new(gemini_client),
+        hevy_client.clone(),
+        double_progression(config),
+        stall_detector(config, prescriptions.clone()),
+        programs.clone(),
+        config.engine,
+    )
+    .with_conditioning(conditioning(config))
+    .with_bodyweight(bodyweight_progression(config))
+    .with_rest_periods(rest_periods(config));
 
-    let state = AppState {
+    Ok(AppState {
         config: config.clone(),
         hevy_client,
         progressive_overload_service,
-        processed_workout_ids: Arc::new(std::sync::Mutex::new(HashSet::new())),
-    };
+        job_queue,
+        routine_locks: RoutineLocks::default(),
+        status: ServiceStatus::open(&config.status_path())?,
+        audit_log: AuditLog::new(&config.audit_path(), config.audit_redact_llm_text),
+        history: HistoryStore::open(&config.history_path())?,
+        programs,
+        prescriptions,
+    })
+}
 
-    let app = Router::new()
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/healthz", get(handle_healthz))
+        .route("/readyz", get(handle_readyz))
+        .route("/status", get(handle_status))
         .route("/webhook", post(handle_workout_completion))
-        .with_state(state.clone());
+        .route("/metrics", get(handle_metrics))
+        .route("/audit", get(list_audit_records))
+        .route("/jobs/dead", get(list_dead_letters))
+        .route("/jobs/dead/{workout_id}/retry", post(retry_dead_letter))
+        .with_state(state)
+}
+
+async fn serve(state: AppState) -> anyhow::Result<()> {
+    let config = state.config.clone();
+
+    let app = router(state.clone());
 
-    // cron scheduler
     let state_arc = Arc::new(state);
-    let _scheduler = start_scheduler(Arc::clone(&state_arc)).await?;
-    tracing::info!("scheduler.started");
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let workers = spawn_workers(Arc::clone(&state_arc), shutdown_rx);
+
+    // cron scheduler
+    let mut scheduler = if config.sync.enabled {
+        let scheduler = start_scheduler(Arc::clone(&state_arc)).await?;
+        state_arc.status.set_scheduler_running(true);
+        tracing::info!("scheduler.started");
+        Some(scheduler)
+    } else {
+        tracing::info!("scheduler.disabled");
+        None
+    };
 
     // Run initial sync on startup
-    let state_for_sync = Arc::clone(&state_arc);
-    tokio::spawn(async move {
-        if let Err(e) = crate::scheduler::run_sync(state_for_sync).await {
-            tracing::error!(error = %e, "initial.sync_failed");
-        }
+    let startup_sync = (config.sync.enabled && config.sync.run_on_startup).then(|| {
+        let state_for_sync = Arc::clone(&state_arc);
+        let handle = tokio::spawn(async move {
+            if let Err(e) = crate::scheduler::run_sync(state_for_sync).await {
+                tracing::error!(error = %e, "initial.sync_failed");
+            }
+        });
+        tracing::info!("initial.sync_started");
+        handle
     });
-    tracing::info!("initial.sync_started");
 
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.port)).await?;
     tracing::info!(port = %config.port, "server.listening");
 
-    axum::serve(listener, app).await?;
+    // Stop accepting webhooks first, then the scheduler, then let the workers
+    // finish whatever they're in the middle of
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown::shutdown_signal())
+        .await?;
+    tracing::info!("server.stopped");
+
+    if let Some(scheduler) = scheduler.as_mut() {
+        if let Err(e) = scheduler.shutdown().await {
+            tracing::error!(error = %e, "scheduler.shutdown_failed");
+        }
+        state_arc.status.set_scheduler_running(false);
+        tracing::info!("scheduler.stopped");
+    }
+
+    // The startup sync only queues jobs, and anything it hadn't reached yet is
+    // picked up by the sync on the next start
+    if let Some(startup_sync) = startup_sync
+        && !startup_sync.is_finished()
+    {
+        startup_sync.abort();
+        if let Err(e) = startup_sync.await
+            && !e.is_cancelled()
+        {
+            tracing::error!(error = %e, "initial.sync_task_failed");
+        }
+        tracing::info!("initial.sync_cancelled");
+    }
+
+    let timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    if shutdown::drain(&shutdown_tx, workers, timeout).await {
+        tracing::info!("jobs.drained");
+    } else {
+        // Interrupted jobs go back on the queue and run again on the next start
+        let released = state_arc.job_queue.release_running()?;
+        tracing::warn!(
+            released,
+            timeout_secs = config.shutdown_timeout_secs,
+            "jobs.drain_timed_out"
+        );
+    }
+
+    tracing::info!("shutdown.completed");
     Ok(())
 }
@@ -0,0 +1,199 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+const NAMESPACE: &str = "hevy_overloader";
+
+pub struct Metrics {
+    registry: Registry,
+    pub webhooks_received: IntCounter,
+    pub webhooks_rejected: IntCounterVec,
+    pub workouts_processed: IntCounterVec,
+    pub llm_request_duration: HistogramVec,
+    pub llm_tokens: IntCounterVec,
+    pub llm_parse_failures: IntCounter,
+    pub llm_parse_repairs: IntCounterVec,
+    pub hevy_request_duration: HistogramVec,
+    pub stall_responses: IntCounterVec,
+    pub scheduler_run_duration: HistogramVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+
+        let webhooks_received = IntCounter::with_opts(
+            Opts::new("webhooks_received_total", "Webhook requests received").namespace(NAMESPACE),
+        )
+        .unwrap();
+        let webhooks_rejected = IntCounterVec::new(
+            Opts::new("webhooks_rejected_total", "Webhook requests rejected").namespace(NAMESPACE),
+            &["reason"],
+        )
+        .unwrap();
+        let workouts_processed = IntCounterVec::new(
+            Opts::new(
+                "workouts_processed_total",
+                "Workout processing runs by outcome",
+            )
+            .namespace(NAMESPACE),
+            &["outcome"],
+        )
+        .unwrap();
+        let llm_request_duration = HistogramVec::new(
+            HistogramOpts::new("llm_request_duration_seconds", "LLM request latency")
+                .namespace(NAMESPACE)
+                .buckets(vec![1.0, 2.5, 5.0, 10.0, 20.0, 40.0, 80.0, 160.0]),
+            &["model", "status"],
+        )
+        .unwrap();
+        let llm_tokens = IntCounterVec::new(
+            Opts::new("llm_tokens_total", "LLM tokens used").namespace(NAMESPACE),
+            &["model", "kind"],
+        )
+        .unwrap();
+        let llm_parse_failures = IntCounter::with_opts(
+            Opts::new(
+                "llm_parse_failures_total",
+                "LLM responses that couldn't be parsed",
+            )
+            .namespace(NAMESPACE),
+        )
+        .unwrap();
+        let llm_parse_repairs = IntCounterVec::new(
+            Opts::new(
+                "llm_parse_repairs_total",
+                "Repairs made while parsing LLM responses",
+            )
+            .namespace(NAMESPACE),
+            &["kind"],
+        )
+        .unwrap();
+        let hevy_request_duration = HistogramVec::new(
+            HistogramOpts::new("hevy_request_duration_seconds", "Hevy API request latency")
+                .namespace(NAMESPACE)
+                .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["endpoint", "status"],
+        )
+        .unwrap();
+        let stall_responses = IntCounterVec::new(
+            Opts::new(
+                "stall_responses_total",
+                "Exercises that missed their prescribed reps, by response",
+            )
+            .namespace(NAMESPACE),
+            &["action"],
+        )
+        .unwrap();
+        let scheduler_run_duration = HistogramVec::new(
+            HistogramOpts::new("scheduler_run_duration_seconds", "Sync run duration")
+                .namespace(NAMESPACE)
+                .buckets(vec![0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+            &["outcome"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(webhooks_received.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(webhooks_rejected.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(workouts_processed.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(llm_request_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(llm_tokens.clone())).unwrap();
+        registry
+            .register(Box::new(llm_parse_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(llm_parse_repairs.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(hevy_request_duration.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(stall_responses.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(scheduler_run_duration.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            webhooks_received,
+            webhooks_rejected,
+            workouts_processed,
+            llm_request_duration,
+            llm_tokens,
+            llm_parse_failures,
+            llm_parse_repairs,
+            hevy_request_duration,
+            stall_responses,
+            scheduler_run_duration,
+        }
+    }
+
+    pub fn observe_hevy_request(&self, endpoint: &str, status: &str, elapsed: Duration) {
+        self.hevy_request_duration
+            .with_label_values(&[endpoint, status])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_llm_request(&self, model: &str, status: &str, elapsed: Duration) {
+        self.llm_request_duration
+            .with_label_values(&[model, status])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn add_llm_tokens(&self, model: &str, kind: &str, count: i32) {
+        if count > 0 {
+            self.llm_tokens
+                .with_label_values(&[model, kind])
+                .inc_by(count as u64);
+        }
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            tracing::error!(error = %e, "metrics.encode_failed");
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_includes_recorded_metrics() {
+        let metrics = metrics();
+        metrics.webhooks_received.inc();
+        metrics
+            .workouts_processed
+            .with_label_values(&["updated"])
+            .inc();
+        metrics.observe_hevy_request("get_workout", "200", Duration::from_millis(120));
+
+        let output = metrics.render();
+        assert!(output.contains("hevy_overloader_webhooks_received_total"));
+        assert!(output.contains("hevy_overloader_workouts_processed_total{outcome=\"updated\"}"));
+        assert!(output.contains(
+            "hevy_overloader_hevy_request_duration_seconds_count{endpoint=\"get_workout\",status=\"200\"}"
+        ));
+    }
+}
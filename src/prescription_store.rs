@@ -0,0 +1,101 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::services::stalls::{Prescription, PrescriptionRecord};
+use crate::store;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutinePrescription {
+    /// The workout whose processing wrote this prescription.
+    pub workout_id: String,
+    pub recorded_at: DateTime<Utc>,
+    pub exercises: HashMap<String, Prescription>,
+    /// Consecutive sessions each exercise template missed its prescribed
+    /// reps in this routine.
+    #[serde(default)]
+    pub misses: HashMap<String, u32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PrescriptionData {
+    /// Last prescription written to each Hevy routine id.
+    pub routines: HashMap<String, RoutinePrescription>,
+}
+
+/// The prescriptions written to Hevy, kept so the next workout can be
+/// compared against them, and the running miss count per routine and
+/// exercise.
+#[derive(Clone)]
+pub struct PrescriptionStore {
+    path: PathBuf,
+    data: Arc<Mutex<PrescriptionData>>,
+}
+
+impl PrescriptionStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: Arc::new(Mutex::new(store::load_json(path)?)),
+        })
+    }
+
+    pub fn snapshot(&self) -> PrescriptionData {
+        self.data.lock().unwrap().clone()
+    }
+
+    pub fn latest(&self, routine_id: &str) -> Option<RoutinePrescription> {
+        self.data.lock().unwrap().routines.get(routine_id).cloned()
+    }
+
+    pub fn prescriptions_for(&self, routine_id: &str) -> HashMap<String, Prescription> {
+        self.data
+            .lock()
+            .unwrap()
+            .routines
+            .get(routine_id)
+            .map(|routine| routine.exercises.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn misses(&self, routine_id: &str) -> HashMap<String, u32> {
+        self.data
+            .lock()
+            .unwrap()
+            .routines
+            .get(routine_id)
+            .map(|routine| routine.misses.clone())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the routine's prescription and updates the miss counts it
+    /// carries.
+    pub fn record(&self, record: &PrescriptionRecord) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let mut misses = data
+            .routines
+            .remove(&record.routine_id)
+            .map(|routine| routine.misses)
+            .unwrap_or_default();
+        for (template_id, count) in &record.misses {
+            if *count == 0 {
+                misses.remove(template_id);
+            } else {
+                misses.insert(template_id.clone(), *count);
+            }
+        }
+        data.routines.insert(
+            record.routine_id.clone(),
+            RoutinePrescription {
+                workout_id: record.workout_id.clone(),
+                recorded_at: Utc::now(),
+                exercises: record.exercises.clone(),
+                misses,
+            },
+        );
+        store::save_json(&self.path, &*data)
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::services::programs::{LiftState, Program, ProgramUpdate, RoutineProgramState};
+use crate::store;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutineProgram {
+    pub program: Program,
+    /// Week of the program last prescribed for this routine, 0 before the
+    /// first prescription.
+    pub week: u32,
+    pub assigned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct ProgramData {
+    /// Program selected for each Hevy routine id.
+    pub routines: HashMap<String, RoutineProgram>,
+    /// Lift state per program, shared by every routine running it.
+    pub lifts: HashMap<Program, HashMap<String, LiftState>>,
+}
+
+/// Which routines run a built-in program, and each program's training maxes
+/// and progress, persisted across restarts.
+#[derive(Clone)]
+pub struct ProgramStore {
+    path: PathBuf,
+    data: Arc<Mutex<ProgramData>>,
+}
+
+impl ProgramStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            data: Arc::new(Mutex::new(store::load_json(path)?)),
+        })
+    }
+
+    pub fn snapshot(&self) -> ProgramData {
+        self.data.lock().unwrap().clone()
+    }
+
+    /// The program a routine runs and its current state, if it has one.
+    pub fn state_for(&self, routine_id: &str) -> Option<(Program, RoutineProgramState)> {
+        let data = self.data.lock().unwrap();
+        let routine = data.routines.get(routine_id)?;
+        Some((
+            routine.program,
+            RoutineProgramState {
+                week: routine.week,
+                lifts: data
+                    .lifts
+                    .get(&routine.program)
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+        ))
+    }
+
+    /// Starts `routine_id` on `program` from its first week. Lift state the
+    /// program already has is kept.
+    pub fn assign(&self, routine_id: &str, program: Program) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.routines.insert(
+            routine_id.to_string(),
+            RoutineProgram {
+                program,
+                week: 0,
+                assigned_at: Utc::now(),
+            },
+        );
+        store::save_json(&self.path, &*data)
+    }
+
+    /// Returns whether the routine had a program.
+    pub fn unassign(&self, routine_id: &str) -> Result<bool> {
+        let mut data = self.data.lock().unwrap();
+        let removed = data.routines.remove(routine_id).is_some();
+        if removed {
+            store::save_json(&self.path, &*data)?;
+        }
+        Ok(removed)
+    }
+
+    /// Records a prescription that has been written to Hevy. Ignored if the
+    /// routine has since been moved to another program.
+    pub fn apply(&self, update: &ProgramUpdate) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let Some(routine) = data.routines.get_mut(&update.routine_id) else {
+            return Ok(());
+        };
+        if routine.program != update.program {
+            return Ok(());
+        }
+
+        routine.week = update.week;
+        data.lifts
+            .entry(update.program)
+            .or_default()
+            .extend(update.lifts.clone());
+        store::save_json(&self.path, &*data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_updates_apply_only_to_the_current_program() {
+        let dir = std::env::temp_dir().join(format!("program-store-{}", std::process::id()));
+        let path = dir.join("programs.json");
+        let store = ProgramStore::open(&path).unwrap();
+        store.assign("r1", Program::FiveThreeOne).unwrap();
+
+        let update = |program| ProgramUpdate {
+            routine_id: "r1".to_string(),
+            program,
+            week: 2,
+            lifts: HashMap::from([(
+                "squat".to_string(),
+                LiftState {
+                    training_max_kg: Some(125.0),
+                    ..LiftState::default()
+                },
+            )]),
+        };
+        store.apply(&update(Program::Gzclp)).unwrap();
+        assert_eq!(store.state_for("r1").unwrap().1.week, 0);
+
+        store.apply(&update(Program::FiveThreeOne)).unwrap();
+        let reopened = ProgramStore::open(&path).unwrap();
+        let (program, state) = reopened.state_for("r1").unwrap();
+        assert_eq!(program, Program::FiveThreeOne);
+        assert_eq!(state.week, 2);
+        assert_eq!(state.lifts["squat"].training_max_kg, Some(125.0));
+
+        assert!(reopened.unassign("r1").unwrap());
+        assert!(reopened.state_for("r1").is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,65 +1,119 @@
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Duration, Local, Timelike, Utc};
 use std::sync::Arc;
+use std::time::Instant;
 use tokio_cron_scheduler::{Job, JobScheduler};
 
-use crate::api::webhooks::{AppState, process_single_workout};
+use crate::api::webhooks::AppState;
+use crate::jobs::queue::JobSource;
+use crate::metrics::metrics;
+use crate::status::SyncStatus;
 
 pub async fn start_scheduler(state: Arc<AppState>) -> anyhow::Result<JobScheduler> {
     let scheduler = JobScheduler::new().await?;
 
-    let state_clone = Arc::clone(&state);
+    for expression in &state.config.sync.cron_expressions {
+        let state_clone = Arc::clone(&state);
 
-    scheduler
-        .add(Job::new_async("0 */15 * * * *", move |_uuid, _l| {
-            // .add(Job::new_async("* * * * * *", move |_uuid, _l| {
-            let state = Arc::clone(&state_clone);
-            Box::pin(async move {
-                if let Err(e) = run_sync(state).await {
-                    tracing::error!(error = %e, "cron.sync_failed");
-                }
-            })
-        })?)
-        .await?;
+        scheduler
+            .add(Job::new_async(expression.as_str(), move |_uuid, _l| {
+                let state = Arc::clone(&state_clone);
+                Box::pin(async move {
+                    if let Some(quiet_hours) = state.config.sync.quiet_hours
+                        && quiet_hours.contains(Local::now().hour())
+                    {
+                        tracing::debug!("cron.sync_skipped_quiet_hours");
+                        return;
+                    }
+
+                    if let Err(e) = run_sync(state).await {
+                        tracing::error!(error = %e, "cron.sync_failed");
+                    }
+                })
+            })?)
+            .await?;
+
+        tracing::info!(cron = %expression, "scheduler.job_added");
+    }
 
     scheduler.start().await?;
     Ok(scheduler)
 }
 
 pub async fn run_sync(state: Arc<AppState>) -> anyhow::Result<()> {
-    tracing::info!("cron.sync_started");
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let result = sync_recent_workouts(&state).await;
 
-    // Fetch recent workouts, say last 100
-    let workouts_response = state.hevy_client.get_workouts(1, 10).await?;
-    let mut workouts = workouts_response.workouts;
+    let outcome = if result.is_ok() { "success" } else { "error" };
+    metrics()
+        .scheduler_run_duration
+        .with_label_values(&[outcome])
+        .observe(started.elapsed().as_secs_f64());
 
-    // Filter to workouts created in the last 24 hours
-    let cutoff = Utc::now() - Duration::hours(24);
-    workouts.retain(|w| {
-        if let Ok(created) = DateTime::parse_from_rfc3339(&w.created_at) {
-            created > cutoff
-        } else {
-            false
-        }
+    state.status.record_sync(SyncStatus {
+        started_at,
+        finished_at: Utc::now(),
+        outcome: outcome.to_string(),
+        workouts_queued: *result.as_ref().unwrap_or(&0),
+        error: result.as_ref().err().map(|e| format!("{:#}", e)),
     });
 
-    tracing::info!(workout_count = workouts.len(), "workouts.fetched_recent");
+    result.map(|_| ())
+}
 
-    for workout in workouts {
-        let workout_id = workout.id.clone();
+/// Queues workouts created within the lookback window, returning how many
+/// were newly queued.
+async fn sync_recent_workouts(state: &AppState) -> anyhow::Result<usize> {
+    tracing::info!("cron.sync_started");
 
-        // Check if already processed
-        {
-            let processed = state.processed_workout_ids.lock().unwrap();
-            if processed.contains(&workout_id) {
-                tracing::debug!(%workout_id, "workout.already_processed");
-                continue;
+    let sync_config = &state.config.sync;
+    let cutoff = Utc::now() - Duration::hours(sync_config.lookback_hours);
+
+    // Workouts come back newest first, so stop paging once a page reaches past the cutoff
+    let mut workouts = Vec::new();
+    let mut page = 1;
+    loop {
+        let workouts_response = state
+            .hevy_client
+            .get_workouts(page, sync_config.page_size)
+            .await?;
+
+        let page_len = workouts_response.workouts.len();
+        let mut reached_cutoff = false;
+
+        for workout in workouts_response.workouts {
+            match DateTime::parse_from_rfc3339(&workout.created_at) {
+                Ok(created) if created > cutoff => workouts.push(workout),
+                _ => reached_cutoff = true,
             }
         }
 
-        // Process the workout using the shared function
-        process_single_workout(&state, workout_id).await;
+        if reached_cutoff
+            || page_len < sync_config.page_size as usize
+            || page >= workouts_response.page_count
+        {
+            break;
+        }
+        page += 1;
+    }
+
+    tracing::info!(
+        workout_count = workouts.len(),
+        lookback_hours = sync_config.lookback_hours,
+        "workouts.fetched_recent"
+    );
+
+    let mut queued = 0;
+    for workout in workouts {
+        // The queue skips workouts that already have a job, so webhook deliveries
+        // and earlier syncs aren't processed twice
+        if state.job_queue.enqueue(&workout.id, JobSource::Sync)? {
+            queued += 1;
+        } else {
+            tracing::debug!(workout_id = %workout.id, "workout.already_queued");
+        }
     }
 
-    tracing::info!("cron.sync_completed");
-    Ok(())
+    tracing::info!(queued, "cron.sync_completed");
+    Ok(queued)
 }
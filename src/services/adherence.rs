@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+
+use crate::clients::models::{
+    common::ExerciseSet,
+    responses::{RoutineResponse, WorkoutResponse},
+};
+use crate::prescription_store::RoutinePrescription;
+use crate::services::stalls::{PrescribedSet, Prescription};
+
+const LOAD_TOLERANCE_KG: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExerciseStatus {
+    /// Prescribed and performed.
+    Performed,
+    /// Prescribed but not performed, and nothing took its place.
+    Skipped,
+    /// Performed in place of a prescribed exercise that was skipped.
+    Substituted,
+    /// Performed without being prescribed.
+    Added,
+}
+
+impl ExerciseStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExerciseStatus::Performed => "performed",
+            ExerciseStatus::Skipped => "skipped",
+            ExerciseStatus::Substituted => "substituted",
+            ExerciseStatus::Added => "added",
+        }
+    }
+}
+
+/// One prescribed working set next to the set performed in its place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetAdherence {
+    pub index: u32,
+    pub prescribed: Option<PrescribedSet>,
+    pub performed: Option<PrescribedSet>,
+    /// Performed minus prescribed reps.
+    pub rep_delta: Option<i32>,
+    /// Performed minus prescribed weight. Not set for substitutions, where
+    /// loads of different exercises can't be compared.
+    pub load_delta_kg: Option<f32>,
+    pub completed: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExerciseAdherence {
+    pub exercise_template_id: String,
+    pub exercise_title: String,
+    pub status: ExerciseStatus,
+    /// The prescribed exercise a substitution replaced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub substituted_for: Option<String>,
+    pub prescribed_sets: u32,
+    pub completed_sets: u32,
+    pub sets: Vec<SetAdherence>,
+}
+
+/// How a workout compares to the prescription last written to its routine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdherenceReport {
+    /// The workout whose processing wrote the prescription.
+    pub prescribed_by: String,
+    pub prescribed_sets: u32,
+    pub completed_sets: u32,
+    pub exercises: Vec<ExerciseAdherence>,
+}
+
+impl AdherenceReport {
+    /// Completed share of prescribed working sets, from 0 to 1.
+    pub fn completion(&self) -> f32 {
+        if self.prescribed_sets == 0 {
+            return 1.0;
+        }
+        self.completed_sets as f32 / self.prescribed_sets as f32
+    }
+}
+
+/// Matches the performed exercises to the prescription by template id.
+/// Prescribed exercises left unmatched are paired in routine order with
+/// performed ones left unmatched as substitutions; the rest are skipped or
+/// added.
+pub fn compare(
+    workout: &WorkoutResponse,
+    routine: &RoutineResponse,
+    prescription: &RoutinePrescription,
+) -> AdherenceReport {
+    let mut prescribed: Vec<(&String, &Prescription)> = prescription.exercises.iter().collect();
+    prescribed.sort_by_key(|(template_id, _)| {
+        let position = routine
+            .exercises
+            .iter()
+            .position(|planned| &planned.exercise_template_id == *template_id)
+            .unwrap_or(usize::MAX);
+        (position, template_id.to_string())
+    });
+
+    let performed_for = |template_id: &str| {
+        workout
+            .exercises
+            .iter()
+            .find(|performed| performed.exercise_template_id == template_id)
+    };
+    let mut unmatched_performed = workout.exercises.iter().filter(|performed| {
+        !prescription
+            .exercises
+            .contains_key(&performed.exercise_template_id)
+    });
+
+    let mut exercises = Vec::new();
+    for (template_id, planned) in prescribed {
+        let (performed, status) = match performed_for(template_id) {
+            Some(performed) => (Some(performed), ExerciseStatus::Performed),
+            None => match unmatched_performed.next() {
+                Some(substitute) => (Some(substitute), ExerciseStatus::Substituted),
+                None => (None, ExerciseStatus::Skipped),
+            },
+        };
+        let same_exercise = status == ExerciseStatus::Performed;
+        let sets = compare_sets(
+            &planned.sets,
+            performed.map(|exercise| exercise.sets.as_slice()),
+            same_exercise,
+        );
+
+        exercises.push(ExerciseAdherence {
+            exercise_template_id: performed
+                .map_or(template_id, |exercise| &exercise.exercise_template_id)
+                .clone(),
+            exercise_title: performed
+                .map_or(&planned.exercise_title, |exercise| &exercise.title)
+                .clone(),
+            status,
+            substituted_for: (!same_exercise && performed.is_some()).then(|| template_id.clone()),
+            prescribed_sets: planned.sets.len() as u32,
+            completed_sets: sets.iter().filter(|set| set.completed).count() as u32,
+            sets,
+        });
+    }
+
+    for added in unmatched_performed {
+        exercises.push(ExerciseAdherence {
+            exercise_template_id: added.exercise_template_id.clone(),
+            exercise_title: added.title.clone(),
+            status: ExerciseStatus::Added,
+            substituted_for: None,
+            prescribed_sets: 0,
+            completed_sets: 0,
+            sets: compare_sets(&[], Some(&added.sets), false),
+        });
+    }
+
+    AdherenceReport {
+        prescribed_by: prescription.workout_id.clone(),
+        prescribed_sets: exercises
+            .iter()
+            .map(|exercise| exercise.prescribed_sets)
+            .sum(),
+        completed_sets: exercises
+            .iter()
+            .map(|exercise| exercise.completed_sets)
+            .sum(),
+        exercises,
+    }
+}
+
+fn compare_sets(
+    prescribed: &[PrescribedSet],
+    performed: Option<&[ExerciseSet]>,
+    same_exercise: bool,
+) -> Vec<SetAdherence> {
+    let performed: Vec<PrescribedSet> = performed
+        .unwrap_or_default()
+        .iter()
+        .filter(|set| !set.is_warmup())
+        .map(|set| PrescribedSet {
+            weight_kg: set.weight_kg,
+            reps: set.reps,
+        })
+        .collect();
+
+    (0..prescribed.len().max(performed.len()))
+        .map(|index| {
+            let target = prescribed.get(index).cloned();
+            let done = performed.get(index).cloned();
+            let rep_delta = match (&target, &done) {
+                (Some(target), Some(done)) => done
+                    .reps
+                    .zip(target.reps)
+                    .map(|(done, target)| done as i32 - target as i32),
+                _ => None,
+            };
+            let load_delta_kg = match (&target, &done) {
+                (Some(target), Some(done)) if same_exercise => {
+                    Some(done.weight_kg.unwrap_or(0.0) - target.weight_kg.unwrap_or(0.0))
+                }
+                _ => None,
+            };
+            let completed = target.is_some()
+                && done.is_some()
+                && rep_delta.unwrap_or(0) >= 0
+                && load_delta_kg.unwrap_or(0.0) >= -LOAD_TOLERANCE_KG;
+
+            SetAdherence {
+                index: index as u32,
+                prescribed: target,
+                performed: done,
+                rep_delta,
+                load_delta_kg,
+                completed,
+            }
+        })
+        .collect()
+}
+
+/// The report as a prompt section, one line per exercise with the sets that
+/// fell short or went over.
+pub fn format_for_prompt(report: &AdherenceReport) -> String {
+    let mut output = format!(
+        "LAST PRESCRIPTION VS PERFORMED:\nCompleted {} of {} prescribed working sets\n",
+        report.completed_sets, report.prescribed_sets
+    );
+
+    for exercise in &report.exercises {
+        let status = match (&exercise.status, &exercise.substituted_for) {
+            (ExerciseStatus::Substituted, Some(original)) => {
+                format!("substituted for {}", original)
+            }
+            (status, _) => status.as_str().to_string(),
+        };
+        output.push_str(&format!(
+            "- {} ({}): {}, {}/{} sets completed\n",
+            exercise.exercise_title,
+            exercise.exercise_template_id,
+            status,
+            exercise.completed_sets,
+            exercise.prescribed_sets
+        ));
+
+        if exercise.status == ExerciseStatus::Skipped {
+            continue;
+        }
+        for set in &exercise.sets {
+            let mut deltas = Vec::new();
+            if let Some(reps) = set.rep_delta.filter(|delta| *delta != 0) {
+                deltas.push(format!("{:+} reps", reps));
+            }
+            if let Some(load) = set
+                .load_delta_kg
+                .filter(|delta| delta.abs() > LOAD_TOLERANCE_KG)
+            {
+                deltas.push(format!("{:+}kg", load));
+            }
+            let line = match (&set.prescribed, &set.performed) {
+                (Some(_), None) => "not done".to_string(),
+                (None, Some(_)) => "extra set".to_string(),
+                _ if deltas.is_empty() => continue,
+                _ => deltas.join(", "),
+            };
+            output.push_str(&format!(
+                "  * Set {}: {} ({})\n",
+                set.index + 1,
+                describe(set),
+                line
+            ));
+        }
+    }
+
+    output.push_str("Don't increase the load on exercises with sets that weren't completed.\n");
+    output
+}
+
+fn describe(set: &SetAdherence) -> String {
+    let format = |set: &PrescribedSet| {
+        let reps = set.reps.map_or("?".to_string(), |reps| reps.to_string());
+        match set.weight_kg {
+            Some(weight) => format!("{}kg x {}", weight, reps),
+            None => format!("BW x {}", reps),
+        }
+    };
+    match (&set.prescribed, &set.performed) {
+        (Some(prescribed), Some(performed)) => {
+            format!(
+                "{} prescribed, {} done",
+                format(prescribed),
+                format(performed)
+            )
+        }
+        (Some(prescribed), None) => format!("{} prescribed", format(prescribed)),
+        (None, Some(performed)) => format!("{} done", format(performed)),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, set, workout};
+    use chrono::Utc;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_compares_sets_and_detects_substitutions() {
+        let planned = vec![
+            exercise("bench", "bench", vec![set("normal", Some(82.5), 7); 2]),
+            exercise("squat", "squat", vec![set("normal", Some(100.0), 5); 2]),
+            exercise("row", "row", vec![set("normal", Some(60.0), 8); 2]),
+        ];
+        let routine = RoutineResponse {
+            id: "r1".to_string(),
+            title: "Day 1 - Week 3".to_string(),
+            notes: None,
+            folder_id: None,
+            updated_at: String::new(),
+            created_at: String::new(),
+            exercises: planned.clone(),
+        };
+        let prescription = RoutinePrescription {
+            workout_id: "w0".to_string(),
+            recorded_at: Utc::now(),
+            exercises: planned
+                .iter()
+                .map(|exercise| {
+                    (
+                        exercise.exercise_template_id.clone(),
+                        Prescription::from_exercise(exercise),
+                    )
+                })
+                .collect::<HashMap<_, _>>(),
+            misses: HashMap::new(),
+        };
+        let workout = workout(
+            "w1",
+            "Day 1 - Week 3",
+            "r1",
+            vec![
+                exercise(
+                    "bench",
+                    "bench",
+                    vec![
+                        set("warmup", Some(40.0), 10),
+                        set("normal", Some(82.5), 8),
+                        set("normal", Some(80.0), 7),
+                    ],
+                ),
+                exercise(
+                    "leg_press",
+                    "leg_press",
+                    vec![set("normal", Some(180.0), 10); 2],
+                ),
+            ],
+        );
+
+        let report = compare(&workout, &routine, &prescription);
+
+        assert_eq!(report.prescribed_sets, 6);
+        assert_eq!(report.completed_sets, 3);
+        let bench = &report.exercises[0];
+        assert_eq!(bench.completed_sets, 1);
+        assert_eq!(bench.sets[0].rep_delta, Some(1));
+        assert_eq!(bench.sets[1].load_delta_kg, Some(-2.5));
+
+        let squat = &report.exercises[1];
+        assert_eq!(squat.status, ExerciseStatus::Substituted);
+        assert_eq!(squat.exercise_template_id, "leg_press");
+        assert_eq!(squat.substituted_for.as_deref(), Some("squat"));
+        assert_eq!(squat.completed_sets, 2);
+        assert_eq!(squat.sets[0].load_delta_kg, None);
+
+        assert_eq!(report.exercises[2].status, ExerciseStatus::Skipped);
+        assert_eq!(report.exercises[2].completed_sets, 0);
+        assert_eq!(report.completion(), 0.5);
+
+        let prompt = format_for_prompt(&report);
+        assert!(prompt.contains("- leg_press (leg_press): substituted for squat, 2/2 sets"));
+        assert!(prompt.contains("  * Set 2: 82.5kg x 7 prescribed, 80kg x 7 done (-2.5kg)"));
+        assert!(prompt.contains("- row (row): skipped, 0/2 sets completed\nDon't"));
+    }
+}
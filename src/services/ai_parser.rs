@@ -1,21 +1,88 @@
 use anyhow::Result;
 use regex::Regex;
-use serde_json::Value;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
 
-use crate::clients::models::common::Exercise;
+use crate::clients::models::common::{Exercise, ExerciseSet};
+use crate::services::lenient_json;
 use crate::services::progressive_overload::ProgressiveOverloadResponse;
 
+/// What the parser had to do to make sense of a model response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepairKind {
+    /// JSON was cut out of a code fence or surrounding prose.
+    Extracted,
+    /// Invalid JSON syntax was rewritten (trailing commas, comments, ...).
+    Syntax,
+    /// A placeholder such as "N/A" was read as null.
+    Placeholder,
+    /// A value of the wrong type was converted, e.g. "7" to 7.
+    Coerced,
+    /// A missing field was filled in.
+    Defaulted,
+    /// A value that could not be used was dropped.
+    Dropped,
+}
+
+impl RepairKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepairKind::Extracted => "extracted",
+            RepairKind::Syntax => "syntax",
+            RepairKind::Placeholder => "placeholder",
+            RepairKind::Coerced => "coerced",
+            RepairKind::Defaulted => "defaulted",
+            RepairKind::Dropped => "dropped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub kind: RepairKind,
+    /// Where in the response the repair was made, e.g.
+    /// `updated_exercises[0].sets[1].reps`, or `$` for the whole response.
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Default)]
+struct Repairs(Vec<ParseWarning>);
+
+impl Repairs {
+    fn push(&mut self, kind: RepairKind, path: &str, message: String) {
+        self.0.push(ParseWarning {
+            kind,
+            path: path.to_string(),
+            message,
+        });
+    }
+}
+
+/// Parses a model response as leniently as is safe: JSON is dug out of
+/// fences and prose, JSON5-style syntax is rewritten, and field values are
+/// coerced to the expected types. Every repair is reported in
+/// `parse_warnings`. Only a response with no usable JSON object, no
+/// `updated_exercises` array or an exercise without a template id fails.
 pub fn parse_gemini_response(response: &str) -> Result<ProgressiveOverloadResponse> {
-    let json_content = extract_json_from_response(response);
-    let parsed_json = parse_json_string(&json_content)?;
-    let exercises = extract_exercises_from_json(&parsed_json)?;
-    let week_number = extract_week_number_from_json(&parsed_json);
-    let routine_title = extract_routine_title_from_json(&parsed_json);
+    let mut repairs = Repairs::default();
+
+    let json_content = extract_json_from_response(response, &mut repairs);
+    let parsed_json = parse_json_string(&json_content, &mut repairs)?;
+    let exercises = extract_exercises_from_json(&parsed_json, &mut repairs)?;
+    let week_number = extract_week_number_from_json(&parsed_json, &mut repairs);
+    let routine_title = extract_routine_title_from_json(&parsed_json, &mut repairs);
 
     Ok(ProgressiveOverloadResponse {
         updated_exercises: exercises,
         week_number,
         routine_title,
+        stall_adjustments: Vec::new(),
+        bodyweight_exercises: HashMap::new(),
+        rest_adjustments: Vec::new(),
+        parse_warnings: repairs.0,
     })
 }
 
@@ -67,45 +134,421 @@ pub fn extract_week_from_title(title: &str) -> Option<u32> {
         .and_then(|m| m.as_str().parse().ok())
 }
 
-fn extract_json_from_response(response: &str) -> String {
-    if let Some(json_block_start) = response.find("```json") {
-        let content_start = json_block_start + "```json".len();
+fn extract_json_from_response(response: &str, repairs: &mut Repairs) -> String {
+    let trimmed = response.trim();
 
-        if let Some(remaining_content) = response.get(content_start..) {
-            if let Some(code_block_end) = remaining_content.find("```") {
-                return remaining_content[..code_block_end].trim().to_string();
-            }
-            return remaining_content.trim().to_string();
+    // Prefer a fenced block, tagged ```json or bare ```
+    let fence = trimmed
+        .find("```json")
+        .map(|start| start + "```json".len())
+        .or_else(|| trimmed.find("```").map(|start| start + "```".len()));
+
+    if let Some(remaining_content) = fence.and_then(|start| trimmed.get(start..)) {
+        repairs.push(
+            RepairKind::Extracted,
+            "$",
+            "took JSON from a code fence".to_string(),
+        );
+        if let Some(code_block_end) = remaining_content.find("```") {
+            return remaining_content[..code_block_end].trim().to_string();
         }
+        return remaining_content.trim().to_string();
     }
 
-    response.trim().to_string()
+    // Otherwise drop any prose around the outermost object
+    match (trimmed.find('{'), trimmed.rfind('}')) {
+        (Some(start), Some(end)) if start < end => {
+            if start > 0 || end < trimmed.len() - 1 {
+                repairs.push(
+                    RepairKind::Extracted,
+                    "$",
+                    "removed text around the JSON object".to_string(),
+                );
+            }
+            trimmed[start..=end].to_string()
+        }
+        _ => trimmed.to_string(),
+    }
 }
 
-fn parse_json_string(json_str: &str) -> Result<Value> {
-    serde_json::from_str(json_str)
-        .map_err(|e| anyhow::anyhow!("Failed to parse JSON response: {}", e))
+fn parse_json_string(json_str: &str, repairs: &mut Repairs) -> Result<Value> {
+    let strict_error = match serde_json::from_str(json_str) {
+        Ok(value) => return Ok(value),
+        Err(e) => e,
+    };
+
+    let repaired = lenient_json::repair(json_str);
+    let value = serde_json::from_str(&repaired.json)
+        .map_err(|_| anyhow::anyhow!("Failed to parse JSON response: {}", strict_error))?;
+
+    for (repair, count) in repaired.repairs {
+        repairs.push(
+            RepairKind::Syntax,
+            "$",
+            format!("fixed {} x{}", repair, count),
+        );
+    }
+    Ok(value)
 }
 
-fn extract_exercises_from_json(json: &Value) -> Result<Vec<Exercise>> {
+fn extract_exercises_from_json(json: &Value, repairs: &mut Repairs) -> Result<Vec<Exercise>> {
     let exercises_value = json
         .get("updated_exercises")
         .ok_or_else(|| anyhow::anyhow!("Missing 'updated_exercises' field in JSON response"))?;
+    let exercises = exercises_value
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse exercises array: not an array"))?;
+
+    exercises
+        .iter()
+        .enumerate()
+        .map(|(position, value)| {
+            let path = format!("updated_exercises[{}]", position);
+            parse_exercise(value, position, &path, repairs)
+        })
+        .collect()
+}
+
+fn parse_exercise(
+    value: &Value,
+    position: usize,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Result<Exercise> {
+    let object = value.as_object().ok_or_else(|| {
+        anyhow::anyhow!("Failed to parse exercises array: {} is not an object", path)
+    })?;
+
+    let exercise_template_id = string_field(object, "exercise_template_id", path, repairs)
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Failed to parse exercises array: {} has no exercise_template_id",
+                path
+            )
+        })?;
+
+    let sets = object
+        .get("sets")
+        .and_then(Value::as_array)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse exercises array: {} has no sets", path))?
+        .iter()
+        .enumerate()
+        .filter_map(|(set_position, set)| {
+            let set_path = format!("{}.sets[{}]", path, set_position);
+            parse_set(set, set_position, &set_path, repairs)
+        })
+        .collect();
+
+    Ok(Exercise {
+        index: index_field(object, position, path, repairs),
+        title: string_field(object, "title", path, repairs).unwrap_or_else(|| {
+            repairs.push(
+                RepairKind::Defaulted,
+                &format!("{}.title", path),
+                "missing title left empty".to_string(),
+            );
+            String::new()
+        }),
+        notes: string_field(object, "notes", path, repairs),
+        exercise_template_id,
+        superset_id: u32_field(object, "superset_id", path, repairs),
+        rest_seconds: u32_field(object, "rest_seconds", path, repairs),
+        sets,
+    })
+}
 
-    serde_json::from_value(exercises_value.clone())
-        .map_err(|e| anyhow::anyhow!("Failed to parse exercises array: {}", e))
+fn parse_set(
+    value: &Value,
+    position: usize,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Option<ExerciseSet> {
+    let Some(object) = value.as_object() else {
+        repairs.push(
+            RepairKind::Dropped,
+            path,
+            format!("dropped set that is not an object: {}", value),
+        );
+        return None;
+    };
+
+    let set_type = match string_field(object, "type", path, repairs) {
+        Some(set_type) if set_type.trim().is_empty() => None,
+        Some(set_type) => {
+            let normalized = set_type.trim().to_ascii_lowercase();
+            if normalized != set_type {
+                repairs.push(
+                    RepairKind::Coerced,
+                    &format!("{}.type", path),
+                    format!("read {:?} as {:?}", set_type, normalized),
+                );
+            }
+            Some(normalized)
+        }
+        None => None,
+    };
+
+    Some(ExerciseSet {
+        index: index_field(object, position, path, repairs),
+        set_type: set_type.unwrap_or_else(|| {
+            repairs.push(
+                RepairKind::Defaulted,
+                &format!("{}.type", path),
+                "missing set type defaulted to \"normal\"".to_string(),
+            );
+            "normal".to_string()
+        }),
+        weight_kg: f32_field(object, "weight_kg", path, repairs),
+        reps: u32_field(object, "reps", path, repairs),
+        distance_meters: u32_field(object, "distance_meters", path, repairs),
+        duration_seconds: u32_field(object, "duration_seconds", path, repairs),
+        rpe: f32_field(object, "rpe", path, repairs),
+        custom_metric: f32_field(object, "custom_metric", path, repairs),
+        rep_range: None,
+    })
 }
 
-fn extract_week_number_from_json(json: &Value) -> u32 {
-    json.get("week_number")
-        .and_then(|w| w.as_u64())
-        .map(|n| n as u32)
-        .unwrap_or(1)
+/// The `index` field, or the item's position in its array when the model
+/// left it out.
+fn index_field(
+    object: &Map<String, Value>,
+    position: usize,
+    path: &str,
+    repairs: &mut Repairs,
+) -> u32 {
+    u32_field(object, "index", path, repairs).unwrap_or_else(|| {
+        repairs.push(
+            RepairKind::Defaulted,
+            &format!("{}.index", path),
+            format!("missing index defaulted to {}", position),
+        );
+        position as u32
+    })
 }
 
-fn extract_routine_title_from_json(json: &Value) -> String {
-    json.get("routine_title")
-        .and_then(|t| t.as_str())
-        .unwrap_or("Updated Routine")
-        .to_string()
+fn string_field(
+    object: &Map<String, Value>,
+    key: &str,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Option<String> {
+    let path = format!("{}.{}", path, key);
+    match object.get(key)? {
+        Value::Null => None,
+        Value::String(text) => Some(text.clone()),
+        Value::Number(number) => {
+            repairs.push(
+                RepairKind::Coerced,
+                &path,
+                format!("read {} as a string", number),
+            );
+            Some(number.to_string())
+        }
+        other => {
+            repairs.push(
+                RepairKind::Dropped,
+                &path,
+                format!("dropped non-string value {}", other),
+            );
+            None
+        }
+    }
+}
+
+fn f32_field(
+    object: &Map<String, Value>,
+    key: &str,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Option<f32> {
+    number_field(object, key, path, repairs).map(|number| number as f32)
+}
+
+fn u32_field(
+    object: &Map<String, Value>,
+    key: &str,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Option<u32> {
+    let number = number_field(object, key, path, repairs)?;
+    let path = format!("{}.{}", path, key);
+
+    if number < 0.0 || number > u32::MAX as f64 {
+        repairs.push(
+            RepairKind::Dropped,
+            &path,
+            format!("dropped out of range value {}", number),
+        );
+        return None;
+    }
+    if number.fract() != 0.0 {
+        repairs.push(
+            RepairKind::Coerced,
+            &path,
+            format!("rounded {} to a whole number", number),
+        );
+    }
+    Some(number.round() as u32)
+}
+
+/// A numeric field, accepting numbers written as strings ("7", "82.5kg",
+/// "8-10" reads as 8) and treating placeholders like "N/A" as null.
+fn number_field(
+    object: &Map<String, Value>,
+    key: &str,
+    path: &str,
+    repairs: &mut Repairs,
+) -> Option<f64> {
+    let path = format!("{}.{}", path, key);
+    match object.get(key)? {
+        Value::Null => None,
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) if is_placeholder(text) => {
+            repairs.push(
+                RepairKind::Placeholder,
+                &path,
+                format!("read {:?} as null", text),
+            );
+            None
+        }
+        Value::String(text) => match leading_number(text) {
+            Some(number) => {
+                repairs.push(
+                    RepairKind::Coerced,
+                    &path,
+                    format!("read {:?} as {}", text, number),
+                );
+                Some(number)
+            }
+            None => {
+                repairs.push(
+                    RepairKind::Dropped,
+                    &path,
+                    format!("dropped non-numeric value {:?}", text),
+                );
+                None
+            }
+        },
+        other => {
+            repairs.push(
+                RepairKind::Dropped,
+                &path,
+                format!("dropped non-numeric value {}", other),
+            );
+            None
+        }
+    }
+}
+
+fn is_placeholder(text: &str) -> bool {
+    matches!(
+        text.trim().to_ascii_lowercase().as_str(),
+        "" | "n/a" | "na" | "none" | "null" | "-" | "--" | "tbd" | "bw" | "bodyweight"
+    )
+}
+
+fn leading_number(text: &str) -> Option<f64> {
+    let number_regex = Regex::new(r"^\s*(-?\d+(?:[.,]\d+)?)").unwrap();
+    number_regex
+        .captures(text)
+        .and_then(|captures| captures[1].replace(',', ".").parse().ok())
+}
+
+fn extract_week_number_from_json(json: &Value, repairs: &mut Repairs) -> u32 {
+    let object = json.as_object().cloned().unwrap_or_default();
+    u32_field(&object, "week_number", "$", repairs).unwrap_or_else(|| {
+        repairs.push(
+            RepairKind::Defaulted,
+            "$.week_number",
+            "missing week_number defaulted to 1".to_string(),
+        );
+        1
+    })
+}
+
+fn extract_routine_title_from_json(json: &Value, repairs: &mut Repairs) -> String {
+    let object = json.as_object().cloned().unwrap_or_default();
+    string_field(&object, "routine_title", "$", repairs).unwrap_or_else(|| {
+        repairs.push(
+            RepairKind::Defaulted,
+            "$.routine_title",
+            "missing routine_title defaulted to \"Updated Routine\"".to_string(),
+        );
+        "Updated Routine".to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::golden::{assert_golden, testdata_path};
+
+    #[test]
+    fn test_parses_messy_model_output_corpus() {
+        let mut inputs: Vec<_> = std::fs::read_dir(testdata_path("llm_outputs"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect();
+        inputs.sort();
+        assert!(!inputs.is_empty());
+
+        for input in inputs {
+            let name = input.file_stem().unwrap().to_string_lossy().into_owned();
+            let response = std::fs::read_to_string(&input).unwrap();
+            let parsed = parse_gemini_response(&response)
+                .unwrap_or_else(|e| panic!("{} failed to parse: {:#}", name, e));
+
+            assert_golden(
+                &format!("llm_outputs/{}.expected.json", name),
+                &(serde_json::to_string_pretty(&parsed).unwrap() + "\n"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_records_a_warning_for_each_repair() {
+        let response = parse_gemini_response(
+            r#"Sure! {updated_exercises: [{"exercise_template_id": "bench", "title": "Bench",
+                "sets": [{"type": "Normal", "weight_kg": "82.5kg", "reps": "7", "rpe": "N/A",},]}],
+                "week_number": 3, "routine_title": "Day 1 - Week 3"}"#,
+        )
+        .unwrap();
+
+        let set = &response.updated_exercises[0].sets[0];
+        assert_eq!(set.set_type, "normal");
+        assert_eq!(set.weight_kg, Some(82.5));
+        assert_eq!(set.reps, Some(7));
+        assert_eq!(set.rpe, None);
+
+        let warnings: Vec<(RepairKind, &str)> = response
+            .parse_warnings
+            .iter()
+            .map(|warning| (warning.kind, warning.path.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            [
+                (RepairKind::Extracted, "$"),
+                (RepairKind::Syntax, "$"),
+                (RepairKind::Syntax, "$"),
+                (RepairKind::Coerced, "updated_exercises[0].sets[0].type"),
+                (RepairKind::Defaulted, "updated_exercises[0].sets[0].index"),
+                (
+                    RepairKind::Coerced,
+                    "updated_exercises[0].sets[0].weight_kg"
+                ),
+                (RepairKind::Coerced, "updated_exercises[0].sets[0].reps"),
+                (RepairKind::Placeholder, "updated_exercises[0].sets[0].rpe"),
+                (RepairKind::Defaulted, "updated_exercises[0].index"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_exercise_without_template_id() {
+        let error = parse_gemini_response(r#"{"updated_exercises": [{"sets": []}]}"#).unwrap_err();
+        assert!(error.to_string().contains("exercise_template_id"));
+    }
 }
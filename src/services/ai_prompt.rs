@@ -1,50 +1,96 @@
+use std::collections::HashMap;
+
 use crate::clients::models::{
     common::{Exercise, ExerciseSet},
     responses::{RoutineResponse, WorkoutResponse},
 };
 
+use crate::services::adherence::{self, AdherenceReport};
+use crate::services::bodyweight::LoadKind;
+use crate::services::conditioning;
 use crate::services::deload::DeloadContext;
 
-pub fn format_workout_for_prompt(workout: &WorkoutResponse) -> String {
+/// Recorded with every audit entry. Bump whenever the prompt wording or
+/// structure changes so old prescriptions can be told apart from new ones.
+pub const PROMPT_VERSION: &str = "5";
+
+pub fn format_workout_for_prompt(
+    workout: &WorkoutResponse,
+    load_kinds: &HashMap<String, LoadKind>,
+) -> String {
     let mut output = format!("Workout Title: {}\n", workout.title);
     output.push_str(&format!("Start Time: {}\n", workout.start_time));
     output.push_str(&format!("End Time: {}\n", workout.end_time));
     output.push_str("\nExercises:\n");
-    output.push_str(&format_exercise_list(&workout.exercises));
+    output.push_str(&format_exercise_list(&workout.exercises, load_kinds));
     output
 }
 
-pub fn format_routine_for_prompt(routine: &RoutineResponse) -> String {
+pub fn format_routine_for_prompt(
+    routine: &RoutineResponse,
+    load_kinds: &HashMap<String, LoadKind>,
+) -> String {
     let mut output = format!(
         "ROUTINE TEMPLATE:\nRoutine: {}\n\nExercises:\n",
         routine.title
     );
 
-    output.push_str(&format_exercise_list(&routine.exercises));
+    output.push_str(&format_exercise_list(&routine.exercises, load_kinds));
     output
 }
 
-fn format_exercise_list(exercises: &[Exercise]) -> String {
-    exercises
-        .iter()
-        .map(|exercise| {
-            let mut block = format!("- {} ({})\n", exercise.title, exercise.exercise_template_id);
-            block.push_str(&format_set_list(&exercise.sets));
-            block.push('\n');
-            block
-        })
-        .collect::<Vec<_>>()
-        .join("")
+fn format_exercise_list(exercises: &[Exercise], load_kinds: &HashMap<String, LoadKind>) -> String {
+    let mut output = String::new();
+    let mut current_superset = None;
+
+    for exercise in exercises {
+        // Consecutive exercises sharing a superset id are done back to back
+        if exercise.superset_id != current_superset {
+            if let Some(superset_id) = exercise.superset_id {
+                output.push_str(&format!(
+                    "Superset {} (alternate sets between these exercises):\n",
+                    superset_id
+                ));
+            }
+            current_superset = exercise.superset_id;
+        }
+
+        let rest = exercise
+            .rest_seconds
+            .map(|seconds| format!(", rest {}", conditioning::format_duration(seconds)))
+            .unwrap_or_default();
+        output.push_str(&format!(
+            "- {} ({}){}\n",
+            exercise.title, exercise.exercise_template_id, rest
+        ));
+        output.push_str(&format_set_list(
+            &exercise.sets,
+            load_kinds.get(&exercise.exercise_template_id).copied(),
+        ));
+        output.push('\n');
+    }
+
+    output
 }
 
-fn format_set_list(sets: &[ExerciseSet]) -> String {
+fn format_set_list(sets: &[ExerciseSet], load_kind: Option<LoadKind>) -> String {
     sets.iter()
         .map(|set| {
+            let performed = match load_kind {
+                Some(kind) => Some(kind.describe_set(set)),
+                None => conditioning::describe_set(set),
+            };
+            let performed = performed.unwrap_or_else(|| {
+                format!(
+                    "{} x {}",
+                    format_weight(set.weight_kg),
+                    format_reps(set.reps)
+                )
+            });
             format!(
-                "  * Set {}: {} x {} ({})\n",
+                "  * Set {}: {} ({})\n",
                 set.index + 1,
-                format_weight(set.weight_kg),
-                format_reps(set.reps),
+                performed,
                 set.set_type
             )
         })
@@ -71,23 +117,57 @@ pub fn build_progressive_overload_prompt(
     deload_context: &DeloadContext,
     current_week_index: u32,
     routine_title: &str,
+    adherence: Option<&AdherenceReport>,
+    load_kinds: &HashMap<String, LoadKind>,
 ) -> String {
+    // Only workouts following a prescription this service wrote get the
+    // comparison, so prompts for fresh routines are unchanged
+    let adherence = adherence
+        .map(|report| format!("\n\n{}", adherence::format_for_prompt(report).trim_end()))
+        .unwrap_or_default();
+    let conditioning = if workout
+        .exercises
+        .iter()
+        .flat_map(|exercise| &exercise.sets)
+        .any(|set| conditioning::describe_set(set).is_some())
+    {
+        "\n- Cardio sets are logged as distance and time: prescribe them with distance_meters and duration_seconds and keep weight_kg and reps null"
+    } else {
+        ""
+    };
+    let bodyweight = if load_kinds
+        .values()
+        .any(|kind| *kind != LoadKind::Bodyweight)
+    {
+        "\n- BW+ is load added to bodyweight and assist - is machine or band assistance; for those exercises weight_kg is the added load or the assistance, so less assistance is progress"
+    } else {
+        ""
+    };
+    let supersets = if routine
+        .exercises
+        .iter()
+        .any(|exercise| exercise.superset_id.is_some())
+    {
+        "\n- Supersets alternate sets with little rest between them: keep each exercise's superset_id so the pairs stay together"
+    } else {
+        ""
+    };
+
     format!(
         r#"You are a professional strength and conditioning coach specializing in block periodization for an 8-week strength-focused training cycle.
 
 CURRENT WORKOUT DATA:
 {}
 
-{}{}
+{}{}{}
 
 TRAINING CONTEXT:
 - Client is a hybrid athlete (strength + cardio)
 - Focuses on main compound movements: Bench Press, Squat, Overhead Press, Romanian Deadlift, Pendlay Row
 - Prefers low-moderate volume (2-4 sets per exercise)
 - Uses 3-day split: Day 1 (Upper), Day 2 (Lower), Day 3 (Full Body)
-- Prioritizes strength gains over hypertrophy
+- Prioritizes strength gains over hypertrophy{}{}{}
 - Currently in week {} of 8-week block
-- If there is a set with 1 rep with weight of 1, then it was a to failure set on an arbitrary weight. Keep the weight at 1 when.
 - The smallest weight plate for barbell exercises available is 2.5kg (5kg if both sides)
 - Don't add a warmup, if there was a warmup from the workout leave it as is{}
 
@@ -138,9 +218,13 @@ Return ONLY a JSON object with this exact structure:
 
 CURRENT WEEK: {}
 NEXT WEEK TARGET: {}"#,
-        format_workout_for_prompt(workout),
-        format_routine_for_prompt(routine),
+        format_workout_for_prompt(workout, load_kinds),
+        format_routine_for_prompt(routine, load_kinds),
         deload_context.reference_data,
+        adherence,
+        conditioning,
+        bodyweight,
+        supersets,
         current_week_index,
         deload_context.cycle_instruction,
         deload_context.next_week_index,
@@ -0,0 +1,284 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    responses::{RoutineResponse, WorkoutResponse},
+};
+use crate::services::double_progression::RepTarget;
+
+/// How an exercise's logged weight relates to the lifter's bodyweight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadKind {
+    /// Reps only, e.g. push ups. There's no load to log.
+    Bodyweight,
+    /// Weight is added to bodyweight, e.g. dips with a belt.
+    Weighted,
+    /// Weight is taken off bodyweight by a machine or band.
+    Assisted,
+}
+
+impl LoadKind {
+    /// The kind for a Hevy exercise template type, `None` for barbell,
+    /// machine and cardio types.
+    pub fn from_exercise_type(exercise_type: &str) -> Option<Self> {
+        match exercise_type {
+            "reps_only" => Some(LoadKind::Bodyweight),
+            "bodyweight_reps" => Some(LoadKind::Weighted),
+            "bodyweight_assisted_reps" => Some(LoadKind::Assisted),
+            _ => None,
+        }
+    }
+
+    /// Guesses the kind from the logged sets, for when the template can't
+    /// be looked up. Only reps without any weight can be told apart.
+    pub fn infer(exercise: &Exercise) -> Option<Self> {
+        let mut working = exercise.working_sets().peekable();
+        (working.peek().is_some()
+            && working.all(|set| set.weight_kg.is_none() && set.reps.is_some()))
+        .then_some(LoadKind::Bodyweight)
+    }
+
+    /// The load actually moved: bodyweight plus added load, or minus
+    /// assistance.
+    pub fn effective_load(self, bodyweight_kg: f32, weight_kg: Option<f32>) -> f32 {
+        let weight = weight_kg.unwrap_or(0.0);
+        match self {
+            LoadKind::Bodyweight => bodyweight_kg,
+            LoadKind::Weighted => bodyweight_kg + weight,
+            LoadKind::Assisted => (bodyweight_kg - weight).max(0.0),
+        }
+    }
+
+    /// `BW x 8`, `BW+10kg x 6` or `assist -20kg x 8`.
+    pub fn describe_set(self, set: &ExerciseSet) -> String {
+        let reps = set
+            .reps
+            .map_or_else(|| "?".to_string(), |reps| reps.to_string());
+        let load = match (self, set.weight_kg) {
+            (LoadKind::Weighted, Some(weight)) if weight > 0.0 => {
+                format!("BW+{}", format_kg(weight))
+            }
+            (LoadKind::Assisted, Some(weight)) if weight > 0.0 => {
+                format!("assist -{}", format_kg(weight))
+            }
+            _ => "BW".to_string(),
+        };
+        format!("{} x {}", load, reps)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyweightStep {
+    /// Not every working set reached the top of the range yet.
+    AddReps,
+    /// Every working set reached the top, so load is added to the belt.
+    AddLoad,
+    /// Every working set reached the top, so assistance comes off.
+    ReduceAssistance,
+    /// Every working set reached the top but there's nothing to add or take
+    /// off in Hevy, so reps stay at the top.
+    TopOfRange,
+}
+
+impl BodyweightStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BodyweightStep::AddReps => "add_reps",
+            BodyweightStep::AddLoad => "add_load",
+            BodyweightStep::ReduceAssistance => "reduce_assistance",
+            BodyweightStep::TopOfRange => "top_of_range",
+        }
+    }
+}
+
+/// Reps first, then load, for bodyweight, weighted and assisted exercises.
+/// The range comes from the routine sets when they have one, otherwise
+/// `default_range`.
+#[derive(Debug, Clone, Default)]
+pub struct BodyweightProgression {
+    pub increment_kg: f32,
+    pub default_range: RepTarget,
+}
+
+impl BodyweightProgression {
+    /// Replaces the sets of every exercise in `exercises` listed in `kinds`
+    /// with the next step from what was performed in `workout`. Returns the
+    /// template id and step for each exercise changed.
+    pub fn apply(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+        kinds: &HashMap<String, LoadKind>,
+        exercises: &mut [Exercise],
+    ) -> Vec<(String, BodyweightStep)> {
+        let mut applied = Vec::new();
+
+        for exercise in exercises.iter_mut() {
+            let template_id = &exercise.exercise_template_id;
+            let Some(&kind) = kinds.get(template_id) else {
+                continue;
+            };
+            let Some(performed) = workout
+                .exercises
+                .iter()
+                .find(|performed| &performed.exercise_template_id == template_id)
+            else {
+                continue;
+            };
+            let range = routine
+                .exercises
+                .iter()
+                .find(|planned| &planned.exercise_template_id == template_id)
+                .and_then(|planned| {
+                    planned
+                        .working_sets()
+                        .find_map(|set| set.rep_range.as_ref().and_then(RepTarget::from_rep_range))
+                })
+                .unwrap_or(self.default_range);
+
+            let (sets, step) = self.progress(performed, kind, range);
+            exercise.sets = sets;
+            applied.push((template_id.clone(), step));
+        }
+
+        applied
+    }
+
+    pub fn progress(
+        &self,
+        performed: &Exercise,
+        kind: LoadKind,
+        range: RepTarget,
+    ) -> (Vec<ExerciseSet>, BodyweightStep) {
+        let mut working_sets = performed.working_sets().peekable();
+        let topped_out = working_sets.peek().is_some()
+            && working_sets.all(|set| set.reps.is_some_and(|reps| reps >= range.max));
+        let assisted = performed
+            .sets
+            .iter()
+            .any(|set| set.weight_kg.is_some_and(|weight| weight > 0.0));
+        let step = match kind {
+            _ if !topped_out => BodyweightStep::AddReps,
+            LoadKind::Weighted => BodyweightStep::AddLoad,
+            LoadKind::Assisted if assisted => BodyweightStep::ReduceAssistance,
+            LoadKind::Assisted | LoadKind::Bodyweight => BodyweightStep::TopOfRange,
+        };
+
+        let sets = performed
+            .sets
+            .iter()
+            .map(|set| {
+                if set.is_warmup() {
+                    return set.clone();
+                }
+
+                let (weight_kg, reps) = match step {
+                    BodyweightStep::AddReps => (
+                        set.weight_kg,
+                        (set.reps.unwrap_or(0) + 1).clamp(range.min, range.max),
+                    ),
+                    BodyweightStep::AddLoad => (
+                        Some(set.weight_kg.unwrap_or(0.0) + self.increment_kg),
+                        range.min,
+                    ),
+                    BodyweightStep::ReduceAssistance => (
+                        set.weight_kg
+                            .map(|weight| (weight - self.increment_kg).max(0.0)),
+                        range.min,
+                    ),
+                    BodyweightStep::TopOfRange => (set.weight_kg, range.max),
+                };
+
+                ExerciseSet {
+                    weight_kg,
+                    reps: Some(reps),
+                    rpe: None,
+                    rep_range: Some(range.to_rep_range()),
+                    ..set.clone()
+                }
+            })
+            .collect();
+
+        (sets, step)
+    }
+}
+
+fn format_kg(weight: f32) -> String {
+    if weight.fract().abs() > f32::EPSILON {
+        format!("{:.1}kg", weight)
+    } else {
+        format!("{:.0}kg", weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, set};
+
+    fn dip(sets: Vec<ExerciseSet>) -> Exercise {
+        exercise("dip", "Dip", sets)
+    }
+
+    fn progression() -> BodyweightProgression {
+        BodyweightProgression {
+            increment_kg: 2.5,
+            default_range: RepTarget { min: 6, max: 10 },
+        }
+    }
+
+    #[test]
+    fn test_reps_then_load_for_each_kind() {
+        let range = progression().default_range;
+
+        let (sets, step) = progression().progress(
+            &dip(vec![
+                set("normal", Some(10.0), 8),
+                set("normal", Some(10.0), 7),
+            ]),
+            LoadKind::Weighted,
+            range,
+        );
+        assert_eq!(step, BodyweightStep::AddReps);
+        assert_eq!(LoadKind::Weighted.describe_set(&sets[0]), "BW+10kg x 9");
+        assert_eq!(sets[1].reps, Some(8));
+
+        let (sets, step) = progression().progress(
+            &dip(vec![set("normal", None, 10), set("normal", None, 10)]),
+            LoadKind::Weighted,
+            range,
+        );
+        assert_eq!(step, BodyweightStep::AddLoad);
+        assert_eq!(LoadKind::Weighted.describe_set(&sets[0]), "BW+2.5kg x 6");
+
+        let (sets, step) = progression().progress(
+            &dip(vec![set("normal", Some(20.0), 10)]),
+            LoadKind::Assisted,
+            range,
+        );
+        assert_eq!(step, BodyweightStep::ReduceAssistance);
+        assert_eq!(
+            LoadKind::Assisted.describe_set(&sets[0]),
+            "assist -17.5kg x 6"
+        );
+
+        let push_ups = dip(vec![set("normal", None, 10)]);
+        assert_eq!(LoadKind::infer(&push_ups), Some(LoadKind::Bodyweight));
+        let (sets, step) = progression().progress(&push_ups, LoadKind::Bodyweight, range);
+        assert_eq!(step, BodyweightStep::TopOfRange);
+        assert_eq!(LoadKind::Bodyweight.describe_set(&sets[0]), "BW x 10");
+    }
+
+    #[test]
+    fn test_effective_load() {
+        assert_eq!(LoadKind::Weighted.effective_load(80.0, Some(20.0)), 100.0);
+        assert_eq!(LoadKind::Assisted.effective_load(80.0, Some(30.0)), 50.0);
+        assert_eq!(LoadKind::Bodyweight.effective_load(80.0, None), 80.0);
+        assert_eq!(
+            LoadKind::infer(&dip(vec![set("normal", Some(60.0), 5)])),
+            None
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    responses::WorkoutResponse,
+};
+
+/// How a conditioning exercise is logged in Hevy, which decides what it
+/// progresses by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditioningKind {
+    /// Runs, rows and rides logged as distance and time.
+    Distance,
+    /// Time-only work such as a bike session or a plank.
+    Duration,
+}
+
+impl ConditioningKind {
+    /// The kind for a Hevy exercise template type, `None` for strength types.
+    pub fn from_exercise_type(exercise_type: &str) -> Option<Self> {
+        match exercise_type {
+            "distance_duration" => Some(ConditioningKind::Distance),
+            "duration" => Some(ConditioningKind::Duration),
+            _ => None,
+        }
+    }
+
+    /// Guesses the kind from the logged sets, for when the template can't
+    /// be looked up. Sets with reps or load are never conditioning.
+    pub fn infer(exercise: &Exercise) -> Option<Self> {
+        let working: Vec<_> = exercise.working_sets().collect();
+        if working.is_empty()
+            || working
+                .iter()
+                .any(|set| set.reps.is_some() || set.weight_kg.is_some())
+        {
+            return None;
+        }
+        if working.iter().any(|set| set.distance_meters.is_some()) {
+            Some(ConditioningKind::Distance)
+        } else if working.iter().any(|set| set.duration_seconds.is_some()) {
+            Some(ConditioningKind::Duration)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConditioningStep {
+    /// Distance or time went up by at most the weekly ramp.
+    Extend,
+    /// Same intervals, each at a faster target pace.
+    FasterPace,
+    /// Nothing could be added without breaking the ramp limit.
+    Hold,
+}
+
+impl ConditioningStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConditioningStep::Extend => "extend",
+            ConditioningStep::FasterPace => "faster_pace",
+            ConditioningStep::Hold => "hold",
+        }
+    }
+}
+
+/// Progression for distance and duration exercises. Routines repeat weekly,
+/// so each session may add at most `max_weekly_increase` (a fraction) to
+/// what was performed. A single continuous set gets longer at the pace it
+/// was done at; intervals keep their distance and get `pace_step` faster.
+#[derive(Debug, Clone, Default)]
+pub struct Conditioning {
+    pub max_weekly_increase: f32,
+    pub pace_step: f32,
+}
+
+impl Conditioning {
+    /// Replaces the sets of every exercise in `exercises` listed in `kinds`
+    /// with the next step from what was performed in `workout`. Returns the
+    /// template id and step for each exercise changed.
+    pub fn apply(
+        &self,
+        workout: &WorkoutResponse,
+        kinds: &HashMap<String, ConditioningKind>,
+        exercises: &mut [Exercise],
+    ) -> Vec<(String, ConditioningStep)> {
+        let mut applied = Vec::new();
+
+        for exercise in exercises.iter_mut() {
+            let template_id = &exercise.exercise_template_id;
+            let Some(&kind) = kinds.get(template_id) else {
+                continue;
+            };
+            let Some(performed) = workout
+                .exercises
+                .iter()
+                .find(|performed| &performed.exercise_template_id == template_id)
+            else {
+                continue;
+            };
+
+            let (sets, step) = self.progress(performed, kind);
+            exercise.sets = sets;
+            applied.push((template_id.clone(), step));
+        }
+
+        applied
+    }
+
+    pub fn progress(
+        &self,
+        performed: &Exercise,
+        kind: ConditioningKind,
+    ) -> (Vec<ExerciseSet>, ConditioningStep) {
+        let working: Vec<_> = performed.working_sets().collect();
+        let intervals = kind == ConditioningKind::Distance
+            && working.len() > 1
+            && working
+                .iter()
+                .all(|set| set.distance_meters.is_some() && set.duration_seconds.is_some());
+
+        let mut step = ConditioningStep::Hold;
+        let sets = performed
+            .sets
+            .iter()
+            .enumerate()
+            .map(|(index, set)| {
+                let next = if set.is_warmup() {
+                    None
+                } else if intervals {
+                    self.faster(set)
+                } else {
+                    self.extend(set, kind)
+                };
+                let next = match next {
+                    Some((next, next_step)) => {
+                        step = next_step;
+                        next
+                    }
+                    None => set.clone(),
+                };
+                ExerciseSet {
+                    index: index as u32,
+                    ..next
+                }
+            })
+            .collect();
+
+        (sets, step)
+    }
+
+    /// A longer set at the same pace, or `None` when rounding leaves no room
+    /// under the ramp.
+    fn extend(
+        &self,
+        set: &ExerciseSet,
+        kind: ConditioningKind,
+    ) -> Option<(ExerciseSet, ConditioningStep)> {
+        let scale = 1.0 + self.max_weekly_increase;
+
+        if let (ConditioningKind::Distance, Some(distance)) = (kind, set.distance_meters) {
+            let next = round_distance_down(distance as f32 * scale);
+            if next <= distance {
+                return None;
+            }
+            let duration_seconds = set
+                .duration_seconds
+                .map(|duration| (duration as f32 * next as f32 / distance as f32).round() as u32);
+            return Some((
+                ExerciseSet {
+                    distance_meters: Some(next),
+                    duration_seconds,
+                    ..set.clone()
+                },
+                ConditioningStep::Extend,
+            ));
+        }
+
+        let duration = set.duration_seconds?;
+        let next = round_duration_down(duration as f32 * scale);
+        (next > duration).then(|| {
+            (
+                ExerciseSet {
+                    duration_seconds: Some(next),
+                    ..set.clone()
+                },
+                ConditioningStep::Extend,
+            )
+        })
+    }
+
+    /// The same interval with a faster target time.
+    fn faster(&self, set: &ExerciseSet) -> Option<(ExerciseSet, ConditioningStep)> {
+        let duration = set.duration_seconds?;
+        let next = (duration as f32 * (1.0 - self.pace_step)).round() as u32;
+        (next > 0 && next < duration).then(|| {
+            (
+                ExerciseSet {
+                    duration_seconds: Some(next),
+                    ..set.clone()
+                },
+                ConditioningStep::FasterPace,
+            )
+        })
+    }
+}
+
+/// Distance, time and pace for sets logged without reps, e.g.
+/// `5km in 25:00 (5:00/km)`. `None` for weight and rep sets.
+pub fn describe_set(set: &ExerciseSet) -> Option<String> {
+    if set.reps.is_some() {
+        return None;
+    }
+
+    let cardio = match (set.distance_meters, set.duration_seconds) {
+        (Some(distance), Some(duration)) if distance > 0 => format!(
+            "{} in {} ({})",
+            format_distance(distance),
+            format_duration(duration),
+            format_pace(distance, duration)
+        ),
+        (Some(distance), _) => format_distance(distance),
+        (None, Some(duration)) => format_duration(duration),
+        (None, None) => return None,
+    };
+
+    Some(match set.weight_kg {
+        Some(weight) if weight.fract().abs() > f32::EPSILON => {
+            format!("{:.1}kg for {}", weight, cardio)
+        }
+        Some(weight) => format!("{:.0}kg for {}", weight, cardio),
+        None => cardio,
+    })
+}
+
+pub fn format_distance(meters: u32) -> String {
+    if meters >= 1000 {
+        let km = format!("{:.2}", meters as f32 / 1000.0);
+        format!("{}km", km.trim_end_matches('0').trim_end_matches('.'))
+    } else {
+        format!("{}m", meters)
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour up.
+pub fn format_duration(seconds: u32) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+pub fn format_pace(meters: u32, seconds: u32) -> String {
+    let per_km = (seconds as f32 * 1000.0 / meters as f32).round() as u32;
+    format!("{}/km", format_duration(per_km))
+}
+
+/// Down to 100m from a kilometre up and 10m below, so the ramp is never
+/// exceeded.
+fn round_distance_down(meters: f32) -> u32 {
+    let step = if meters >= 1000.0 { 100.0 } else { 10.0 };
+    ((meters / step).floor() * step) as u32
+}
+
+fn round_duration_down(seconds: f32) -> u32 {
+    ((seconds / 5.0).floor() * 5.0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{self, cardio_set, set};
+
+    fn exercise(sets: Vec<ExerciseSet>) -> Exercise {
+        mock_hevy::exercise("run", "Running", sets)
+    }
+
+    fn conditioning() -> Conditioning {
+        Conditioning {
+            max_weekly_increase: 0.10,
+            pace_step: 0.02,
+        }
+    }
+
+    #[test]
+    fn test_continuous_sessions_ramp_at_the_same_pace() {
+        let run = exercise(vec![
+            cardio_set("warmup", Some(1000), Some(420)),
+            cardio_set("normal", Some(5000), Some(1500)),
+        ]);
+        assert_eq!(
+            ConditioningKind::infer(&run),
+            Some(ConditioningKind::Distance)
+        );
+
+        let (sets, step) = conditioning().progress(&run, ConditioningKind::Distance);
+
+        assert_eq!(step, ConditioningStep::Extend);
+        assert_eq!(sets[0].distance_meters, Some(1000));
+        assert_eq!(sets[1].distance_meters, Some(5500));
+        assert_eq!(sets[1].duration_seconds, Some(1650));
+        assert_eq!(describe_set(&sets[1]).unwrap(), "5.5km in 27:30 (5:00/km)");
+
+        let plank = exercise(vec![cardio_set("normal", None, Some(60))]);
+        let (sets, step) = conditioning().progress(&plank, ConditioningKind::Duration);
+        assert_eq!(step, ConditioningStep::Extend);
+        assert_eq!(sets[0].duration_seconds, Some(65));
+
+        let (sets, step) = Conditioning::default().progress(&plank, ConditioningKind::Duration);
+        assert_eq!(step, ConditioningStep::Hold);
+        assert_eq!(sets[0].duration_seconds, Some(60));
+    }
+
+    #[test]
+    fn test_intervals_keep_their_distance_and_get_faster() {
+        let intervals = exercise(vec![cardio_set("normal", Some(400), Some(100)); 4]);
+
+        let (sets, step) = conditioning().progress(&intervals, ConditioningKind::Distance);
+
+        assert_eq!(step, ConditioningStep::FasterPace);
+        assert_eq!(sets.len(), 4);
+        assert!(
+            sets.iter()
+                .all(|set| set.distance_meters == Some(400) && set.duration_seconds == Some(98))
+        );
+        assert_eq!(describe_set(&sets[3]).unwrap(), "400m in 1:38 (4:05/km)");
+        assert_eq!(sets[3].index, 3);
+    }
+
+    #[test]
+    fn test_strength_sets_are_not_conditioning() {
+        let mut bench = exercise(vec![set("normal", Some(80.0), 5)]);
+        assert_eq!(ConditioningKind::infer(&bench), None);
+        assert_eq!(describe_set(&bench.sets[0]), None);
+
+        bench.sets[0].reps = None;
+        bench.sets[0].duration_seconds = Some(3725);
+        assert_eq!(describe_set(&bench.sets[0]).unwrap(), "80kg for 1:02:05");
+        assert_eq!(ConditioningKind::from_exercise_type("weight_reps"), None);
+    }
+}
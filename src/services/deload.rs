@@ -1,5 +1,6 @@
 use anyhow::Result;
 use regex::Regex;
+use std::collections::HashMap;
 use tracing::warn;
 
 use crate::clients::hevy::HevyClient;
@@ -21,6 +22,13 @@ pub struct DeloadContext {
     pub reference_data: String,
 }
 
+/// Whether going from `current_week` to `next_week` enters the week 8
+/// deload or leaves it for a new block. These changes in load are planned,
+/// so week-to-week rules like load caps and stall responses stay out of them.
+pub fn is_block_transition(current_week: u32, next_week: u32) -> bool {
+    next_week >= 8 || current_week >= 8 || next_week < current_week
+}
+
 impl Default for DeloadCalculator {
     fn default() -> Self {
         Self {
@@ -94,7 +102,7 @@ impl<'a> DeloadContextBuilder<'a> {
                 let reference_data = format!(
                     "\n\n{} (for deload calculation):\n{}",
                     week_label,
-                    super::ai_prompt::format_workout_for_prompt(&week1_reference)
+                    super::ai_prompt::format_workout_for_prompt(&week1_reference, &HashMap::new())
                 );
 
                 DeloadContext {
@@ -173,6 +181,15 @@ impl<'a> DeloadContextBuilder<'a> {
             return Ok(Some(reference));
         }
 
+        Ok(self.find_week7_reference(current_workout).await)
+    }
+
+    /// The last week 7 workout of the same routine: the heaviest working
+    /// weights before the deload.
+    pub async fn find_week7_reference(
+        &self,
+        current_workout: &WorkoutResponse,
+    ) -> Option<WorkoutResponse> {
         let max_pages = 10;
         let page_size = 10;
 
@@ -183,7 +200,7 @@ impl<'a> DeloadContextBuilder<'a> {
                         if super::ai_parser::extract_week_from_title(&workout.title) == Some(7)
                             && workout.routine_id == current_workout.routine_id
                         {
-                            return Ok(Some(workout.clone()));
+                            return Some(workout.clone());
                         }
                     }
 
@@ -198,7 +215,7 @@ impl<'a> DeloadContextBuilder<'a> {
             }
         }
 
-        Ok(None)
+        None
     }
 
     fn is_week1_same_day_workout(&self, workout: &WorkoutResponse, target_day: u32) -> bool {
@@ -250,4 +267,13 @@ mod tests {
         );
         assert_eq!(calculator.extract_day_from_title("Push Day"), None);
     }
+
+    #[test]
+    fn test_is_block_transition() {
+        assert!(!is_block_transition(3, 4));
+        assert!(!is_block_transition(6, 7));
+        assert!(is_block_transition(7, 8));
+        assert!(is_block_transition(8, 1));
+        assert!(is_block_transition(9, 1));
+    }
 }
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet, RepRange},
+    responses::{RoutineResponse, WorkoutResponse},
+};
+
+/// Inclusive rep range for double progression, e.g. 8-12.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RepTarget {
+    pub min: u32,
+    pub max: u32,
+}
+
+#[derive(Debug, Error)]
+#[error("invalid rep range `{0}`, expected e.g. 8-12")]
+pub struct InvalidRepTarget(String);
+
+impl Default for RepTarget {
+    fn default() -> Self {
+        Self { min: 8, max: 12 }
+    }
+}
+
+impl RepTarget {
+    /// A range from Hevy, when both ends are set and in order.
+    pub fn from_rep_range(range: &RepRange) -> Option<Self> {
+        match (range.start, range.end) {
+            (Some(min), Some(max)) if min > 0 && min <= max => Some(Self { min, max }),
+            _ => None,
+        }
+    }
+
+    pub fn to_rep_range(self) -> RepRange {
+        RepRange {
+            start: Some(self.min),
+            end: Some(self.max),
+        }
+    }
+}
+
+impl fmt::Display for RepTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+impl FromStr for RepTarget {
+    type Err = InvalidRepTarget;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split_once('-')
+            .and_then(|(min, max)| Some((min.trim().parse().ok()?, max.trim().parse().ok()?)))
+            .and_then(|(min, max)| {
+                RepTarget::from_rep_range(&RepRange {
+                    start: Some(min),
+                    end: Some(max),
+                })
+            })
+            .ok_or_else(|| InvalidRepTarget(value.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleProgressionStep {
+    /// Not every working set reached the top of the range yet.
+    AddReps,
+    /// Every working set reached the top, so the load goes up and reps drop
+    /// back to the bottom of the range.
+    AddLoad,
+}
+
+impl DoubleProgressionStep {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DoubleProgressionStep::AddReps => "add_reps",
+            DoubleProgressionStep::AddLoad => "add_load",
+        }
+    }
+}
+
+/// Double progression for accessories. An exercise uses it when its routine
+/// sets already carry a rep range in Hevy, or when its template id is listed
+/// in `exercise_template_ids` (which then get `default_range`).
+#[derive(Debug, Clone, Default)]
+pub struct DoubleProgression {
+    pub increment_kg: f32,
+    pub default_range: RepTarget,
+    pub exercise_template_ids: HashSet<String>,
+}
+
+impl DoubleProgression {
+    pub fn rep_range_for(&self, planned: &Exercise) -> Option<RepTarget> {
+        planned
+            .working_sets()
+            .find_map(|set| set.rep_range.as_ref().and_then(RepTarget::from_rep_range))
+            .or_else(|| {
+                self.exercise_template_ids
+                    .contains(&planned.exercise_template_id)
+                    .then_some(self.default_range)
+            })
+    }
+
+    /// Replaces the sets of every double-progression exercise in `exercises`
+    /// with the next step from what was performed in `workout`. Returns the
+    /// template id and step for each exercise changed.
+    pub fn apply(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+        exercises: &mut [Exercise],
+    ) -> Vec<(String, DoubleProgressionStep)> {
+        let mut applied = Vec::new();
+
+        for exercise in exercises.iter_mut() {
+            let template_id = &exercise.exercise_template_id;
+            let Some(range) = routine
+                .exercises
+                .iter()
+                .find(|planned| &planned.exercise_template_id == template_id)
+                .and_then(|planned| self.rep_range_for(planned))
+            else {
+                continue;
+            };
+            let Some(performed) = workout
+                .exercises
+                .iter()
+                .find(|performed| &performed.exercise_template_id == template_id)
+            else {
+                continue;
+            };
+
+            let (sets, step) = self.progress(performed, range);
+            exercise.sets = sets;
+            applied.push((template_id.clone(), step));
+        }
+
+        applied
+    }
+
+    pub fn progress(
+        &self,
+        performed: &Exercise,
+        range: RepTarget,
+    ) -> (Vec<ExerciseSet>, DoubleProgressionStep) {
+        let mut working_sets = performed.working_sets().peekable();
+        let topped_out = working_sets.peek().is_some()
+            && working_sets.all(|set| set.reps.is_some_and(|reps| reps >= range.max));
+        let step = if topped_out {
+            DoubleProgressionStep::AddLoad
+        } else {
+            DoubleProgressionStep::AddReps
+        };
+
+        let sets = performed
+            .sets
+            .iter()
+            .map(|set| {
+                if set.is_warmup() {
+                    return set.clone();
+                }
+
+                let (weight_kg, reps) = match (step, set.weight_kg) {
+                    // Bodyweight sets have no load to add, so they stay at the top
+                    (DoubleProgressionStep::AddLoad, None) => (None, range.max),
+                    (DoubleProgressionStep::AddLoad, Some(weight)) => {
+                        (Some(weight + self.increment_kg), range.min)
+                    }
+                    (DoubleProgressionStep::AddReps, weight) => (
+                        weight,
+                        (set.reps.unwrap_or(0) + 1).clamp(range.min, range.max),
+                    ),
+                };
+
+                ExerciseSet {
+                    weight_kg,
+                    reps: Some(reps),
+                    rpe: None,
+                    rep_range: Some(range.to_rep_range()),
+                    ..set.clone()
+                }
+            })
+            .collect();
+
+        (sets, step)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, set};
+
+    fn lateral(sets: Vec<ExerciseSet>) -> Exercise {
+        exercise("lateral", "Lateral Raise (Dumbbell)", sets)
+    }
+
+    fn progression() -> DoubleProgression {
+        DoubleProgression {
+            increment_kg: 2.0,
+            default_range: RepTarget::default(),
+            exercise_template_ids: HashSet::from(["lateral".to_string()]),
+        }
+    }
+
+    #[test]
+    fn test_adds_reps_until_top_of_range_then_load() {
+        let (sets, step) = progression().progress(
+            &lateral(vec![
+                set("warmup", Some(4.0), 15),
+                set("normal", Some(10.0), 12),
+                set("normal", Some(10.0), 9),
+            ]),
+            RepTarget::default(),
+        );
+        assert_eq!(step, DoubleProgressionStep::AddReps);
+        assert_eq!(sets[0].rep_range, None);
+        assert_eq!(sets[1].reps, Some(12));
+        assert_eq!(sets[2].reps, Some(10));
+        assert_eq!(sets[2].weight_kg, Some(10.0));
+        assert_eq!(sets[2].rep_range.as_ref().unwrap().end, Some(12));
+
+        let (sets, step) = progression().progress(
+            &lateral(vec![
+                set("normal", Some(10.0), 12),
+                set("normal", Some(10.0), 13),
+            ]),
+            RepTarget::default(),
+        );
+        assert_eq!(step, DoubleProgressionStep::AddLoad);
+        assert!(
+            sets.iter()
+                .all(|set| set.weight_kg == Some(12.0) && set.reps == Some(8))
+        );
+    }
+
+    #[test]
+    fn test_rep_range_from_routine_wins_over_default() {
+        let mut planned = lateral(vec![set("normal", Some(10.0), 10)]);
+        assert_eq!(
+            progression().rep_range_for(&planned),
+            Some(RepTarget::default())
+        );
+
+        planned.sets[0].rep_range = Some(RepRange {
+            start: Some(10),
+            end: Some(15),
+        });
+        planned.exercise_template_id = "other".to_string();
+        assert_eq!(
+            progression().rep_range_for(&planned),
+            Some(RepTarget { min: 10, max: 15 })
+        );
+
+        assert_eq!(
+            "6 - 10".parse::<RepTarget>().unwrap(),
+            RepTarget { min: 6, max: 10 }
+        );
+        assert!("12-8".parse::<RepTarget>().is_err());
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Which progression engine produces the next session's prescription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Engine {
+    /// Prompted LLM (Gemini).
+    #[default]
+    Gemini,
+    /// Deterministic rules in `rule_based`, no network calls.
+    RuleBased,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown engine `{0}`, expected `gemini` or `rule_based`")]
+pub struct UnknownEngine(String);
+
+impl Engine {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Engine::Gemini => "gemini",
+            Engine::RuleBased => "rule_based",
+        }
+    }
+}
+
+impl fmt::Display for Engine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Engine {
+    type Err = UnknownEngine;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "gemini" => Ok(Engine::Gemini),
+            "rule_based" | "rules" => Ok(Engine::RuleBased),
+            _ => Err(UnknownEngine(value.to_string())),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+
+use crate::clients::hevy::HevyClient;
+use crate::clients::models::common::Exercise;
+use crate::services::bodyweight::LoadKind;
+use crate::services::conditioning::ConditioningKind;
+
+/// Which performed exercises progress as conditioning or relative to
+/// bodyweight, by template id. Decided by the Hevy template type, or by how
+/// the sets were logged when the template can't be fetched.
+#[derive(Debug, Clone, Default)]
+pub struct ExerciseKinds {
+    pub conditioning: HashMap<String, ConditioningKind>,
+    pub load: HashMap<String, LoadKind>,
+}
+
+impl ExerciseKinds {
+    pub async fn lookup(hevy_client: &HevyClient, exercises: &[Exercise]) -> Self {
+        let template_ids: Vec<_> = exercises
+            .iter()
+            .map(|exercise| exercise.exercise_template_id.as_str())
+            .collect();
+        let types = hevy_client.get_exercise_types(&template_ids).await;
+
+        let mut kinds = Self::default();
+        for exercise in exercises {
+            let template_id = &exercise.exercise_template_id;
+            let (conditioning, load) = match types.get(template_id) {
+                Some(exercise_type) => (
+                    ConditioningKind::from_exercise_type(exercise_type),
+                    LoadKind::from_exercise_type(exercise_type),
+                ),
+                None => (ConditioningKind::infer(exercise), LoadKind::infer(exercise)),
+            };
+            if let Some(kind) = conditioning {
+                kinds.conditioning.insert(template_id.clone(), kind);
+            }
+            if let Some(kind) = load {
+                kinds.load.insert(template_id.clone(), kind);
+            }
+        }
+        kinds
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Text rewritten into strict JSON, with how often each kind of fix was
+/// needed.
+#[derive(Debug, Default)]
+pub struct Repaired {
+    pub json: String,
+    pub repairs: BTreeMap<&'static str, usize>,
+}
+
+impl Repaired {
+    fn note(&mut self, repair: &'static str) {
+        *self.repairs.entry(repair).or_default() += 1;
+    }
+}
+
+/// Rewrites the JSON5-ish text models tend to produce into strict JSON:
+/// comments, trailing commas, single-quoted strings, unquoted keys, raw
+/// newlines inside strings and Python/JavaScript literals. Anything else is
+/// copied through unchanged for `serde_json` to reject.
+pub fn repair(text: &str) -> Repaired {
+    let mut repaired = Repaired {
+        json: String::with_capacity(text.len()),
+        ..Repaired::default()
+    };
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => copy_string(&mut chars, '"', &mut repaired),
+            '\'' => {
+                repaired.note("single-quoted string");
+                copy_string(&mut chars, '\'', &mut repaired);
+            }
+            '/' if matches!(chars.peek(), Some('/') | Some('*')) => {
+                repaired.note("comment");
+                skip_comment(&mut chars);
+            }
+            ',' if next_significant(&chars).is_some_and(|next| next == '}' || next == ']') => {
+                repaired.note("trailing comma");
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                // Exponents and decimals stay part of the number
+                repaired.json.push(c);
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_alphanumeric() || matches!(next, '.' | '+' | '-')) {
+                        break;
+                    }
+                    repaired.json.push(next);
+                    chars.next();
+                }
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_' || next == '$') {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                push_word(&word, next_significant(&chars) == Some(':'), &mut repaired);
+            }
+            _ => repaired.json.push(c),
+        }
+    }
+
+    repaired
+}
+
+/// Copies a string whose opening `quote` has been consumed, always emitting
+/// it double-quoted.
+fn copy_string(chars: &mut Peekable<Chars>, quote: char, repaired: &mut Repaired) {
+    repaired.json.push('"');
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\'') => repaired.json.push('\''),
+                Some(escaped) => {
+                    repaired.json.push('\\');
+                    repaired.json.push(escaped);
+                }
+                None => {}
+            },
+            c if c == quote => break,
+            '"' => repaired.json.push_str("\\\""),
+            '\n' => {
+                repaired.note("raw newline in string");
+                repaired.json.push_str("\\n");
+            }
+            '\r' => {}
+            '\t' => repaired.json.push_str("\\t"),
+            c => repaired.json.push(c),
+        }
+    }
+
+    repaired.json.push('"');
+}
+
+fn skip_comment(chars: &mut Peekable<Chars>) {
+    if chars.next() == Some('/') {
+        for c in chars.by_ref() {
+            if c == '\n' {
+                break;
+            }
+        }
+        return;
+    }
+
+    let mut previous = None;
+    for c in chars.by_ref() {
+        if previous == Some('*') && c == '/' {
+            break;
+        }
+        previous = Some(c);
+    }
+}
+
+fn push_word(word: &str, is_key: bool, repaired: &mut Repaired) {
+    if is_key {
+        repaired.note("unquoted key");
+        repaired.json.push_str(&format!("\"{}\"", word));
+        return;
+    }
+
+    let literal = match word {
+        "true" | "false" | "null" => {
+            repaired.json.push_str(word);
+            return;
+        }
+        "True" | "TRUE" => "true",
+        "False" | "FALSE" => "false",
+        "None" | "NULL" | "Null" | "nil" | "undefined" | "NaN" | "Infinity" => "null",
+        _ => {
+            repaired.note("bare word");
+            repaired.json.push_str(&format!("\"{}\"", word));
+            return;
+        }
+    };
+    repaired.note("non-JSON literal");
+    repaired.json.push_str(literal);
+}
+
+fn next_significant(chars: &Peekable<Chars>) -> Option<char> {
+    chars.clone().find(|c| !c.is_whitespace())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repairs_json5_style_text() {
+        let repaired = repair(
+            "{\n  // next week\n  reps: 7, 'notes': 'say \"hi\"\nthen go', rpe: None, type: normal,\n  'tags': ['a', 'b',],\n}",
+        );
+
+        let value: serde_json::Value = serde_json::from_str(&repaired.json).unwrap();
+        assert_eq!(value["reps"], 7);
+        assert_eq!(value["notes"], "say \"hi\"\nthen go");
+        assert!(value["rpe"].is_null());
+        assert_eq!(value["type"], "normal");
+        assert_eq!(value["tags"], serde_json::json!(["a", "b"]));
+
+        assert_eq!(repaired.repairs["trailing comma"], 2);
+        assert_eq!(repaired.repairs["unquoted key"], 3);
+        assert_eq!(repaired.repairs["single-quoted string"], 5);
+        assert_eq!(repaired.repairs["comment"], 1);
+        assert_eq!(repaired.repairs["non-JSON literal"], 1);
+        assert_eq!(repaired.repairs["bare word"], 1);
+    }
+
+    #[test]
+    fn test_leaves_valid_json_untouched() {
+        let json = r#"{"a": [1, 2.5, -3e2], "b": "x, } // not a comment", "c": null}"#;
+        let repaired = repair(json);
+
+        assert_eq!(repaired.json, json);
+        assert!(repaired.repairs.is_empty());
+    }
+}
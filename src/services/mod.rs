@@ -1,5 +1,19 @@
+pub mod adherence;
 pub mod ai_parser;
 pub mod ai_prompt;
+pub mod bodyweight;
+pub mod conditioning;
 pub mod deload;
+pub mod double_progression;
+pub mod engine;
+pub mod exercise_kinds;
+pub mod lenient_json;
+pub mod notes;
 pub mod output_formatter;
+pub mod programs;
 pub mod progressive_overload;
+pub mod rest;
+pub mod rule_based;
+pub mod stalls;
+pub mod substitutions;
+pub mod update_mode;
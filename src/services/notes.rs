@@ -0,0 +1,188 @@
+/// Opens the part of exercise and routine notes this service writes.
+pub const SECTION_START: &str = "--- progression ---";
+/// Closes it. Anything outside the two markers belongs to the lifter.
+pub const SECTION_END: &str = "--- end progression ---";
+
+/// Puts `section` between the markers in `existing`, replacing whatever was
+/// there before and leaving the lifter's text around it untouched. Notes
+/// without a section get one after their text, except a summary written
+/// before the markers existed, which is replaced outright.
+pub fn merge_section(existing: Option<&str>, section: &str) -> String {
+    let block = format!("{}\n{}\n{}", SECTION_START, section.trim(), SECTION_END);
+    let existing = existing.unwrap_or_default();
+    if is_legacy_summary(existing) {
+        return block;
+    }
+
+    let Some(start) = existing.find(SECTION_START) else {
+        return match existing.trim_end() {
+            "" => block,
+            user_text => format!("{}\n\n{}", user_text, block),
+        };
+    };
+    // A section whose end marker was deleted runs to the end of the notes
+    let end = existing[start..]
+        .find(SECTION_END)
+        .map_or(existing.len(), |end| start + end + SECTION_END.len());
+
+    format!("{}{}{}", &existing[..start], block, &existing[end..])
+}
+
+/// Exercise notes used to be the bare summary, e.g. "2 sets\n80x7\n80x7".
+/// Unmarked notes are only taken to be ours when they open with a set count
+/// and every other line is one the summary wrote, so a cue added under it
+/// keeps the whole note as the lifter's.
+fn is_legacy_summary(notes: &str) -> bool {
+    if notes.contains(SECTION_START) {
+        return false;
+    }
+
+    let mut lines = notes.trim().lines().map(str::trim);
+    let opens_with_count = lines
+        .next()
+        .and_then(|line| line.strip_suffix(" sets"))
+        .is_some_and(is_count);
+    opens_with_count && lines.all(is_summary_line)
+}
+
+/// A stall or RPE line, or a set: `80x7`, `8 reps`, `BW+10kg x 6`,
+/// `5km in 25:00 (5:00/km)`, any of them with a ` (8-12)` rep range.
+fn is_summary_line(line: &str) -> bool {
+    if line.starts_with("Missed reps ") {
+        return true;
+    }
+    if let Some(rpe) = line.strip_prefix("RPE ") {
+        return !rpe.is_empty() && rpe.bytes().all(|b| b.is_ascii_digit() || b == b'-');
+    }
+
+    let set = match line
+        .strip_suffix(')')
+        .and_then(|rest| rest.rsplit_once(" ("))
+    {
+        Some((set, range))
+            if range
+                .split_once('-')
+                .is_some_and(|(a, b)| is_count(a) && is_count(b)) =>
+        {
+            set
+        }
+        _ => line,
+    };
+    is_weight_set(set) || is_cardio_set(set)
+}
+
+fn is_weight_set(set: &str) -> bool {
+    let is_reps = |reps: &str| reps == "?" || is_count(reps);
+    if let Some(reps) = set.strip_suffix(" reps") {
+        return is_reps(reps);
+    }
+    if let Some((load, reps)) = set.split_once(" x ") {
+        let known_load = load
+            .strip_prefix("BW+")
+            .or_else(|| load.strip_prefix("assist -"))
+            .map_or(load == "BW", |kg| {
+                kg.strip_suffix("kg").is_some_and(is_number)
+            });
+        return known_load && is_reps(reps);
+    }
+    set.split_once('x')
+        .is_some_and(|(weight, reps)| is_number(weight) && is_reps(reps))
+}
+
+fn is_cardio_set(set: &str) -> bool {
+    let set = match set.split_once("kg for ") {
+        Some((weight, cardio)) if is_number(weight) => cardio,
+        _ => set,
+    };
+    let is_distance = |distance: &str| {
+        distance
+            .strip_suffix("km")
+            .or_else(|| distance.strip_suffix('m'))
+            .is_some_and(is_number)
+    };
+    match set.split_once(" in ") {
+        Some((distance, time)) => {
+            let Some((duration, pace)) = time.split_once(" (") else {
+                return false;
+            };
+            is_distance(distance)
+                && is_duration(duration)
+                && pace.strip_suffix("/km)").is_some_and(is_duration)
+        }
+        None => is_distance(set) || is_duration(set),
+    }
+}
+
+fn is_duration(duration: &str) -> bool {
+    duration.split_once(':').is_some_and(|(lead, rest)| {
+        is_count(lead)
+            && rest
+                .split(':')
+                .all(|part| part.len() == 2 && is_count(part))
+    })
+}
+
+fn is_number(value: &str) -> bool {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    is_count(whole) && is_count(fraction)
+}
+
+fn is_count(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_section_keeps_user_text() {
+        let first = merge_section(Some("elbows tucked\n"), "2 sets\n80x7\n80x7");
+        assert_eq!(
+            first,
+            "elbows tucked\n\n--- progression ---\n2 sets\n80x7\n80x7\n--- end progression ---"
+        );
+
+        let edited = format!("{}\nuse blue band", first);
+        assert_eq!(
+            merge_section(Some(&edited), "2 sets\n82.5x7\n82.5x7"),
+            "elbows tucked\n\n--- progression ---\n2 sets\n82.5x7\n82.5x7\n--- end progression ---\nuse blue band"
+        );
+
+        assert_eq!(
+            merge_section(None, "Week 3"),
+            "--- progression ---\nWeek 3\n--- end progression ---"
+        );
+        assert_eq!(
+            merge_section(Some("cue\n--- progression ---\nold"), "new"),
+            "cue\n--- progression ---\nnew\n--- end progression ---"
+        );
+    }
+
+    #[test]
+    fn test_merge_section_replaces_legacy_summary() {
+        let legacy = "3 sets\nMissed reps last session, same weight again\n100x5\n100x5\n100x5";
+        assert_eq!(
+            merge_section(Some(legacy), "3 sets\n100x5\n100x5\n100x5"),
+            "--- progression ---\n3 sets\n100x5\n100x5\n100x5\n--- end progression ---"
+        );
+
+        let bodyweight = "2 sets\nRPE 8\nBW+10kg x 6 (6-12)\nassist -20kg x 8";
+        assert!(is_legacy_summary(bodyweight));
+        assert!(is_legacy_summary("1 sets\n5km in 27:30 (5:30/km)"));
+    }
+
+    #[test]
+    fn test_merge_section_keeps_unmarked_notes_that_are_not_a_summary() {
+        // A cue under a set count is the lifter's, so nothing of it goes
+        assert_eq!(
+            merge_section(Some("3 sets\nkeep elbows tucked"), "3 sets\n100x5"),
+            "3 sets\nkeep elbows tucked\n\n--- progression ---\n3 sets\n100x5\n--- end progression ---"
+        );
+        assert_eq!(
+            merge_section(Some("3 sets of band pull-aparts first"), "Week 3"),
+            "3 sets of band pull-aparts first\n\n--- progression ---\nWeek 3\n--- end progression ---"
+        );
+        assert!(!is_legacy_summary("3 sets\n100x5\npause on the chest"));
+    }
+}
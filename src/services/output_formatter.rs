@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
+use crate::clients::models::common::RepRange;
+use crate::services::conditioning;
 use crate::services::progressive_overload::ProgressiveOverloadResponse;
+use crate::services::stalls::{StallAdjustment, StallResponse};
 
 pub fn build_exercise_suggestions(
     response: &ProgressiveOverloadResponse,
@@ -19,13 +22,30 @@ pub fn build_exercise_suggestions(
         if !working_sets.is_empty() {
             lines.push(format!("{} sets", working_sets.len()));
 
+            if let Some(stall) = response
+                .stall_adjustments
+                .iter()
+                .find(|stall| stall.exercise_template_id == exercise.exercise_template_id)
+            {
+                lines.push(stall_line(stall));
+            }
+
             if let Some(notes) = &exercise.notes
                 && let Some(rpe) = extract_rpe_from_notes(notes)
             {
                 lines.push(format!("RPE {}", rpe))
             }
 
+            let load_kind = response
+                .bodyweight_exercises
+                .get(&exercise.exercise_template_id);
+
             for set in working_sets {
+                if let Some(entry) = conditioning::describe_set(set) {
+                    lines.push(entry);
+                    continue;
+                }
+
                 let reps = set
                     .reps
                     .map(|value| value.to_string())
@@ -39,10 +59,18 @@ pub fn build_exercise_suggestions(
                     }
                 });
 
-                let entry = match weight {
-                    Some(weight_str) => format!("{}x{}", weight_str, reps),
-                    None => format!("{} reps", reps),
+                let mut entry = match (load_kind, weight) {
+                    (Some(kind), _) => kind.describe_set(set),
+                    (None, Some(weight_str)) => format!("{}x{}", weight_str, reps),
+                    (None, None) => format!("{} reps", reps),
                 };
+                if let Some(RepRange {
+                    start: Some(start),
+                    end: Some(end),
+                }) = &set.rep_range
+                {
+                    entry.push_str(&format!(" ({}-{})", start, end));
+                }
 
                 lines.push(entry);
             }
@@ -57,6 +85,47 @@ pub fn build_exercise_suggestions(
     suggestions
 }
 
+/// The routine-level note: the week coming up, and any rest or load changes
+/// made to the prescription.
+pub fn build_routine_summary(response: &ProgressiveOverloadResponse) -> String {
+    let mut lines = vec![format!("Week {}", response.week_number)];
+
+    for adjustment in &response.rest_adjustments {
+        let title = response
+            .updated_exercises
+            .iter()
+            .find(|exercise| exercise.exercise_template_id == adjustment.exercise_template_id)
+            .map_or(adjustment.exercise_template_id.as_str(), |exercise| {
+                exercise.title.as_str()
+            });
+        lines.push(format!(
+            "Rest {} for {}, was {}",
+            conditioning::format_duration(adjustment.to_seconds),
+            title,
+            conditioning::format_duration(adjustment.from_seconds)
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn stall_line(stall: &StallAdjustment) -> String {
+    let action = match stall.action {
+        StallResponse::Repeat => "same weight again",
+        StallResponse::Reduce => "weight reduced",
+        StallResponse::ChangeReps => "new rep scheme",
+        StallResponse::Flag => "flagged for coach review",
+    };
+    if stall.consecutive_misses == 1 {
+        format!("Missed reps last session, {}", action)
+    } else {
+        format!(
+            "Missed reps {} sessions in a row, {}",
+            stall.consecutive_misses, action
+        )
+    }
+}
+
 fn extract_rpe_from_notes(notes: &str) -> Option<String> {
     if let Some(start) = notes.to_lowercase().find("rpe") {
         let after_rpe = &notes[start + 3..];
@@ -0,0 +1,174 @@
+//! Wendler's 5/3/1. Main lifts run four-week waves off a training max (90%
+//! of the estimated one rep max): 5s, 3s, 5/3/1 and a deload, with the last
+//! set of the first three weeks done for as many reps as possible. The
+//! training max goes up after each deload and drops 10% whenever that last
+//! set falls short of its minimum reps. Assistance work uses double
+//! progression in 10-15.
+
+use super::{
+    ProgramSession, SetTarget, accessory, best_e1rm, build_exercise, increment_for, is_main_lift,
+    round_to_plates,
+};
+use crate::clients::models::common::Exercise;
+use crate::services::double_progression::RepTarget;
+
+const CYCLE_WEEKS: u32 = 4;
+const TRAINING_MAX_FACTOR: f32 = 0.9;
+const FAILED_TRAINING_MAX_FACTOR: f32 = 0.9;
+const ACCESSORY_RANGE: RepTarget = RepTarget { min: 10, max: 15 };
+
+/// Percentage of the training max, reps and whether the set is AMRAP.
+fn scheme(week: u32) -> [(f32, u32, bool); 3] {
+    match week {
+        1 => [(0.65, 5, false), (0.75, 5, false), (0.85, 5, true)],
+        2 => [(0.70, 3, false), (0.80, 3, false), (0.90, 3, true)],
+        3 => [(0.75, 5, false), (0.85, 3, false), (0.95, 1, true)],
+        _ => [(0.40, 5, false), (0.50, 5, false), (0.60, 5, false)],
+    }
+}
+
+pub(crate) fn prescribe(session: &mut ProgramSession) -> (Vec<Exercise>, u32) {
+    let performed_week = session.week;
+    let next_week = performed_week % CYCLE_WEEKS + 1;
+
+    let exercises = session
+        .exercises()
+        .into_iter()
+        .map(|(_, performed)| {
+            let e1rm = best_e1rm(performed);
+            if !is_main_lift(performed) || e1rm.is_none() {
+                return accessory(performed, ACCESSORY_RANGE);
+            }
+
+            let increment = increment_for(performed);
+            let lift = session.lift(&performed.exercise_template_id);
+            let training_max = match lift.training_max_kg {
+                None => round_to_plates(e1rm.unwrap_or_default() * TRAINING_MAX_FACTOR),
+                Some(mut training_max) => {
+                    if let Some((_, min_reps, true)) = (1..CYCLE_WEEKS)
+                        .contains(&performed_week)
+                        .then(|| scheme(performed_week)[2])
+                    {
+                        let amrap_reps = performed
+                            .working_sets()
+                            .last()
+                            .and_then(|set| set.reps)
+                            .unwrap_or(0);
+                        if amrap_reps < min_reps {
+                            lift.failures += 1;
+                            training_max =
+                                round_to_plates(training_max * FAILED_TRAINING_MAX_FACTOR);
+                        } else {
+                            lift.failures = 0;
+                        }
+                    }
+                    if performed_week == CYCLE_WEEKS {
+                        training_max += increment;
+                    }
+                    training_max
+                }
+            };
+            lift.training_max_kg = Some(training_max);
+
+            let targets: Vec<SetTarget> = scheme(next_week)
+                .iter()
+                .map(|&(percentage, reps, amrap)| {
+                    let weight = Some(round_to_plates(training_max * percentage));
+                    if amrap {
+                        SetTarget::amrap(weight, reps)
+                    } else {
+                        SetTarget::new(weight, reps)
+                    }
+                })
+                .collect();
+            build_exercise(performed, &targets)
+        })
+        .collect();
+
+    (exercises, next_week)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+    use super::super::{LiftState, Program, RoutineProgramState};
+
+    fn state(week: u32, training_max_kg: f32) -> RoutineProgramState {
+        let mut state = RoutineProgramState {
+            week,
+            ..RoutineProgramState::default()
+        };
+        state.lifts.insert(
+            "squat".to_string(),
+            LiftState {
+                training_max_kg: Some(training_max_kg),
+                ..LiftState::default()
+            },
+        );
+        state
+    }
+
+    #[test]
+    fn test_starts_from_estimated_max_and_runs_the_wave() {
+        let (workout, routine) = workout(
+            "Day 1 - Week 7",
+            vec![
+                exercise(
+                    "squat",
+                    "Squat (Barbell)",
+                    vec![set("warmup", Some(60.0), 5), set("normal", Some(120.0), 5)],
+                ),
+                exercise(
+                    "dips",
+                    "Chest Dip (Weighted)",
+                    vec![set("normal", Some(10.0), 15); 3],
+                ),
+            ],
+        );
+
+        let (response, update) =
+            Program::FiveThreeOne.prescribe(&workout, &routine, &RoutineProgramState::default());
+
+        // 120x5 is a 140kg e1RM, so the training max is 125kg
+        assert_eq!(update.lifts["squat"].training_max_kg, Some(125.0));
+        assert_eq!(response.routine_title, "Day 1 - Week 1");
+        let squat = &response.updated_exercises[0];
+        assert_eq!(squat.sets[0].set_type, "warmup");
+        assert_eq!(describe(squat), ["82.5x5", "95x5", "107.5x5+"]);
+        assert_eq!(
+            describe(&response.updated_exercises[1]),
+            ["12.5x10", "12.5x10", "12.5x10"]
+        );
+    }
+
+    #[test]
+    fn test_missed_amrap_drops_training_max_and_deload_raises_it() {
+        let (workout, routine) = workout(
+            "Day 1 - Week 3",
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![
+                    set("normal", Some(95.0), 5),
+                    set("normal", Some(107.5), 3),
+                    set("failure", Some(120.0), 0),
+                ],
+            )],
+        );
+
+        let (response, update) =
+            Program::FiveThreeOne.prescribe(&workout, &routine, &state(3, 125.0));
+        assert_eq!(update.lifts["squat"].training_max_kg, Some(112.5));
+        assert_eq!(update.lifts["squat"].failures, 1);
+        assert_eq!(update.week, 4);
+        assert_eq!(
+            describe(&response.updated_exercises[0]),
+            ["45x5", "57.5x5", "67.5x5"]
+        );
+
+        let (response, update) =
+            Program::FiveThreeOne.prescribe(&workout, &routine, &state(4, 125.0));
+        assert_eq!(update.lifts["squat"].training_max_kg, Some(130.0));
+        assert_eq!(response.week_number, 1);
+    }
+}
@@ -0,0 +1,177 @@
+//! GZCLP. The first exercise of the routine is the T1 lift (5x3, then 6x2,
+//! then 10x1 after each failure, last set AMRAP), the second is T2 (3x10,
+//! 3x8, 3x6) and the rest are T3 (3x15 with the last set AMRAP). T1 and T2
+//! add load after every successful session; failing the last stage resets
+//! to the first at 85% of the weight. T3 adds 2.5kg once the AMRAP set
+//! reaches 25 reps.
+
+use super::{
+    ProgramSession, SetTarget, build_exercise, hit_target, increment_for, round_to_plates,
+    top_working_weight,
+};
+use crate::clients::models::common::Exercise;
+
+const T1_STAGES: [(usize, u32); 3] = [(5, 3), (6, 2), (10, 1)];
+const T2_STAGES: [(usize, u32); 3] = [(3, 10), (3, 8), (3, 6)];
+const T3_SETS: usize = 3;
+const T3_REPS: u32 = 15;
+const T3_PROGRESS_AT_REPS: u32 = 25;
+const T3_INCREMENT_KG: f32 = 2.5;
+const RESET_FACTOR: f32 = 0.85;
+
+pub(crate) fn prescribe(session: &mut ProgramSession) -> (Vec<Exercise>, u32) {
+    let exercises = session
+        .exercises()
+        .into_iter()
+        .map(|(position, performed)| {
+            let top = top_working_weight(performed);
+            match (position, top) {
+                (0, Some(top)) => tiered(session, "t1", performed, top, &T1_STAGES, true),
+                (1, Some(top)) => tiered(session, "t2", performed, top, &T2_STAGES, false),
+                _ => tier3(session, performed, top),
+            }
+        })
+        .collect();
+
+    (exercises, session.title_week())
+}
+
+fn tiered(
+    session: &mut ProgramSession,
+    tier: &str,
+    performed: &Exercise,
+    top: f32,
+    stages: &[(usize, u32); 3],
+    amrap_last: bool,
+) -> Exercise {
+    let increment = increment_for(performed);
+    let lift = session.lift(&format!("{}:{}", tier, performed.exercise_template_id));
+    let stage = (lift.stage as usize).min(stages.len() - 1);
+    let weight = lift.working_weight_kg.unwrap_or(top);
+
+    let next = if hit_target(performed, weight, stages[stage].1) {
+        lift.failures = 0;
+        weight + increment
+    } else if stage + 1 < stages.len() {
+        lift.failures += 1;
+        lift.stage += 1;
+        weight
+    } else {
+        lift.failures += 1;
+        lift.stage = 0;
+        round_to_plates(weight * RESET_FACTOR)
+    };
+    lift.working_weight_kg = Some(next);
+
+    let (sets, reps) = stages[lift.stage as usize];
+    let mut targets = vec![SetTarget::new(Some(next), reps); sets];
+    if amrap_last && let Some(last) = targets.last_mut() {
+        *last = SetTarget::amrap(Some(next), reps);
+    }
+    build_exercise(performed, &targets)
+}
+
+fn tier3(session: &mut ProgramSession, performed: &Exercise, top: Option<f32>) -> Exercise {
+    let lift = session.lift(&format!("t3:{}", performed.exercise_template_id));
+    let weight = lift.working_weight_kg.or(top);
+    let amrap_reps = performed
+        .working_sets()
+        .last()
+        .and_then(|set| set.reps)
+        .unwrap_or(0);
+
+    let next = weight.map(|weight| {
+        if amrap_reps >= T3_PROGRESS_AT_REPS {
+            weight + T3_INCREMENT_KG
+        } else {
+            weight
+        }
+    });
+    lift.working_weight_kg = next;
+
+    let mut targets = vec![SetTarget::new(next, T3_REPS); T3_SETS - 1];
+    targets.push(SetTarget::amrap(next, T3_REPS));
+    build_exercise(performed, &targets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+    use super::super::{LiftState, Program, RoutineProgramState};
+
+    #[test]
+    fn test_tiers_progress_and_step_down_on_failure() {
+        let (workout, routine) = workout(
+            "Day A1",
+            vec![
+                exercise(
+                    "squat",
+                    "Squat (Barbell)",
+                    vec![set("normal", Some(100.0), 3); 5],
+                ),
+                exercise(
+                    "bench",
+                    "Bench Press (Barbell)",
+                    vec![
+                        set("normal", Some(60.0), 10),
+                        set("normal", Some(60.0), 10),
+                        set("normal", Some(60.0), 7),
+                    ],
+                ),
+                exercise(
+                    "pulldown",
+                    "Lat Pulldown (Cable)",
+                    vec![
+                        set("normal", Some(40.0), 15),
+                        set("normal", Some(40.0), 15),
+                        set("normal", Some(40.0), 26),
+                    ],
+                ),
+            ],
+        );
+
+        let (response, update) =
+            Program::Gzclp.prescribe(&workout, &routine, &RoutineProgramState::default());
+
+        assert_eq!(response.routine_title, "Day A1");
+        assert_eq!(
+            describe(&response.updated_exercises[0]),
+            ["105x3", "105x3", "105x3", "105x3", "105x3+"]
+        );
+        assert_eq!(
+            describe(&response.updated_exercises[1]),
+            ["60x8", "60x8", "60x8"]
+        );
+        assert_eq!(update.lifts["t2:bench"].stage, 1);
+        assert_eq!(
+            describe(&response.updated_exercises[2]),
+            ["42.5x15", "42.5x15", "42.5x15+"]
+        );
+    }
+
+    #[test]
+    fn test_failing_last_t1_stage_resets() {
+        let (workout, routine) = workout(
+            "Day A1",
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(120.0), 1), set("normal", Some(120.0), 0)],
+            )],
+        );
+        let mut state = RoutineProgramState::default();
+        state.lifts.insert(
+            "t1:squat".to_string(),
+            LiftState {
+                working_weight_kg: Some(120.0),
+                stage: 2,
+                ..LiftState::default()
+            },
+        );
+
+        let (response, update) = Program::Gzclp.prescribe(&workout, &routine, &state);
+
+        assert_eq!(update.lifts["t1:squat"].stage, 0);
+        assert_eq!(describe(&response.updated_exercises[0])[0], "102.5x3");
+    }
+}
@@ -0,0 +1,104 @@
+//! Linear progression: main lifts for 3x5, adding load after every session
+//! where all sets were completed and dropping 10% after three failed sessions
+//! in a row. Accessories use double progression in 8-12.
+
+use super::{
+    ProgramSession, SetTarget, accessory, build_exercise, hit_target, increment_for, is_main_lift,
+    round_to_plates, top_working_weight,
+};
+use crate::clients::models::common::Exercise;
+use crate::services::double_progression::RepTarget;
+
+const SETS: usize = 3;
+const REPS: u32 = 5;
+const RESET_AFTER_FAILURES: u32 = 3;
+const RESET_FACTOR: f32 = 0.9;
+const ACCESSORY_RANGE: RepTarget = RepTarget { min: 8, max: 12 };
+
+pub(crate) fn prescribe(session: &mut ProgramSession) -> (Vec<Exercise>, u32) {
+    let exercises = session
+        .exercises()
+        .into_iter()
+        .map(|(_, performed)| {
+            let top = top_working_weight(performed);
+            if !is_main_lift(performed) || top.is_none() {
+                return accessory(performed, ACCESSORY_RANGE);
+            }
+
+            let increment = increment_for(performed);
+            let lift = session.lift(&performed.exercise_template_id);
+            let current = lift.working_weight_kg.or(top).unwrap_or_default();
+
+            let next = if hit_target(performed, current, REPS) {
+                lift.failures = 0;
+                current + increment
+            } else {
+                lift.failures += 1;
+                if lift.failures >= RESET_AFTER_FAILURES {
+                    lift.failures = 0;
+                    round_to_plates(current * RESET_FACTOR)
+                } else {
+                    current
+                }
+            };
+            lift.working_weight_kg = Some(next);
+
+            build_exercise(performed, &[SetTarget::new(Some(next), REPS); SETS])
+        })
+        .collect();
+
+    (exercises, session.title_week())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+    use super::super::{LiftState, Program, RoutineProgramState};
+
+    #[test]
+    fn test_adds_load_on_success_and_resets_after_three_failures() {
+        let (workout, routine) = workout(
+            "Day 1 - Week 3",
+            vec![
+                exercise(
+                    "bench",
+                    "Bench Press (Barbell)",
+                    vec![set("normal", Some(80.0), 5); 3],
+                ),
+                exercise(
+                    "squat",
+                    "Squat (Barbell)",
+                    vec![set("normal", Some(100.0), 4); 3],
+                ),
+                exercise(
+                    "curl",
+                    "Bicep Curl (Dumbbell)",
+                    vec![set("normal", Some(12.0), 9); 2],
+                ),
+            ],
+        );
+        let mut state = RoutineProgramState::default();
+        state.lifts.insert(
+            "squat".to_string(),
+            LiftState {
+                working_weight_kg: Some(100.0),
+                failures: 2,
+                ..LiftState::default()
+            },
+        );
+
+        let (response, update) = Program::Linear.prescribe(&workout, &routine, &state);
+
+        assert_eq!(response.routine_title, "Day 1 - Week 3");
+        assert_eq!(
+            describe(&response.updated_exercises[0]),
+            ["82.5x5", "82.5x5", "82.5x5"]
+        );
+        assert_eq!(
+            describe(&response.updated_exercises[1]),
+            ["90x5", "90x5", "90x5"]
+        );
+        assert_eq!(update.lifts["squat"].failures, 0);
+        assert_eq!(describe(&response.updated_exercises[2]), ["12x10", "12x10"]);
+    }
+}
@@ -0,0 +1,399 @@
+pub mod five_three_one;
+pub mod gzclp;
+pub mod linear;
+pub mod texas_method;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    responses::{RoutineResponse, WorkoutResponse},
+};
+use crate::history::estimated_one_rep_max;
+use crate::services::ai_parser;
+use crate::services::double_progression::{DoubleProgression, RepTarget};
+use crate::services::progressive_overload::ProgressiveOverloadResponse;
+
+/// Bump whenever a program's rules change. Audit records carry it in their
+/// `model`, e.g. `gzclp-v2`, so a bump separates prescriptions made under the
+/// new rules from older ones.
+pub const PROGRAMS_VERSION: &str = "2";
+
+const UPPER_INCREMENT_KG: f32 = 2.5;
+const LOWER_INCREMENT_KG: f32 = 5.0;
+const PLATE_STEP_KG: f32 = 2.5;
+
+/// An established program that prescribes a routine's next session
+/// deterministically instead of an engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Program {
+    /// Wendler's 5/3/1: four-week waves off a training max.
+    FiveThreeOne,
+    /// GZCL linear progression with T1/T2/T3 tiers.
+    Gzclp,
+    /// Volume, recovery and intensity days off a five rep max.
+    TexasMethod,
+    /// 3x5, adding load every successful session.
+    Linear,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown program `{0}`, expected `531`, `gzclp`, `texas_method` or `linear`")]
+pub struct UnknownProgram(String);
+
+impl Program {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Program::FiveThreeOne => "five_three_one",
+            Program::Gzclp => "gzclp",
+            Program::TexasMethod => "texas_method",
+            Program::Linear => "linear",
+        }
+    }
+
+    /// Prescribes the next session of `routine` from the `workout` just
+    /// performed. Nothing is mutated: the returned update is applied to the
+    /// program store once Hevy has accepted the routine update.
+    pub fn prescribe(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+        state: &RoutineProgramState,
+    ) -> (ProgressiveOverloadResponse, ProgramUpdate) {
+        let mut session = ProgramSession {
+            workout,
+            routine,
+            week: state.week,
+            lifts: state.lifts.clone(),
+            touched: Vec::new(),
+        };
+
+        let (updated_exercises, next_week) = match self {
+            Program::FiveThreeOne => five_three_one::prescribe(&mut session),
+            Program::Gzclp => gzclp::prescribe(&mut session),
+            Program::TexasMethod => texas_method::prescribe(&mut session),
+            Program::Linear => linear::prescribe(&mut session),
+        };
+
+        let response = ProgressiveOverloadResponse {
+            updated_exercises,
+            week_number: next_week,
+            routine_title: retitle(&workout.title, next_week),
+            stall_adjustments: Vec::new(),
+            bodyweight_exercises: HashMap::new(),
+            rest_adjustments: Vec::new(),
+            parse_warnings: Vec::new(),
+        };
+        let update = ProgramUpdate {
+            routine_id: routine.id.clone(),
+            program: *self,
+            week: next_week,
+            lifts: session
+                .touched
+                .iter()
+                .filter_map(|key| Some((key, session.lifts.get(key)?)))
+                .filter(|(key, lift)| state.lifts.get(*key) != Some(*lift))
+                .map(|(key, lift)| (key.clone(), lift.clone()))
+                .collect(),
+        };
+        (response, update)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Program {
+    type Err = UnknownProgram;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "531" | "5/3/1" | "five_three_one" | "wendler" => Ok(Program::FiveThreeOne),
+            "gzclp" => Ok(Program::Gzclp),
+            "texas" | "texas_method" => Ok(Program::TexasMethod),
+            "linear" | "lp" => Ok(Program::Linear),
+            _ => Err(UnknownProgram(value.to_string())),
+        }
+    }
+}
+
+/// Progress of one lift within a program.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiftState {
+    /// 5/3/1 training max, or the Texas Method intensity-day five rep max.
+    pub training_max_kg: Option<f32>,
+    /// Working weight for GZCLP and linear progression.
+    pub working_weight_kg: Option<f32>,
+    /// GZCLP rep scheme stage, 0 being the first.
+    pub stage: u32,
+    /// Consecutive failed sessions.
+    pub failures: u32,
+}
+
+/// What a program needs to prescribe one routine: the week it last
+/// prescribed (0 before the first run) and the lifts of that program.
+#[derive(Debug, Default, Clone)]
+pub struct RoutineProgramState {
+    pub week: u32,
+    pub lifts: HashMap<String, LiftState>,
+}
+
+/// State changes from one prescription, to be applied once it has been
+/// written to Hevy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgramUpdate {
+    pub routine_id: String,
+    pub program: Program,
+    pub week: u32,
+    /// Only the lifts this prescription changed, so routines sharing a
+    /// program don't overwrite each other's progress.
+    pub lifts: HashMap<String, LiftState>,
+}
+
+pub(crate) struct ProgramSession<'a> {
+    pub workout: &'a WorkoutResponse,
+    pub routine: &'a RoutineResponse,
+    pub week: u32,
+    lifts: HashMap<String, LiftState>,
+    touched: Vec<String>,
+}
+
+impl<'a> ProgramSession<'a> {
+    /// The week in the performed workout's title, for programs without a
+    /// week structure of their own.
+    pub fn title_week(&self) -> u32 {
+        ai_parser::extract_week_and_day(&self.workout.title).0
+    }
+
+    pub fn lift(&mut self, key: &str) -> &mut LiftState {
+        if !self.touched.iter().any(|touched| touched == key) {
+            self.touched.push(key.to_string());
+        }
+        self.lifts.entry(key.to_string()).or_default()
+    }
+
+    /// Performed exercises in routine order, with their position in the
+    /// routine (or after it, for exercises added during the workout).
+    pub fn exercises(&self) -> Vec<(usize, &'a Exercise)> {
+        let routine = self.routine;
+        let mut exercises: Vec<_> = self
+            .workout
+            .exercises
+            .iter()
+            .map(|performed| {
+                let position = routine
+                    .exercises
+                    .iter()
+                    .position(|planned| {
+                        planned.exercise_template_id == performed.exercise_template_id
+                    })
+                    .unwrap_or(routine.exercises.len() + performed.index as usize);
+                (position, performed)
+            })
+            .collect();
+        exercises.sort_by_key(|(position, _)| *position);
+        exercises
+    }
+}
+
+/// One prescribed working set.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SetTarget {
+    pub weight_kg: Option<f32>,
+    pub reps: u32,
+    /// As many reps as possible, written as a Hevy "failure" set.
+    pub amrap: bool,
+}
+
+impl SetTarget {
+    pub fn new(weight_kg: Option<f32>, reps: u32) -> Self {
+        Self {
+            weight_kg,
+            reps,
+            amrap: false,
+        }
+    }
+
+    pub fn amrap(weight_kg: Option<f32>, reps: u32) -> Self {
+        Self {
+            weight_kg,
+            reps,
+            amrap: true,
+        }
+    }
+}
+
+/// The performed warmups followed by `targets` as working sets.
+pub(crate) fn build_exercise(performed: &Exercise, targets: &[SetTarget]) -> Exercise {
+    let warmups = performed.sets.iter().filter(|set| set.is_warmup()).cloned();
+    let working = targets.iter().map(|target| ExerciseSet {
+        index: 0,
+        set_type: if target.amrap { "failure" } else { "normal" }.to_string(),
+        weight_kg: target.weight_kg,
+        reps: Some(target.reps),
+        distance_meters: None,
+        duration_seconds: None,
+        rpe: None,
+        custom_metric: None,
+        rep_range: None,
+    });
+
+    Exercise {
+        sets: warmups
+            .chain(working)
+            .enumerate()
+            .map(|(index, set)| ExerciseSet {
+                index: index as u32,
+                ..set
+            })
+            .collect(),
+        ..performed.clone()
+    }
+}
+
+/// Accessories progress by double progression within `range`.
+pub(crate) fn accessory(performed: &Exercise, range: RepTarget) -> Exercise {
+    let progression = DoubleProgression {
+        increment_kg: UPPER_INCREMENT_KG,
+        default_range: range,
+        ..DoubleProgression::default()
+    };
+    let (sets, _) = progression.progress(performed, range);
+    Exercise {
+        sets,
+        ..performed.clone()
+    }
+}
+
+/// Barbell work is programmed as a main lift, everything else as an
+/// accessory. Hevy puts the equipment in the title, e.g. "Squat (Barbell)".
+pub(crate) fn is_main_lift(exercise: &Exercise) -> bool {
+    exercise.title.to_ascii_lowercase().contains("barbell")
+}
+
+pub(crate) fn increment_for(exercise: &Exercise) -> f32 {
+    let title = exercise.title.to_ascii_lowercase();
+    let lower_body = ["squat", "deadlift", "leg press", "lunge", "hip thrust"]
+        .iter()
+        .any(|keyword| title.contains(keyword));
+    if lower_body {
+        LOWER_INCREMENT_KG
+    } else {
+        UPPER_INCREMENT_KG
+    }
+}
+
+pub(crate) fn round_to_plates(weight_kg: f32) -> f32 {
+    (weight_kg / PLATE_STEP_KG).round() * PLATE_STEP_KG
+}
+
+pub(crate) fn top_working_weight(exercise: &Exercise) -> Option<f32> {
+    exercise
+        .working_sets()
+        .filter_map(|set| set.weight_kg)
+        .reduce(f32::max)
+}
+
+pub(crate) fn best_e1rm(exercise: &Exercise) -> Option<f32> {
+    exercise
+        .working_sets()
+        .filter_map(|set| Some(estimated_one_rep_max(set.weight_kg?, set.reps?)))
+        .reduce(f32::max)
+}
+
+/// Whether every working set was done at `weight_kg` or more for at least
+/// `reps`. A session with no working sets never counts as a success.
+pub(crate) fn hit_target(exercise: &Exercise, weight_kg: f32, reps: u32) -> bool {
+    let mut sets = exercise.working_sets().peekable();
+    sets.peek().is_some()
+        && sets.all(|set| {
+            set.weight_kg.unwrap_or(0.0) >= weight_kg - 0.01 && set.reps.unwrap_or(0) >= reps
+        })
+}
+
+/// Replaces the "Week N" in `title` with `week`, leaving titles without a
+/// week alone.
+fn retitle(title: &str, week: u32) -> String {
+    let week_regex = Regex::new(r"(?i)week\s*\d+").unwrap();
+    week_regex
+        .replace(title, format!("Week {}", week).as_str())
+        .into_owned()
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::testing::mock_hevy;
+    pub use crate::testing::mock_hevy::{exercise, set};
+
+    /// A workout in routine `r1` with the same exercises planned.
+    pub fn workout(title: &str, exercises: Vec<Exercise>) -> (WorkoutResponse, RoutineResponse) {
+        let workout = mock_hevy::workout("w1", title, "r1", exercises.clone());
+        (workout, mock_hevy::routine("r1", title, exercises))
+    }
+
+    /// Working sets as `weight x reps`, with `+` marking AMRAP sets.
+    pub fn describe(exercise: &Exercise) -> Vec<String> {
+        exercise
+            .working_sets()
+            .map(|set| {
+                format!(
+                    "{}x{}{}",
+                    set.weight_kg.unwrap_or(0.0),
+                    set.reps.unwrap_or(0),
+                    if set.set_type == "failure" { "+" } else { "" }
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+
+    #[test]
+    fn test_program_names_and_titles() {
+        assert_eq!("5/3/1".parse::<Program>().unwrap(), Program::FiveThreeOne);
+        assert_eq!(
+            "Texas-Method".parse::<Program>().unwrap(),
+            Program::TexasMethod
+        );
+        assert!("starting_strength".parse::<Program>().is_err());
+
+        assert_eq!(retitle("Day 1 - Week 4", 1), "Day 1 - Week 1");
+        assert_eq!(retitle("Upper A", 3), "Upper A");
+    }
+
+    #[test]
+    fn test_update_only_carries_touched_lifts() {
+        let (workout, routine) = workout(
+            "Day 1",
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(100.0), 5); 3],
+            )],
+        );
+        let mut state = RoutineProgramState::default();
+        state
+            .lifts
+            .insert("bench".to_string(), LiftState::default());
+
+        let (response, update) = Program::Linear.prescribe(&workout, &routine, &state);
+
+        assert_eq!(response.routine_title, "Day 1");
+        assert_eq!(update.lifts.len(), 1);
+        assert_eq!(update.lifts["squat"].working_weight_kg, Some(105.0));
+    }
+}
@@ -0,0 +1,174 @@
+//! Texas Method. Each routine is one day of the week, told apart by its
+//! title ("Volume", "Recovery"/"Light", "Intensity", or Day 1/2/3). Main lifts
+//! run off the intensity-day five rep max: 5x5 at 90% on volume day, 2x5 at
+//! 80% of that on recovery day, and a single set of 5 above the last one on
+//! intensity day. Two missed intensity days in a row drop the five rep max
+//! by 10%. Accessories use double progression in 8-12.
+
+use super::{
+    ProgramSession, SetTarget, accessory, best_e1rm, build_exercise, hit_target, increment_for,
+    is_main_lift, round_to_plates,
+};
+use crate::clients::models::common::Exercise;
+use crate::services::ai_parser;
+use crate::services::double_progression::RepTarget;
+
+const VOLUME_FACTOR: f32 = 0.9;
+const RECOVERY_FACTOR: f32 = 0.8;
+const RESET_AFTER_FAILURES: u32 = 2;
+const RESET_FACTOR: f32 = 0.9;
+const ACCESSORY_RANGE: RepTarget = RepTarget { min: 8, max: 12 };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Day {
+    Volume,
+    Recovery,
+    Intensity,
+}
+
+fn day_of(title: &str) -> Day {
+    let lowercase = title.to_ascii_lowercase();
+    if lowercase.contains("intensity") {
+        Day::Intensity
+    } else if lowercase.contains("recovery") || lowercase.contains("light") {
+        Day::Recovery
+    } else if lowercase.contains("volume") {
+        Day::Volume
+    } else {
+        match ai_parser::extract_week_and_day(title).1 {
+            2 => Day::Recovery,
+            3 => Day::Intensity,
+            _ => Day::Volume,
+        }
+    }
+}
+
+pub(crate) fn prescribe(session: &mut ProgramSession) -> (Vec<Exercise>, u32) {
+    let day = day_of(&session.routine.title);
+
+    let exercises = session
+        .exercises()
+        .into_iter()
+        .map(|(_, performed)| {
+            let e1rm = best_e1rm(performed);
+            if !is_main_lift(performed) || e1rm.is_none() {
+                return accessory(performed, ACCESSORY_RANGE);
+            }
+
+            let increment = increment_for(performed);
+            let lift = session.lift(&performed.exercise_template_id);
+            let five_rep_max = match lift.training_max_kg {
+                // Epley, solved for five reps. Volume and recovery days are
+                // lifted below the five rep max, so scale back up to it
+                None => {
+                    let five_reps = e1rm.unwrap_or_default() / (1.0 + 5.0 / 30.0);
+                    round_to_plates(match day {
+                        Day::Volume => five_reps / VOLUME_FACTOR,
+                        Day::Recovery => five_reps / (VOLUME_FACTOR * RECOVERY_FACTOR),
+                        Day::Intensity => five_reps,
+                    })
+                }
+                Some(five_rep_max) if day == Day::Intensity => {
+                    if hit_target(performed, five_rep_max + increment, 5) {
+                        lift.failures = 0;
+                        five_rep_max + increment
+                    } else {
+                        lift.failures += 1;
+                        if lift.failures >= RESET_AFTER_FAILURES {
+                            lift.failures = 0;
+                            round_to_plates(five_rep_max * RESET_FACTOR)
+                        } else {
+                            five_rep_max
+                        }
+                    }
+                }
+                Some(five_rep_max) => five_rep_max,
+            };
+            lift.training_max_kg = Some(five_rep_max);
+
+            let volume = round_to_plates(five_rep_max * VOLUME_FACTOR);
+            let targets = match day {
+                Day::Volume => vec![SetTarget::new(Some(volume), 5); 5],
+                Day::Recovery => {
+                    vec![SetTarget::new(Some(round_to_plates(volume * RECOVERY_FACTOR)), 5); 2]
+                }
+                Day::Intensity => vec![SetTarget::new(Some(five_rep_max + increment), 5)],
+            };
+            build_exercise(performed, &targets)
+        })
+        .collect();
+
+    (exercises, session.title_week() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::*;
+    use super::super::{LiftState, Program, RoutineProgramState};
+    use super::*;
+
+    #[test]
+    fn test_days_share_the_five_rep_max() {
+        assert_eq!(day_of("Texas - Light Day"), Day::Recovery);
+        assert_eq!(day_of("Day 3 - Week 2"), Day::Intensity);
+
+        let mut state = RoutineProgramState::default();
+        state.lifts.insert(
+            "squat".to_string(),
+            LiftState {
+                training_max_kg: Some(140.0),
+                ..LiftState::default()
+            },
+        );
+
+        let (volume_workout, volume_routine) = workout(
+            "Day 1 - Week 2",
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(125.0), 5); 5],
+            )],
+        );
+        let (response, update) =
+            Program::TexasMethod.prescribe(&volume_workout, &volume_routine, &state);
+        assert_eq!(describe(&response.updated_exercises[0]), ["125x5"; 5]);
+        assert_eq!(response.routine_title, "Day 1 - Week 3");
+        assert!(update.lifts.is_empty(), "volume day leaves the 5RM alone");
+
+        let (intensity_workout, intensity_routine) = workout(
+            "Day 3 - Week 2",
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(145.0), 5)],
+            )],
+        );
+        let (response, update) =
+            Program::TexasMethod.prescribe(&intensity_workout, &intensity_routine, &state);
+        assert_eq!(update.lifts["squat"].training_max_kg, Some(145.0));
+        assert_eq!(describe(&response.updated_exercises[0]), ["150x5"]);
+    }
+
+    #[test]
+    fn test_first_run_seeds_the_five_rep_max_from_the_day_lifted() {
+        let state = RoutineProgramState::default();
+        let squat = |weight, sets| {
+            vec![exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(weight), 5); sets],
+            )]
+        };
+
+        let (volume_workout, volume_routine) = workout("Day 1 - Week 1", squat(125.0, 5));
+        let (response, update) =
+            Program::TexasMethod.prescribe(&volume_workout, &volume_routine, &state);
+        assert_eq!(update.lifts["squat"].training_max_kg, Some(140.0));
+        assert_eq!(describe(&response.updated_exercises[0]), ["125x5"; 5]);
+
+        let (recovery_workout, recovery_routine) = workout("Day 2 - Week 1", squat(90.0, 2));
+        let (response, _) =
+            Program::TexasMethod.prescribe(&recovery_workout, &recovery_routine, &state);
+        assert_eq!(describe(&response.updated_exercises[0]), ["90x5"; 2]);
+    }
+}
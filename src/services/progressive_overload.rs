@@ -1,15 +1,29 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use tracing::debug;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tracing::{debug, warn};
 
-use crate::clients::gemini::GeminiClient;
 use crate::clients::hevy::HevyClient;
+use crate::clients::llm::LlmClient;
 use crate::clients::models::{
     common::Exercise,
     responses::{RoutineResponse, WorkoutResponse},
 };
-use crate::services::deload::{DeloadCalculator, DeloadContextBuilder};
+use crate::metrics::metrics;
+use crate::program_store::ProgramStore;
+use crate::services::adherence::{self, AdherenceReport};
+use crate::services::ai_parser::ParseWarning;
+use crate::services::bodyweight::{BodyweightProgression, LoadKind};
+use crate::services::conditioning::Conditioning;
+use crate::services::deload::{self, DeloadCalculator, DeloadContextBuilder};
+use crate::services::double_progression::DoubleProgression;
+use crate::services::engine::Engine;
+use crate::services::exercise_kinds::ExerciseKinds;
+use crate::services::programs::{PROGRAMS_VERSION, ProgramUpdate};
+use crate::services::rest::{RestAdjustment, RestPeriods};
+use crate::services::rule_based::{RULES_VERSION, RuleBasedEngine};
+use crate::services::stalls::{Prescription, PrescriptionRecord, StallAdjustment, StallDetector};
 use crate::services::{ai_parser, ai_prompt, output_formatter};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,52 +32,410 @@ pub struct ProgressiveOverloadRequest {
     pub routine: RoutineResponse,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgressiveOverloadResponse {
     pub updated_exercises: Vec<Exercise>,
     pub week_number: u32,
     pub routine_title: String,
+    /// Exercises that missed their last prescription and what was done
+    /// about it.
+    #[serde(default)]
+    pub stall_adjustments: Vec<StallAdjustment>,
+    /// Bodyweight, weighted and assisted exercises by template id, so their
+    /// loads can be shown relative to bodyweight.
+    #[serde(default)]
+    pub bodyweight_exercises: HashMap<String, LoadKind>,
+    /// Rest periods to write to the routine for the next week's phase.
+    #[serde(default)]
+    pub rest_adjustments: Vec<RestAdjustment>,
+    /// Repairs the parser made to the model output, empty for the rule-based
+    /// engine.
+    #[serde(default)]
+    pub parse_warnings: Vec<ParseWarning>,
+}
+
+/// A parsed response together with the prompt and raw model output that
+/// produced it, kept for the audit log.
+#[derive(Debug)]
+pub struct ProgressiveOverloadRun {
+    pub prompt: String,
+    pub raw_response: String,
+    pub response: ProgressiveOverloadResponse,
+    /// What prescribed the response: the service's engine, or "program" when
+    /// the routine runs a built-in program.
+    pub engine: String,
+    pub model: String,
+    /// Program state to record once the routine update has been accepted.
+    pub program_update: Option<ProgramUpdate>,
+    /// The prescription to compare the next workout against, recorded once
+    /// the routine update has been accepted.
+    pub prescription_record: Option<PrescriptionRecord>,
+    /// How the workout compared to the last prescription, if one was
+    /// recorded for the routine.
+    pub adherence: Option<AdherenceReport>,
 }
 
 #[derive(Clone)]
 pub struct ProgressiveOverloadService {
-    gemini_client: GeminiClient,
+    engine: Engine,
+    llm_client: Arc<dyn LlmClient>,
     hevy_client: HevyClient,
     deload_calculator: DeloadCalculator,
+    rule_based: RuleBasedEngine,
+    double_progression: DoubleProgression,
+    conditioning: Conditioning,
+    bodyweight: BodyweightProgression,
+    rest_periods: RestPeriods,
+    stalls: StallDetector,
+    programs: ProgramStore,
 }
 
 impl ProgressiveOverloadService {
-    pub fn new(gemini_client: GeminiClient, hevy_client: HevyClient) -> Self {
+    pub fn new(
+        llm_client: Arc<dyn LlmClient>,
+        hevy_client: HevyClient,
+        double_progression: DoubleProgression,
+        stalls: StallDetector,
+        programs: ProgramStore,
+        engine: Engine,
+    ) -> Self {
         Self {
-            gemini_client,
+            engine,
+            llm_client,
             hevy_client,
             deload_calculator: DeloadCalculator::default(),
+            rule_based: RuleBasedEngine::default(),
+            double_progression,
+            conditioning: Conditioning::default(),
+            bodyweight: BodyweightProgression::default(),
+            rest_periods: RestPeriods::default(),
+            stalls,
+            programs,
+        }
+    }
+
+    pub fn with_conditioning(self, conditioning: Conditioning) -> Self {
+        Self {
+            conditioning,
+            ..self
+        }
+    }
+
+    pub fn with_bodyweight(self, bodyweight: BodyweightProgression) -> Self {
+        Self { bodyweight, ..self }
+    }
+
+    pub fn with_rest_periods(self, rest_periods: RestPeriods) -> Self {
+        Self {
+            rest_periods,
+            ..self
+        }
+    }
+
+    /// The same service with a different engine, for side-by-side comparisons.
+    pub fn with_engine(&self, engine: Engine) -> Self {
+        Self {
+            engine,
+            ..self.clone()
+        }
+    }
+
+    pub fn engine(&self) -> &'static str {
+        self.engine.as_str()
+    }
+
+    pub fn model(&self) -> String {
+        match self.engine {
+            Engine::Gemini => self.llm_client.model().to_string(),
+            Engine::RuleBased => format!("rules-v{}", RULES_VERSION),
         }
     }
 
     pub async fn process_workout_completion(
         &self,
         request: ProgressiveOverloadRequest,
-    ) -> Result<ProgressiveOverloadResponse> {
-        let prompt = self
-            .build_progressive_overload_prompt(&request.current_workout, &request.routine)
-            .await?;
+    ) -> Result<ProgressiveOverloadRun> {
+        let adherence = self.adherence(&request.current_workout, &request.routine);
+        if let Some(report) = &adherence {
+            debug!(
+                completed_sets = report.completed_sets,
+                prescribed_sets = report.prescribed_sets,
+                "adherence.compared"
+            );
+        }
 
-        debug!(prompt = %prompt, "gemini.prompt");
+        let kinds =
+            ExerciseKinds::lookup(&self.hevy_client, &request.current_workout.exercises).await;
 
-        let gemini_response = self.gemini_client.generate_text(&prompt).await?;
+        // Programs follow their own rules end to end, including deliberate
+        // jumps like 5/3/1's deload back to week 1, so neither double
+        // progression nor stall handling apply to them
+        if let Some((program, state)) = self.programs.state_for(&request.routine.id) {
+            let (mut response, update) =
+                program.prescribe(&request.current_workout, &request.routine, &state);
+            // Programs only know barbell work, so cardio and bodyweight
+            // exercises in the routine still follow their own progression
+            self.progress_by_kind(&request, &kinds, &mut response.updated_exercises, false);
+            response.bodyweight_exercises = kinds.load;
+            debug!(
+                program = %program,
+                week = update.week,
+                "program.prescribed"
+            );
+            let prescription_record = prescription_record(
+                &request,
+                &response.updated_exercises,
+                HashMap::new(),
+                HashMap::new(),
+            );
+            return Ok(ProgressiveOverloadRun {
+                prompt: String::new(),
+                raw_response: serde_json::to_string(&response)?,
+                response,
+                engine: "program".to_string(),
+                model: format!("{}-v{}", program, PROGRAMS_VERSION),
+                program_update: Some(update),
+                prescription_record: Some(prescription_record),
+                adherence,
+            });
+        }
 
-        debug!(response = %gemini_response, "gemini.response");
+        let (prompt, raw_response, mut parsed_response) = match self.engine {
+            Engine::Gemini => {
+                let prompt = self
+                    .build_prompt(
+                        &request.current_workout,
+                        &request.routine,
+                        adherence.as_ref(),
+                        &kinds.load,
+                    )
+                    .await;
 
-        let parsed_response = self.parse_gemini_response(&gemini_response)?;
-        Ok(parsed_response)
+                debug!(prompt = %prompt, "gemini.prompt");
+
+                let gemini_response = self.llm_client.generate_text(&prompt).await?;
+
+                debug!(response = %gemini_response, "gemini.response");
+
+                let parsed_response = self.parse_gemini_response(&gemini_response)?;
+                (prompt, gemini_response, parsed_response)
+            }
+            Engine::RuleBased => {
+                let pre_deload = self.pre_deload_reference(&request.current_workout).await;
+                let response = self.rule_based.prescribe(
+                    &request.current_workout,
+                    &request.routine,
+                    pre_deload.as_ref(),
+                );
+                (String::new(), serde_json::to_string(&response)?, response)
+            }
+        };
+
+        // Going into the deload or out of it into a new block, the engine's
+        // prescription is the plan; week-to-week rules would undo it
+        let (current_week, _) = ai_parser::extract_week_and_day(&request.current_workout.title);
+        let block_transition =
+            deload::is_block_transition(current_week, parsed_response.week_number);
+        if block_transition {
+            debug!(
+                current_week,
+                next_week = parsed_response.week_number,
+                "progression.block_transition"
+            );
+        }
+
+        // Double progression replaces whatever the engine prescribed for
+        // those exercises
+        let mut double_progression_ids = HashSet::new();
+        if !block_transition {
+            for (template_id, step) in self.double_progression.apply(
+                &request.current_workout,
+                &request.routine,
+                &mut parsed_response.updated_exercises,
+            ) {
+                debug!(
+                    exercise_template_id = %template_id,
+                    step = step.as_str(),
+                    "double_progression.applied"
+                );
+                double_progression_ids.insert(template_id);
+            }
+        }
+
+        let kind_ids = self.progress_by_kind(
+            &request,
+            &kinds,
+            &mut parsed_response.updated_exercises,
+            block_transition,
+        );
+        parsed_response.bodyweight_exercises = kinds.load;
+
+        // Missed reps override the engine, which may still push the weight
+        // up. Double progression already handles misses within its range.
+        // A miss going into the deload or out of it says nothing about the
+        // new loads, so it's neither acted on nor counted
+        let skip: HashSet<_> = double_progression_ids.union(&kind_ids).cloned().collect();
+        let (stall_adjustments, misses) = if block_transition {
+            (Vec::new(), HashMap::new())
+        } else {
+            self.stalls.apply(
+                &request.current_workout,
+                &mut parsed_response.updated_exercises,
+                &skip,
+            )
+        };
+        for adjustment in &stall_adjustments {
+            metrics()
+                .stall_responses
+                .with_label_values(&[adjustment.action.as_str()])
+                .inc();
+            warn!(
+                exercise_template_id = %adjustment.exercise_template_id,
+                consecutive_misses = adjustment.consecutive_misses,
+                action = adjustment.action.as_str(),
+                "stall.detected"
+            );
+        }
+        parsed_response.stall_adjustments = stall_adjustments;
+
+        // Intervals set their own rest, so conditioning exercises keep theirs
+        let conditioning_ids: HashSet<_> = kinds.conditioning.keys().cloned().collect();
+        let rest_baselines = self
+            .stalls
+            .prescriptions
+            .prescriptions_for(&request.routine.id)
+            .into_iter()
+            .filter_map(|(template_id, prescription)| {
+                Some((template_id, prescription.base_rest_seconds?))
+            })
+            .collect();
+        let (rest_adjustments, rest_baselines) = self.rest_periods.apply(
+            current_week,
+            parsed_response.week_number,
+            &request.routine,
+            &rest_baselines,
+            &mut parsed_response.updated_exercises,
+            &conditioning_ids,
+        );
+        parsed_response.rest_adjustments = rest_adjustments;
+        for adjustment in &parsed_response.rest_adjustments {
+            debug!(
+                exercise_template_id = %adjustment.exercise_template_id,
+                from_seconds = adjustment.from_seconds,
+                to_seconds = adjustment.to_seconds,
+                "rest.adjusted"
+            );
+        }
+
+        let prescription_record = prescription_record(
+            &request,
+            &parsed_response.updated_exercises,
+            misses,
+            rest_baselines,
+        );
+
+        Ok(ProgressiveOverloadRun {
+            prompt,
+            raw_response,
+            response: parsed_response,
+            engine: self.engine().to_string(),
+            model: self.model(),
+            program_update: None,
+            prescription_record: Some(prescription_record),
+            adherence,
+        })
+    }
+
+    /// Conditioning and bodyweight progression replace whatever the engine
+    /// or program prescribed for exercises of those kinds. Both are left to
+    /// the engine on a `block_transition`. Returns the template ids changed.
+    fn progress_by_kind(
+        &self,
+        request: &ProgressiveOverloadRequest,
+        kinds: &ExerciseKinds,
+        exercises: &mut [Exercise],
+        block_transition: bool,
+    ) -> HashSet<String> {
+        let mut applied = HashSet::new();
+        let conditioning = if block_transition {
+            Vec::new()
+        } else {
+            self.conditioning
+                .apply(&request.current_workout, &kinds.conditioning, exercises)
+        };
+        for (template_id, step) in conditioning {
+            debug!(
+                exercise_template_id = %template_id,
+                step = step.as_str(),
+                "conditioning.applied"
+            );
+            applied.insert(template_id);
+        }
+        let bodyweight = if block_transition {
+            Vec::new()
+        } else {
+            self.bodyweight.apply(
+                &request.current_workout,
+                &request.routine,
+                &kinds.load,
+                exercises,
+            )
+        };
+        for (template_id, step) in bodyweight {
+            debug!(
+                exercise_template_id = %template_id,
+                step = step.as_str(),
+                "bodyweight.applied"
+            );
+            applied.insert(template_id);
+        }
+        applied
     }
 
-    async fn build_progressive_overload_prompt(
+    /// The week 7 workout to start a new block from, looked up only once
+    /// week 8 is done.
+    async fn pre_deload_reference(&self, workout: &WorkoutResponse) -> Option<WorkoutResponse> {
+        let (current_week, _) = ai_parser::extract_week_and_day(&workout.title);
+        if current_week < 8 {
+            return None;
+        }
+        DeloadContextBuilder {
+            deload_calculator: &self.deload_calculator,
+            hevy_client: &self.hevy_client,
+        }
+        .find_week7_reference(workout)
+        .await
+    }
+
+    /// Compares `workout` with the prescription last written to `routine`.
+    pub fn adherence(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+    ) -> Option<AdherenceReport> {
+        let prescription = self.stalls.prescriptions.latest(&routine.id)?;
+        Some(adherence::compare(workout, routine, &prescription))
+    }
+
+    pub async fn build_progressive_overload_prompt(
         &self,
         workout: &WorkoutResponse,
         routine: &RoutineResponse,
     ) -> Result<String> {
+        let adherence = self.adherence(workout, routine);
+        let kinds = ExerciseKinds::lookup(&self.hevy_client, &workout.exercises).await;
+        Ok(self
+            .build_prompt(workout, routine, adherence.as_ref(), &kinds.load)
+            .await)
+    }
+
+    async fn build_prompt(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+        adherence: Option<&AdherenceReport>,
+        load_kinds: &HashMap<String, LoadKind>,
+    ) -> String {
         let (current_week_index, _) = ai_parser::extract_week_and_day(&workout.title);
         let routine_title = ai_parser::determine_routine_title_format(&workout.title);
 
@@ -74,13 +446,15 @@ impl ProgressiveOverloadService {
         .create_deload_transition_context(current_week_index, workout)
         .await;
 
-        Ok(ai_prompt::build_progressive_overload_prompt(
+        ai_prompt::build_progressive_overload_prompt(
             workout,
             routine,
             &deload_context,
             current_week_index,
             &routine_title,
-        ))
+            adherence,
+            load_kinds,
+        )
     }
 
     pub fn build_exercise_suggestions(
@@ -90,8 +464,55 @@ impl ProgressiveOverloadService {
         output_formatter::build_exercise_suggestions(response)
     }
 
+    pub fn build_routine_summary(&self, response: &ProgressiveOverloadResponse) -> String {
+        output_formatter::build_routine_summary(response)
+    }
+
     fn parse_gemini_response(&self, response: &str) -> Result<ProgressiveOverloadResponse> {
-        ai_parser::parse_gemini_response(response)
+        let parsed_response = ai_parser::parse_gemini_response(response).inspect_err(|_| {
+            metrics().llm_parse_failures.inc();
+        })?;
+
+        for warning in &parsed_response.parse_warnings {
+            metrics()
+                .llm_parse_repairs
+                .with_label_values(&[warning.kind.as_str()])
+                .inc();
+            warn!(
+                kind = warning.kind.as_str(),
+                path = %warning.path,
+                message = %warning.message,
+                "llm.parse_repaired"
+            );
+        }
+
+        Ok(parsed_response)
+    }
+}
+
+fn prescription_record(
+    request: &ProgressiveOverloadRequest,
+    exercises: &[Exercise],
+    misses: HashMap<String, u32>,
+    rest_baselines: HashMap<String, u32>,
+) -> PrescriptionRecord {
+    PrescriptionRecord {
+        routine_id: request.routine.id.clone(),
+        workout_id: request.current_workout.id.clone(),
+        exercises: exercises
+            .iter()
+            .map(|exercise| {
+                let template_id = &exercise.exercise_template_id;
+                (
+                    template_id.clone(),
+                    Prescription {
+                        base_rest_seconds: rest_baselines.get(template_id).copied(),
+                        ..Prescription::from_exercise(exercise)
+                    },
+                )
+            })
+            .collect(),
+        misses,
     }
 }
 
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::clients::models::{common::Exercise, responses::RoutineResponse};
+
+/// Rest is rounded to what a rest timer is usually set to.
+const REST_ROUNDING_SECONDS: f32 = 15.0;
+
+/// Where a week sits in the 8-week block, as far as rest goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockPhase {
+    /// Weeks 1-4, foundation and intensity.
+    Build,
+    /// Weeks 5-7, heavy work and testing.
+    Heavy,
+    /// Week 8.
+    Deload,
+}
+
+impl BlockPhase {
+    pub fn for_week(week: u32) -> Self {
+        match week {
+            5..=7 => BlockPhase::Heavy,
+            8.. => BlockPhase::Deload,
+            _ => BlockPhase::Build,
+        }
+    }
+}
+
+/// A rest period changed for the next week.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestAdjustment {
+    pub exercise_template_id: String,
+    pub from_seconds: u32,
+    pub to_seconds: u32,
+}
+
+/// Rest periods that follow the block: `heavy_increase` longer in heavy
+/// weeks and `deload_decrease` shorter in the deload, both fractions of the
+/// rest used in build weeks.
+#[derive(Debug, Clone, Default)]
+pub struct RestPeriods {
+    pub heavy_increase: f32,
+    pub deload_decrease: f32,
+}
+
+impl RestPeriods {
+    fn factor(&self, phase: BlockPhase) -> f32 {
+        match phase {
+            BlockPhase::Build => 1.0,
+            BlockPhase::Heavy => 1.0 + self.heavy_increase,
+            BlockPhase::Deload => (1.0 - self.deload_decrease).max(0.1),
+        }
+    }
+
+    /// Sets every exercise's rest for `next_week` from its build-week rest.
+    /// In a build week that's the routine's rest; otherwise it's the one in
+    /// `baselines`, recorded before the routine was rescaled, or worked back
+    /// from the routine's rest when there isn't one. Scaling from the
+    /// baseline each time keeps rounding from piling up, so rest returns to
+    /// where it started once the block comes round. Exercises in `skip` and
+    /// those without a rest in the routine are left alone. Returns the
+    /// adjustments made and the baseline of every exercise set, to record
+    /// with the prescription.
+    pub fn apply(
+        &self,
+        current_week: u32,
+        next_week: u32,
+        routine: &RoutineResponse,
+        baselines: &HashMap<String, u32>,
+        exercises: &mut [Exercise],
+        skip: &HashSet<String>,
+    ) -> (Vec<RestAdjustment>, HashMap<String, u32>) {
+        let current_phase = BlockPhase::for_week(current_week);
+        let next_phase = BlockPhase::for_week(next_week);
+        let mut adjustments = Vec::new();
+        let mut used_baselines = HashMap::new();
+
+        for exercise in exercises
+            .iter_mut()
+            .filter(|exercise| !skip.contains(&exercise.exercise_template_id))
+        {
+            let template_id = &exercise.exercise_template_id;
+            let Some(planned) = routine
+                .exercises
+                .iter()
+                .find(|planned| &planned.exercise_template_id == template_id)
+                .and_then(|planned| planned.rest_seconds)
+            else {
+                continue;
+            };
+
+            let baseline = match (current_phase, baselines.get(template_id)) {
+                (BlockPhase::Build, _) => planned,
+                (_, Some(baseline)) => *baseline,
+                (phase, None) => round_rest(planned as f32 / self.factor(phase)),
+            };
+            // Build weeks get the baseline exactly as the lifter set it
+            let rest = match next_phase {
+                BlockPhase::Build => baseline,
+                phase => round_rest(baseline as f32 * self.factor(phase)),
+            };
+
+            exercise.rest_seconds = Some(rest);
+            used_baselines.insert(template_id.clone(), baseline);
+            if rest != planned {
+                adjustments.push(RestAdjustment {
+                    exercise_template_id: template_id.clone(),
+                    from_seconds: planned,
+                    to_seconds: rest,
+                });
+            }
+        }
+
+        (adjustments, used_baselines)
+    }
+}
+
+/// Rounds to the rest timer's step, never below one step.
+fn round_rest(seconds: f32) -> u32 {
+    ((seconds / REST_ROUNDING_SECONDS).round() * REST_ROUNDING_SECONDS).max(REST_ROUNDING_SECONDS)
+        as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy;
+
+    fn exercise(template_id: &str, rest_seconds: Option<u32>) -> Exercise {
+        Exercise {
+            rest_seconds,
+            ..mock_hevy::exercise(template_id, template_id, Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_rest_follows_the_block_and_comes_back_round() {
+        let rest_periods = RestPeriods {
+            heavy_increase: 0.10,
+            deload_decrease: 0.25,
+        };
+        let mut routine = mock_hevy::routine(
+            "r1",
+            "Day 1",
+            vec![exercise("squat", Some(100)), exercise("curl", None)],
+        );
+
+        // Recorded baselines come back with the next workout
+        let mut rest = Vec::new();
+        let mut baselines = HashMap::new();
+        for (current, next) in [(4, 5), (5, 6), (7, 8), (8, 1)] {
+            let mut exercises = vec![exercise("squat", None), exercise("curl", Some(60))];
+            let (_, used) = rest_periods.apply(
+                current,
+                next,
+                &routine,
+                &baselines,
+                &mut exercises,
+                &HashSet::new(),
+            );
+            assert_eq!(exercises[1].rest_seconds, Some(60));
+            routine.exercises[0].rest_seconds = exercises[0].rest_seconds;
+            baselines = used;
+            rest.push(exercises[0].rest_seconds.unwrap());
+        }
+        assert_eq!(rest, [105, 105, 75, 100]);
+
+        // Without a recorded baseline it's worked back from the routine
+        let mut exercises = vec![exercise("squat", None)];
+        routine.exercises[0].rest_seconds = Some(105);
+        let (_, used) = rest_periods.apply(
+            6,
+            7,
+            &routine,
+            &HashMap::new(),
+            &mut exercises,
+            &HashSet::new(),
+        );
+        assert_eq!(used["squat"], 90);
+
+        // A short rest cut for the deload still leaves a step on the timer
+        let deep_cut = RestPeriods {
+            heavy_increase: 0.0,
+            deload_decrease: 0.9,
+        };
+        routine.exercises[0].rest_seconds = Some(30);
+        let mut exercises = vec![exercise("squat", None)];
+        deep_cut.apply(
+            7,
+            8,
+            &routine,
+            &HashMap::new(),
+            &mut exercises,
+            &HashSet::new(),
+        );
+        assert_eq!(exercises[0].rest_seconds, Some(15));
+
+        let mut exercises = vec![exercise("squat", None)];
+        let skip = HashSet::from(["squat".to_string()]);
+        let (adjustments, used) =
+            rest_periods.apply(4, 5, &routine, &HashMap::new(), &mut exercises, &skip);
+        assert!(adjustments.is_empty() && used.is_empty());
+        assert_eq!(exercises[0].rest_seconds, None);
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    responses::{RoutineResponse, WorkoutResponse},
+};
+use crate::services::ai_parser;
+use crate::services::progressive_overload::ProgressiveOverloadResponse;
+
+/// Bump whenever the rules change. Recorded as the audit `model`, e.g.
+/// `rules-v2`, so prescriptions from older rules can be told apart.
+pub const RULES_VERSION: &str = "2";
+
+/// Deterministic progression: add weight to every working set whose target
+/// reps were hit and hold it otherwise, drop to the deload going into week 8,
+/// and start the next block from the working weight before the deload.
+#[derive(Debug, Clone)]
+pub struct RuleBasedEngine {
+    pub increment_kg: f32,
+    /// Fraction of the last working weight used for the week 8 deload.
+    pub deload_factor: f32,
+    /// Fraction of the week 7 working weight used for week 1 of the next
+    /// block.
+    pub new_block_factor: f32,
+}
+
+impl Default for RuleBasedEngine {
+    fn default() -> Self {
+        Self {
+            increment_kg: 2.5,
+            deload_factor: 0.60,
+            // Week 1 works at 75% and week 7 at 90%
+            new_block_factor: 0.75 / 0.90,
+        }
+    }
+}
+
+/// What the next week is relative to the one just performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transition {
+    Progress,
+    Deload,
+    NewBlock,
+}
+
+impl RuleBasedEngine {
+    /// `pre_deload` is the week 7 workout of the same routine, used to start
+    /// a new block once week 8 is done. Without one, week 1 keeps the week 8
+    /// loads.
+    pub fn prescribe(
+        &self,
+        workout: &WorkoutResponse,
+        routine: &RoutineResponse,
+        pre_deload: Option<&WorkoutResponse>,
+    ) -> ProgressiveOverloadResponse {
+        let (current_week, _) = ai_parser::extract_week_and_day(&workout.title);
+        let transition = match current_week {
+            8.. => Transition::NewBlock,
+            7 => Transition::Deload,
+            _ => Transition::Progress,
+        };
+
+        let updated_exercises = workout
+            .exercises
+            .iter()
+            .map(|performed| {
+                let template_id = &performed.exercise_template_id;
+                let planned = routine
+                    .exercises
+                    .iter()
+                    .find(|exercise| &exercise.exercise_template_id == template_id);
+                let reference_kg = pre_deload
+                    .filter(|_| transition == Transition::NewBlock)
+                    .and_then(|workout| {
+                        workout
+                            .exercises
+                            .iter()
+                            .filter(|exercise| &exercise.exercise_template_id == template_id)
+                            .flat_map(|exercise| exercise.working_sets())
+                            .filter_map(|set| set.weight_kg)
+                            .reduce(f32::max)
+                    });
+                self.progress_exercise(performed, planned, transition, reference_kg)
+            })
+            .collect();
+
+        ProgressiveOverloadResponse {
+            updated_exercises,
+            week_number: if transition == Transition::NewBlock {
+                1
+            } else {
+                current_week + 1
+            },
+            routine_title: ai_parser::determine_routine_title_format(&workout.title),
+            stall_adjustments: Vec::new(),
+            bodyweight_exercises: HashMap::new(),
+            rest_adjustments: Vec::new(),
+            parse_warnings: Vec::new(),
+        }
+    }
+
+    fn progress_exercise(
+        &self,
+        performed: &Exercise,
+        planned: Option<&Exercise>,
+        transition: Transition,
+        reference_kg: Option<f32>,
+    ) -> Exercise {
+        let planned_working_sets: Vec<&ExerciseSet> = planned
+            .map(|exercise| exercise.working_sets().collect())
+            .unwrap_or_default();
+
+        let mut working_index = 0;
+        let sets = performed
+            .sets
+            .iter()
+            .map(|set| {
+                // Warmups are left exactly as performed
+                if set.is_warmup() {
+                    return set.clone();
+                }
+                let target = planned_working_sets.get(working_index).copied();
+                working_index += 1;
+                self.progress_set(set, target, transition, reference_kg)
+            })
+            .collect();
+
+        Exercise {
+            notes: planned.and_then(|exercise| exercise.notes.clone()),
+            sets,
+            ..performed.clone()
+        }
+    }
+
+    fn progress_set(
+        &self,
+        performed: &ExerciseSet,
+        target: Option<&ExerciseSet>,
+        transition: Transition,
+        reference_kg: Option<f32>,
+    ) -> ExerciseSet {
+        let target_reps = target.and_then(|set| set.reps).or(performed.reps);
+        let hit_target = match (performed.reps, target_reps) {
+            (Some(done), Some(target)) => done >= target,
+            _ => false,
+        };
+
+        let weight_kg = performed.weight_kg.map(|weight| match transition {
+            Transition::Deload => self.round_down(weight * self.deload_factor),
+            // Week 8 was already light, so it's only a fallback
+            Transition::NewBlock => reference_kg.map_or(weight, |reference| {
+                self.round_down(reference * self.new_block_factor)
+            }),
+            Transition::Progress if hit_target => weight + self.increment_kg,
+            Transition::Progress => weight,
+        });
+
+        // Bodyweight sets progress by a rep instead
+        let reps = match (performed.weight_kg, target_reps) {
+            (None, Some(reps)) if hit_target && transition == Transition::Progress => {
+                Some(reps + 1)
+            }
+            (_, reps) => reps,
+        };
+
+        ExerciseSet {
+            weight_kg,
+            reps,
+            rpe: None,
+            ..performed.clone()
+        }
+    }
+
+    fn round_down(&self, weight: f32) -> f32 {
+        (weight / self.increment_kg).floor() * self.increment_kg
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, routine, set, workout};
+
+    #[test]
+    fn test_adds_weight_only_when_target_reps_hit() {
+        let performed = workout(
+            "w1",
+            "Day 1 - Week 2",
+            "r1",
+            vec![exercise(
+                "bench",
+                "Bench Press",
+                vec![
+                    set("warmup", Some(40.0), 10),
+                    set("normal", Some(80.0), 7),
+                    set("normal", Some(80.0), 5),
+                ],
+            )],
+        );
+        let planned = routine(
+            "r1",
+            "Day 1 - Week 2",
+            vec![exercise(
+                "bench",
+                "Bench Press",
+                vec![
+                    set("warmup", Some(40.0), 10),
+                    set("normal", Some(80.0), 7),
+                    set("normal", Some(80.0), 7),
+                ],
+            )],
+        );
+
+        let response = RuleBasedEngine::default().prescribe(&performed, &planned, None);
+        let sets = &response.updated_exercises[0].sets;
+
+        assert_eq!(response.routine_title, "Day 1 - Week 3");
+        assert_eq!(response.week_number, 3);
+        assert_eq!(sets[0].weight_kg, Some(40.0));
+        assert_eq!(sets[1].weight_kg, Some(82.5));
+        assert_eq!(sets[2].weight_kg, Some(80.0));
+        assert_eq!(sets[2].reps, Some(7));
+    }
+
+    #[test]
+    fn test_deloads_in_week_8_and_restarts_from_week_7() {
+        let engine = RuleBasedEngine::default();
+        let bench = |weight, reps| {
+            vec![exercise(
+                "bench",
+                "Bench Press",
+                vec![set("normal", Some(weight), reps)],
+            )]
+        };
+        let week_7 = workout("w1", "Day 1 - Week 7", "r1", bench(100.0, 3));
+        let planned = routine("r1", "Day 1 - Week 2", bench(100.0, 3));
+
+        let response = engine.prescribe(&week_7, &planned, None);
+        assert_eq!(response.routine_title, "Day 1 - Week 8");
+        assert_eq!(response.updated_exercises[0].sets[0].weight_kg, Some(60.0));
+
+        let week_8 = workout("w2", "Day 1 - Week 8", "r1", bench(60.0, 5));
+        let response = engine.prescribe(&week_8, &planned, Some(&week_7));
+        assert_eq!(response.routine_title, "Day 1 - Week 1");
+        assert_eq!(response.week_number, 1);
+        assert_eq!(response.updated_exercises[0].sets[0].weight_kg, Some(82.5));
+
+        // No week 7 to go on, so week 8's loads are held rather than cut again
+        let response = engine.prescribe(&week_8, &planned, None);
+        assert_eq!(response.updated_exercises[0].sets[0].weight_kg, Some(60.0));
+    }
+}
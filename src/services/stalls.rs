@@ -0,0 +1,464 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    responses::WorkoutResponse,
+};
+use crate::prescription_store::PrescriptionStore;
+
+const LOAD_ROUNDING_KG: f32 = 0.5;
+
+/// What to do once an exercise has missed its prescribed reps
+/// `StallPolicy::threshold` sessions in a row. Before that, every miss
+/// repeats the weight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StallResponse {
+    /// Keep prescribing the missed weight and reps.
+    Repeat,
+    /// Drop the missed weight by `StallPolicy::reduce_by`.
+    #[default]
+    Reduce,
+    /// Keep the weight but spread the volume over more, shorter sets.
+    ChangeReps,
+    /// Repeat the weight and flag the exercise for a coach to review.
+    Flag,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown stall response `{0}`, expected `repeat`, `reduce`, `change_reps` or `flag`")]
+pub struct UnknownStallResponse(String);
+
+impl StallResponse {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StallResponse::Repeat => "repeat",
+            StallResponse::Reduce => "reduce",
+            StallResponse::ChangeReps => "change_reps",
+            StallResponse::Flag => "flag",
+        }
+    }
+
+    /// Whether the miss count starts over after this response.
+    fn resets(&self) -> bool {
+        matches!(self, StallResponse::Reduce | StallResponse::ChangeReps)
+    }
+}
+
+impl fmt::Display for StallResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StallResponse {
+    type Err = UnknownStallResponse;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "repeat" | "hold" => Ok(StallResponse::Repeat),
+            "reduce" | "deload" => Ok(StallResponse::Reduce),
+            "change_reps" => Ok(StallResponse::ChangeReps),
+            "flag" => Ok(StallResponse::Flag),
+            _ => Err(UnknownStallResponse(value.to_string())),
+        }
+    }
+}
+
+/// One working set as written to Hevy.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrescribedSet {
+    pub weight_kg: Option<f32>,
+    pub reps: Option<u32>,
+}
+
+/// The working sets last written to a routine for one exercise.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prescription {
+    pub exercise_title: String,
+    pub sets: Vec<PrescribedSet>,
+    /// Build-week rest the written rest was scaled from, see
+    /// `RestPeriods::apply`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_rest_seconds: Option<u32>,
+}
+
+impl Prescription {
+    pub fn from_exercise(exercise: &Exercise) -> Self {
+        Self {
+            exercise_title: exercise.title.clone(),
+            sets: exercise
+                .working_sets()
+                .map(|set| PrescribedSet {
+                    weight_kg: set.weight_kg,
+                    reps: set.reps,
+                })
+                .collect(),
+            base_rest_seconds: None,
+        }
+    }
+
+    /// Whether any prescribed set was skipped or done for fewer reps.
+    /// Sets without a rep target can't be missed.
+    pub fn missed_by(&self, performed: &Exercise) -> bool {
+        let performed: Vec<_> = performed.working_sets().collect();
+        self.sets.iter().enumerate().any(|(index, prescribed)| {
+            let Some(target) = prescribed.reps else {
+                return false;
+            };
+            performed
+                .get(index)
+                .is_none_or(|set| set.reps.unwrap_or(0) < target)
+        })
+    }
+}
+
+/// A stall response applied to one exercise, kept with the response for the
+/// audit log and the exercise notes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StallAdjustment {
+    pub exercise_template_id: String,
+    pub exercise_title: String,
+    pub consecutive_misses: u32,
+    pub action: StallResponse,
+}
+
+/// Prescriptions and miss counts to record once the routine update they came
+/// from has been accepted by Hevy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrescriptionRecord {
+    pub routine_id: String,
+    pub workout_id: String,
+    pub exercises: HashMap<String, Prescription>,
+    /// Consecutive misses in this routine for every exercise compared
+    /// against its last prescription, keyed by exercise template id.
+    pub misses: HashMap<String, u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StallPolicy {
+    /// Consecutive misses before `response` replaces repeating the weight.
+    pub threshold: u32,
+    pub response: StallResponse,
+    /// Fraction taken off the weight by `StallResponse::Reduce` (0.10 = 10%).
+    pub reduce_by: f32,
+}
+
+impl Default for StallPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: 3,
+            response: StallResponse::default(),
+            reduce_by: 0.10,
+        }
+    }
+}
+
+/// Compares each workout against the prescription last written to its
+/// routine and overrides the engine for exercises whose reps were missed.
+#[derive(Clone)]
+pub struct StallDetector {
+    pub policy: StallPolicy,
+    pub prescriptions: PrescriptionStore,
+}
+
+impl StallDetector {
+    /// Replaces the sets of every exercise in `exercises` that missed its
+    /// last prescription, skipping template ids in `skip`. Returns the
+    /// adjustments made and the misses to record.
+    pub fn apply(
+        &self,
+        workout: &WorkoutResponse,
+        exercises: &mut [Exercise],
+        skip: &HashSet<String>,
+    ) -> (Vec<StallAdjustment>, HashMap<String, u32>) {
+        let previous = self.prescriptions.prescriptions_for(&workout.routine_id);
+        let misses = self.prescriptions.misses(&workout.routine_id);
+        let mut adjustments = Vec::new();
+        let mut updated_misses = HashMap::new();
+
+        for exercise in exercises.iter_mut() {
+            let template_id = &exercise.exercise_template_id;
+            if skip.contains(template_id) {
+                continue;
+            }
+            let Some(prescription) = previous.get(template_id) else {
+                continue;
+            };
+            let Some(performed) = workout
+                .exercises
+                .iter()
+                .find(|performed| &performed.exercise_template_id == template_id)
+            else {
+                continue;
+            };
+
+            if !prescription.missed_by(performed) {
+                updated_misses.insert(template_id.clone(), 0);
+                continue;
+            }
+
+            let consecutive_misses = misses.get(template_id).copied().unwrap_or(0) + 1;
+            let action = if consecutive_misses >= self.policy.threshold.max(1) {
+                self.policy.response
+            } else {
+                StallResponse::Repeat
+            };
+            exercise.sets = self.respond(&exercise.sets, prescription, action);
+
+            updated_misses.insert(
+                template_id.clone(),
+                if action.resets() {
+                    0
+                } else {
+                    consecutive_misses
+                },
+            );
+            adjustments.push(StallAdjustment {
+                exercise_template_id: template_id.clone(),
+                exercise_title: exercise.title.clone(),
+                consecutive_misses,
+                action,
+            });
+        }
+
+        (adjustments, updated_misses)
+    }
+
+    /// The warmups in `sets` followed by working sets for `action`, built
+    /// from the missed prescription.
+    fn respond(
+        &self,
+        sets: &[ExerciseSet],
+        prescription: &Prescription,
+        action: StallResponse,
+    ) -> Vec<ExerciseSet> {
+        let targets: Vec<PrescribedSet> = match action {
+            StallResponse::Repeat | StallResponse::Flag => prescription.sets.clone(),
+            StallResponse::Reduce => prescription
+                .sets
+                .iter()
+                .map(|set| PrescribedSet {
+                    weight_kg: set
+                        .weight_kg
+                        .map(|weight| round_down(weight * (1.0 - self.policy.reduce_by))),
+                    reps: set.reps,
+                })
+                .collect(),
+            StallResponse::ChangeReps => change_rep_scheme(&prescription.sets),
+        };
+
+        let template = sets.iter().find(|set| !set.is_warmup()).cloned();
+        let warmups = sets.iter().filter(|set| set.is_warmup()).cloned();
+        let working = targets.into_iter().map(|target| ExerciseSet {
+            weight_kg: target.weight_kg,
+            reps: target.reps,
+            rpe: None,
+            rep_range: None,
+            ..template.clone().unwrap_or_else(|| ExerciseSet {
+                index: 0,
+                set_type: "normal".to_string(),
+                weight_kg: None,
+                reps: None,
+                distance_meters: None,
+                duration_seconds: None,
+                rpe: None,
+                custom_metric: None,
+                rep_range: None,
+            })
+        });
+
+        warmups
+            .chain(working)
+            .enumerate()
+            .map(|(index, set)| ExerciseSet {
+                index: index as u32,
+                ..set
+            })
+            .collect()
+    }
+}
+
+/// The same weight and total reps over sets of two thirds the reps, e.g.
+/// 3x5 becomes 5x3 and 3x10 becomes 5x6.
+fn change_rep_scheme(sets: &[PrescribedSet]) -> Vec<PrescribedSet> {
+    let Some(reps) = sets.iter().filter_map(|set| set.reps).max() else {
+        return sets.to_vec();
+    };
+    let total: u32 = sets.iter().filter_map(|set| set.reps).sum();
+    let new_reps = (reps * 2 / 3).max(1);
+    let weight_kg = sets.iter().filter_map(|set| set.weight_kg).reduce(f32::max);
+
+    vec![
+        PrescribedSet {
+            weight_kg,
+            reps: Some(new_reps),
+        };
+        total.div_ceil(new_reps) as usize
+    ]
+}
+
+fn round_down(weight: f32) -> f32 {
+    (weight / LOAD_ROUNDING_KG).floor() * LOAD_ROUNDING_KG
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, set, workout};
+
+    fn squat(sets: Vec<ExerciseSet>) -> Exercise {
+        exercise("squat", "Squat (Barbell)", sets)
+    }
+
+    fn detector(response: StallResponse) -> StallDetector {
+        let path =
+            std::env::temp_dir().join(format!("stalls-{}-{}.json", std::process::id(), response));
+        let _ = std::fs::remove_file(&path);
+        StallDetector {
+            policy: StallPolicy {
+                threshold: 2,
+                response,
+                reduce_by: 0.10,
+            },
+            prescriptions: PrescriptionStore::open(&path).unwrap(),
+        }
+    }
+
+    /// Records 100x5 x3 as the last prescription, then runs a workout where
+    /// the last set got `last_reps` with the engine asking for 105kg.
+    fn run(detector: &StallDetector, last_reps: u32) -> (Vec<ExerciseSet>, Vec<StallAdjustment>) {
+        let prescribed = squat(vec![set("normal", Some(100.0), 5); 3]);
+        let performed = workout(
+            "w1",
+            "Day 1 - Week 2",
+            "r1",
+            vec![squat(vec![
+                set("warmup", Some(60.0), 5),
+                set("normal", Some(100.0), 5),
+                set("normal", Some(100.0), 5),
+                set("normal", Some(100.0), last_reps),
+            ])],
+        );
+        let mut exercises = vec![squat(vec![
+            set("warmup", Some(60.0), 5),
+            set("normal", Some(105.0), 5),
+            set("normal", Some(105.0), 5),
+        ])];
+
+        let (adjustments, misses) = detector.apply(&performed, &mut exercises, &HashSet::new());
+        detector
+            .prescriptions
+            .record(&PrescriptionRecord {
+                routine_id: "r1".to_string(),
+                workout_id: "w1".to_string(),
+                exercises: HashMap::from([(
+                    "squat".to_string(),
+                    Prescription::from_exercise(&prescribed),
+                )]),
+                misses,
+            })
+            .unwrap();
+        (exercises.remove(0).sets, adjustments)
+    }
+
+    fn describe(sets: &[ExerciseSet]) -> Vec<String> {
+        sets.iter()
+            .map(|set| {
+                format!(
+                    "{}{}x{}",
+                    if set.is_warmup() { "w" } else { "" },
+                    set.weight_kg.unwrap_or(0.0),
+                    set.reps.unwrap_or(0)
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_misses_repeat_then_reduce_and_reset() {
+        let detector = detector(StallResponse::Reduce);
+
+        // Nothing prescribed yet, so the engine's sets stand
+        let (sets, adjustments) = run(&detector, 3);
+        assert!(adjustments.is_empty());
+        assert_eq!(describe(&sets), ["w60x5", "105x5", "105x5"]);
+
+        let (sets, adjustments) = run(&detector, 3);
+        assert_eq!(adjustments[0].action, StallResponse::Repeat);
+        assert_eq!(describe(&sets), ["w60x5", "100x5", "100x5", "100x5"]);
+
+        let (sets, adjustments) = run(&detector, 4);
+        assert_eq!(adjustments[0].consecutive_misses, 2);
+        assert_eq!(adjustments[0].action, StallResponse::Reduce);
+        assert_eq!(describe(&sets), ["w60x5", "90x5", "90x5", "90x5"]);
+        assert!(!detector.prescriptions.misses("r1").contains_key("squat"));
+
+        let (_, adjustments) = run(&detector, 5);
+        assert!(adjustments.is_empty());
+    }
+
+    #[test]
+    fn test_change_reps_and_flag() {
+        let detector_change = detector(StallResponse::ChangeReps);
+        run(&detector_change, 2);
+        run(&detector_change, 2);
+        let (sets, _) = run(&detector_change, 2);
+        assert_eq!(
+            describe(&sets),
+            ["w60x5", "100x3", "100x3", "100x3", "100x3", "100x3"]
+        );
+
+        let detector_flag = detector(StallResponse::Flag);
+        run(&detector_flag, 2);
+        run(&detector_flag, 2);
+        let (sets, adjustments) = run(&detector_flag, 2);
+        assert_eq!(adjustments[0].action, StallResponse::Flag);
+        assert_eq!(describe(&sets), ["w60x5", "100x5", "100x5", "100x5"]);
+        assert_eq!(detector_flag.prescriptions.misses("r1")["squat"], 2);
+    }
+
+    #[test]
+    fn test_misses_are_counted_per_routine() {
+        let detector = detector(StallResponse::Repeat);
+        run(&detector, 3);
+        run(&detector, 3);
+        assert_eq!(detector.prescriptions.misses("r1")["squat"], 1);
+
+        // The same exercise missed in another routine starts its own count
+        detector
+            .prescriptions
+            .record(&PrescriptionRecord {
+                routine_id: "r2".to_string(),
+                workout_id: "w0".to_string(),
+                exercises: HashMap::from([(
+                    "squat".to_string(),
+                    Prescription::from_exercise(&squat(vec![set("normal", Some(100.0), 5)])),
+                )]),
+                misses: HashMap::new(),
+            })
+            .unwrap();
+        let performed = workout(
+            "w1",
+            "Day 1 - Week 2",
+            "r2",
+            vec![squat(vec![set("normal", Some(100.0), 3)])],
+        );
+        let mut exercises = vec![squat(vec![set("normal", Some(105.0), 5)])];
+        let (adjustments, misses) = detector.apply(&performed, &mut exercises, &HashSet::new());
+        assert_eq!(adjustments[0].consecutive_misses, 1);
+        assert_eq!(misses["squat"], 1);
+    }
+
+    #[test]
+    fn test_skipped_set_is_a_miss() {
+        let prescription =
+            Prescription::from_exercise(&squat(vec![set("normal", Some(100.0), 5); 3]));
+        assert!(prescription.missed_by(&squat(vec![set("normal", Some(100.0), 5); 2])));
+        assert!(!prescription.missed_by(&squat(vec![set("normal", Some(100.0), 6); 3])));
+    }
+}
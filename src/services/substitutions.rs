@@ -0,0 +1,318 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::clients::models::{
+    common::Exercise,
+    responses::{RoutineResponse, WorkoutResponse},
+};
+
+/// What to do with the routine when a workout swapped one of its exercises
+/// for another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SubstitutionPolicy {
+    /// Leave the routine exercise alone; the substitute's suggestion is
+    /// dropped.
+    #[default]
+    Keep,
+    /// Replace the routine exercise with the substitute and its suggestion.
+    Adopt,
+    /// Keep the routine exercise but give it the substitute's suggestion,
+    /// when the two are listed as equivalent. Other swaps are kept.
+    CarryOver,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown substitution policy `{0}`, expected `keep`, `adopt` or `carry_over`")]
+pub struct UnknownSubstitutionPolicy(String);
+
+impl SubstitutionPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubstitutionPolicy::Keep => "keep",
+            SubstitutionPolicy::Adopt => "adopt",
+            SubstitutionPolicy::CarryOver => "carry_over",
+        }
+    }
+}
+
+impl fmt::Display for SubstitutionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for SubstitutionPolicy {
+    type Err = UnknownSubstitutionPolicy;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "keep" => Ok(SubstitutionPolicy::Keep),
+            "adopt" => Ok(SubstitutionPolicy::Adopt),
+            "carry_over" | "carry" => Ok(SubstitutionPolicy::CarryOver),
+            _ => Err(UnknownSubstitutionPolicy(value.to_string())),
+        }
+    }
+}
+
+/// Groups of exercise template ids that can stand in for each other, e.g.
+/// barbell and smith machine bench. Parsed from `a=b;c=d=e`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExerciseEquivalences(Vec<Vec<String>>);
+
+#[derive(Debug, Error)]
+#[error("invalid exercise equivalences `{0}`, expected e.g. `id_a=id_b;id_c=id_d`")]
+pub struct InvalidEquivalences(String);
+
+impl ExerciseEquivalences {
+    pub fn are_equivalent(&self, a: &str, b: &str) -> bool {
+        a == b
+            || self
+                .0
+                .iter()
+                .any(|group| group.iter().any(|id| id == a) && group.iter().any(|id| id == b))
+    }
+}
+
+impl FromStr for ExerciseEquivalences {
+    type Err = InvalidEquivalences;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let groups: Vec<Vec<String>> = value
+            .split(';')
+            .map(str::trim)
+            .filter(|group| !group.is_empty())
+            .map(|group| {
+                group
+                    .split('=')
+                    .map(|id| id.trim().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        if groups
+            .iter()
+            .any(|group| group.len() < 2 || group.iter().any(String::is_empty))
+        {
+            return Err(InvalidEquivalences(value.to_string()));
+        }
+        Ok(Self(groups))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubstitutionAction {
+    Kept,
+    Adopted,
+    CarriedOver,
+}
+
+impl SubstitutionAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubstitutionAction::Kept => "kept",
+            SubstitutionAction::Adopted => "adopted",
+            SubstitutionAction::CarriedOver => "carried_over",
+        }
+    }
+}
+
+/// A routine exercise that was replaced by another one in the workout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Substitution {
+    pub routine_template_id: String,
+    pub workout_template_id: String,
+    pub workout_title: String,
+    pub equivalent: bool,
+    pub action: SubstitutionAction,
+}
+
+/// Differences between a workout and the routine it was started from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutineMismatch {
+    /// Performed exercises that aren't in the routine and didn't replace
+    /// anything.
+    pub added: Vec<String>,
+    /// Routine exercises that weren't performed or replaced.
+    pub removed: Vec<String>,
+    pub substituted: Vec<Substitution>,
+}
+
+impl RoutineMismatch {
+    pub fn substitution_for(&self, routine_template_id: &str) -> Option<&Substitution> {
+        self.substituted
+            .iter()
+            .find(|substitution| substitution.routine_template_id == routine_template_id)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Substitutions {
+    pub policy: SubstitutionPolicy,
+    pub equivalences: ExerciseEquivalences,
+}
+
+impl Substitutions {
+    /// Finds added, removed and substituted exercises. A routine exercise
+    /// that wasn't performed is paired with an equivalent performed exercise
+    /// first. Otherwise it's only paired with an unmatched performed
+    /// exercise in the same superset or, failing that, the same position.
+    pub fn detect(&self, routine: &RoutineResponse, workout: &WorkoutResponse) -> RoutineMismatch {
+        let in_workout = |template_id: &str| {
+            workout
+                .exercises
+                .iter()
+                .any(|performed| performed.exercise_template_id == template_id)
+        };
+        let missing: Vec<(usize, &Exercise)> = routine
+            .exercises
+            .iter()
+            .enumerate()
+            .filter(|(_, planned)| !in_workout(&planned.exercise_template_id))
+            .collect();
+        let mut extra: Vec<(usize, &Exercise)> = workout
+            .exercises
+            .iter()
+            .enumerate()
+            .filter(|(_, performed)| {
+                !routine
+                    .exercises
+                    .iter()
+                    .any(|planned| planned.exercise_template_id == performed.exercise_template_id)
+            })
+            .collect();
+
+        let mut pairs = Vec::new();
+        let mut unpaired = Vec::new();
+        for (position, planned) in missing {
+            let routine_template_id = planned.exercise_template_id.as_str();
+            match extra.iter().position(|(_, performed)| {
+                self.equivalences
+                    .are_equivalent(routine_template_id, &performed.exercise_template_id)
+            }) {
+                Some(index) => pairs.push((routine_template_id, extra.remove(index).1, true)),
+                None => unpaired.push((position, planned)),
+            }
+        }
+
+        let mut removed = Vec::new();
+        for (position, planned) in unpaired {
+            let same_superset = extra.iter().position(|(_, performed)| {
+                planned.superset_id.is_some() && performed.superset_id == planned.superset_id
+            });
+            let same_position = || {
+                extra
+                    .iter()
+                    .position(|(performed_position, _)| *performed_position == position)
+            };
+            match same_superset.or_else(same_position) {
+                Some(index) => pairs.push((
+                    planned.exercise_template_id.as_str(),
+                    extra.remove(index).1,
+                    false,
+                )),
+                None => removed.push(planned.exercise_template_id.clone()),
+            }
+        }
+
+        RoutineMismatch {
+            added: extra
+                .iter()
+                .map(|(_, performed)| performed.exercise_template_id.clone())
+                .collect(),
+            removed,
+            substituted: pairs
+                .into_iter()
+                .map(
+                    |(routine_template_id, performed, equivalent)| Substitution {
+                        routine_template_id: routine_template_id.to_string(),
+                        workout_template_id: performed.exercise_template_id.clone(),
+                        workout_title: performed.title.clone(),
+                        equivalent,
+                        action: match self.policy {
+                            SubstitutionPolicy::Adopt => SubstitutionAction::Adopted,
+                            SubstitutionPolicy::CarryOver if equivalent => {
+                                SubstitutionAction::CarriedOver
+                            }
+                            _ => SubstitutionAction::Kept,
+                        },
+                    },
+                )
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy;
+
+    fn exercise(template_id: &str) -> Exercise {
+        mock_hevy::exercise(template_id, template_id, Vec::new())
+    }
+
+    fn in_superset(template_id: &str, superset_id: u32) -> Exercise {
+        Exercise {
+            superset_id: Some(superset_id),
+            ..exercise(template_id)
+        }
+    }
+
+    #[test]
+    fn test_detects_equivalent_swaps_before_positional_ones() {
+        let routine = mock_hevy::routine(
+            "r1",
+            "Day 1",
+            vec![
+                exercise("bench"),
+                exercise("row"),
+                in_superset("curl", 1),
+                exercise("dips"),
+            ],
+        );
+        let workout = mock_hevy::workout(
+            "w1",
+            "Day 1",
+            "r1",
+            vec![
+                exercise("cable_row"),
+                exercise("smith_bench"),
+                exercise("lunge"),
+                exercise("ring_dips"),
+                in_superset("hammer_curl", 1),
+            ],
+        );
+        let substitutions = Substitutions {
+            policy: SubstitutionPolicy::CarryOver,
+            equivalences: "bench=smith_bench; squat=hack_squat=leg_press"
+                .parse()
+                .unwrap(),
+        };
+
+        let mismatch = substitutions.detect(&routine, &workout);
+
+        let bench = mismatch.substitution_for("bench").unwrap();
+        assert_eq!(bench.workout_template_id, "smith_bench");
+        assert_eq!(bench.action, SubstitutionAction::CarriedOver);
+        let curl = mismatch.substitution_for("curl").unwrap();
+        assert_eq!(curl.workout_template_id, "hammer_curl");
+        let dips = mismatch.substitution_for("dips").unwrap();
+        assert_eq!(dips.workout_template_id, "ring_dips");
+        assert!(!dips.equivalent);
+        assert_eq!(dips.action, SubstitutionAction::Kept);
+        // Done somewhere else in the workout, so not a swap for the row
+        assert_eq!(mismatch.removed, ["row"]);
+        assert_eq!(mismatch.added, ["cable_row", "lunge"]);
+
+        assert!("bench".parse::<ExerciseEquivalences>().is_err());
+        assert!(
+            "squat=hack_squat=leg_press"
+                .parse::<ExerciseEquivalences>()
+                .unwrap()
+                .are_equivalent("leg_press", "squat")
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::clients::models::common::{Exercise, ExerciseForUpdate};
+
+/// What a routine update writes for each exercise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UpdateMode {
+    /// The prescription goes into the exercise notes and the sets only get
+    /// rep ranges, so Hevy pre-fills last week's numbers.
+    #[default]
+    Notes,
+    /// The routine sets are rewritten from the prescription and the notes
+    /// are left alone.
+    Sets,
+    /// Both notes and sets.
+    Both,
+}
+
+#[derive(Debug, Error)]
+#[error("unknown update mode `{0}`, expected `notes`, `sets` or `both`")]
+pub struct UnknownUpdateMode(String);
+
+impl UpdateMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateMode::Notes => "notes",
+            UpdateMode::Sets => "sets",
+            UpdateMode::Both => "both",
+        }
+    }
+
+    pub fn writes_notes(&self) -> bool {
+        matches!(self, UpdateMode::Notes | UpdateMode::Both)
+    }
+
+    pub fn writes_sets(&self) -> bool {
+        matches!(self, UpdateMode::Sets | UpdateMode::Both)
+    }
+}
+
+impl fmt::Display for UpdateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for UpdateMode {
+    type Err = UnknownUpdateMode;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "notes" => Ok(UpdateMode::Notes),
+            "sets" => Ok(UpdateMode::Sets),
+            "both" => Ok(UpdateMode::Both),
+            _ => Err(UnknownUpdateMode(value.to_string())),
+        }
+    }
+}
+
+/// Replaces the routine exercise's working sets with the prescribed ones.
+/// The routine's own warmups are kept as they are, ahead of the working
+/// sets, and any warmup in the prescription is ignored. A prescription
+/// without working sets leaves the exercise alone.
+pub fn write_sets(exercise: &mut ExerciseForUpdate, prescribed: &Exercise) {
+    let working: Vec<_> = prescribed
+        .sets
+        .iter()
+        .filter(|set| !set.is_warmup())
+        .map(|set| set.to_update_format())
+        .collect();
+    if working.is_empty() {
+        return;
+    }
+
+    exercise.sets.retain(|set| set.is_warmup());
+    exercise.sets.extend(working);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::models::common::{ExerciseSet, RepRange};
+    use crate::testing::mock_hevy::{exercise, set};
+
+    fn bench(sets: Vec<ExerciseSet>) -> Exercise {
+        exercise("bench", "Bench Press (Barbell)", sets)
+    }
+
+    #[test]
+    fn test_write_sets_keeps_routine_warmups() {
+        let mut routine = bench(vec![
+            set("warmup", Some(40.0), 10),
+            set("normal", Some(80.0), 7),
+            set("normal", Some(80.0), 7),
+        ])
+        .to_update_format();
+        let mut prescribed = bench(vec![
+            set("warmup", Some(50.0), 8),
+            set("normal", Some(82.5), 6),
+            set("normal", Some(82.5), 6),
+            set("failure", Some(82.5), 6),
+        ]);
+        prescribed.sets[1].rep_range = Some(RepRange {
+            start: Some(5),
+            end: Some(7),
+        });
+
+        write_sets(&mut routine, &prescribed);
+        let sets: Vec<_> = routine
+            .sets
+            .iter()
+            .map(|set| (set.set_type.as_str(), set.weight_kg, set.reps))
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                ("warmup", Some(40.0), Some(10)),
+                ("normal", Some(82.5), Some(6)),
+                ("normal", Some(82.5), Some(6)),
+                ("failure", Some(82.5), Some(6)),
+            ]
+        );
+        assert_eq!(routine.sets[1].rep_range, prescribed.sets[1].rep_range);
+
+        write_sets(&mut routine, &bench(vec![set("warmup", Some(20.0), 5)]));
+        assert_eq!(routine.sets.len(), 4);
+
+        assert_eq!("Both".parse::<UpdateMode>().unwrap(), UpdateMode::Both);
+        assert!("everything".parse::<UpdateMode>().is_err());
+    }
+}
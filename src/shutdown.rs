@@ -0,0 +1,65 @@
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// Resolves on the first SIGINT (Ctrl+C) or SIGTERM.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "shutdown.ctrl_c_handler_failed");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "shutdown.sigterm_handler_failed");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!(signal = "SIGINT", "shutdown.signal_received"),
+        _ = terminate => tracing::info!(signal = "SIGTERM", "shutdown.signal_received"),
+    }
+}
+
+/// Tells background tasks to stop taking new work and waits up to `timeout`
+/// for them to finish what they're doing. Returns `false` if the timeout hit
+/// and the remaining tasks were aborted.
+pub async fn drain(
+    shutdown_tx: &watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>,
+    timeout: Duration,
+) -> bool {
+    let _ = shutdown_tx.send(true);
+
+    let abort_handles: Vec<_> = handles.iter().map(|handle| handle.abort_handle()).collect();
+
+    let wait_all = async {
+        for handle in handles {
+            if let Err(e) = handle.await {
+                tracing::error!(error = %e, "shutdown.task_failed");
+            }
+        }
+    };
+
+    match tokio::time::timeout(timeout, wait_all).await {
+        Ok(()) => true,
+        Err(_) => {
+            for handle in abort_handles {
+                handle.abort();
+            }
+            false
+        }
+    }
+}
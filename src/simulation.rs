@@ -0,0 +1,338 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::clients::models::{
+    common::Exercise,
+    responses::{RoutineResponse, WorkoutResponse},
+};
+use crate::services::progressive_overload::{
+    ProgressiveOverloadRequest, ProgressiveOverloadResponse, ProgressiveOverloadService,
+};
+
+/// A recorded workout and the routine it was performed from, in the same
+/// shapes the Hevy API returns.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SimulationFixture {
+    pub workout: WorkoutResponse,
+    pub routine: RoutineResponse,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimulationReport {
+    pub engines: Vec<String>,
+    pub fixtures: Vec<FixtureReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FixtureReport {
+    pub name: String,
+    pub workout_title: String,
+    pub runs: Vec<EngineRun>,
+    pub exercises: Vec<ExerciseComparison>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EngineRun {
+    pub engine: String,
+    pub model: String,
+    pub next_routine_title: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExerciseComparison {
+    pub exercise_template_id: String,
+    pub title: String,
+    pub performed_top_weight_kg: Option<f32>,
+    pub prescriptions: Vec<EnginePrescription>,
+    /// Spread between the heaviest and lightest top working weight across
+    /// engines. `None` when fewer than two engines prescribed a weight.
+    pub divergence_kg: Option<f32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EnginePrescription {
+    pub engine: String,
+    /// Working sets as `weight x reps`, e.g. `82.5x7`.
+    pub sets: Vec<String>,
+    pub top_weight_kg: Option<f32>,
+    pub load_delta_kg: Option<f32>,
+    pub load_delta_pct: Option<f32>,
+}
+
+/// Reads one fixture file, or every `*.json` file in a directory (sorted by
+/// name). Fixtures are named after their file stem.
+pub fn load_fixtures(path: &Path) -> Result<Vec<(String, SimulationFixture)>> {
+    let files = if path.is_dir() {
+        let mut files: Vec<_> = fs::read_dir(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| file.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        files
+    } else {
+        vec![path.to_path_buf()]
+    };
+
+    files
+        .iter()
+        .map(|file| {
+            let contents = fs::read_to_string(file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let fixture = serde_json::from_str(&contents)
+                .with_context(|| format!("Failed to parse {}", file.display()))?;
+            let name = file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            Ok((name, fixture))
+        })
+        .collect()
+}
+
+/// Runs every fixture through every engine. Engine failures are recorded in
+/// the report rather than aborting the run.
+pub async fn run_simulation(
+    services: &[ProgressiveOverloadService],
+    fixtures: &[(String, SimulationFixture)],
+) -> SimulationReport {
+    let mut reports = Vec::new();
+
+    for (name, fixture) in fixtures {
+        let mut results = Vec::new();
+        for service in services {
+            let request = ProgressiveOverloadRequest {
+                current_workout: fixture.workout.clone(),
+                routine: fixture.routine.clone(),
+            };
+            let result = service
+                .process_workout_completion(request)
+                .await
+                .map(|run| run.response)
+                .map_err(|e| format!("{:#}", e));
+            results.push((service.engine().to_string(), service.model(), result));
+        }
+        reports.push(compare(name, &fixture.workout, results));
+    }
+
+    SimulationReport {
+        engines: services
+            .iter()
+            .map(|service| service.engine().to_string())
+            .collect(),
+        fixtures: reports,
+    }
+}
+
+fn compare(
+    name: &str,
+    workout: &WorkoutResponse,
+    results: Vec<(String, String, Result<ProgressiveOverloadResponse, String>)>,
+) -> FixtureReport {
+    let exercises = workout
+        .exercises
+        .iter()
+        .map(|performed| {
+            let performed_top = top_working_weight(performed);
+            let prescriptions: Vec<EnginePrescription> = results
+                .iter()
+                .filter_map(|(engine, _, result)| {
+                    let response = result.as_ref().ok()?;
+                    let prescribed = response.updated_exercises.iter().find(|exercise| {
+                        exercise.exercise_template_id == performed.exercise_template_id
+                    })?;
+                    let top = top_working_weight(prescribed);
+                    let delta = top.zip(performed_top).map(|(top, base)| top - base);
+                    Some(EnginePrescription {
+                        engine: engine.clone(),
+                        sets: format_working_sets(prescribed),
+                        top_weight_kg: top,
+                        load_delta_kg: delta,
+                        load_delta_pct: delta
+                            .zip(performed_top)
+                            .filter(|(_, base)| *base > 0.0)
+                            .map(|(delta, base)| delta / base * 100.0),
+                    })
+                })
+                .collect();
+
+            let tops: Vec<f32> = prescriptions
+                .iter()
+                .filter_map(|prescription| prescription.top_weight_kg)
+                .collect();
+            let divergence_kg = (tops.len() >= 2).then(|| {
+                tops.iter().cloned().fold(f32::MIN, f32::max)
+                    - tops.iter().cloned().fold(f32::MAX, f32::min)
+            });
+
+            ExerciseComparison {
+                exercise_template_id: performed.exercise_template_id.clone(),
+                title: performed.title.clone(),
+                performed_top_weight_kg: performed_top,
+                prescriptions,
+                divergence_kg,
+            }
+        })
+        .collect();
+
+    FixtureReport {
+        name: name.to_string(),
+        workout_title: workout.title.clone(),
+        runs: results
+            .into_iter()
+            .map(|(engine, model, result)| EngineRun {
+                engine,
+                model,
+                next_routine_title: result
+                    .as_ref()
+                    .ok()
+                    .map(|response| response.routine_title.clone()),
+                error: result.err(),
+            })
+            .collect(),
+        exercises,
+    }
+}
+
+/// Plain-text rendering of a report, one block per fixture.
+pub fn render_table(report: &SimulationReport) -> String {
+    let mut output = String::new();
+
+    for fixture in &report.fixtures {
+        output.push_str(&format!(
+            "== {} ({})\n",
+            fixture.name, fixture.workout_title
+        ));
+        for run in &fixture.runs {
+            match (&run.next_routine_title, &run.error) {
+                (_, Some(error)) => output.push_str(&format!(
+                    "  {} [{}]: failed: {}\n",
+                    run.engine, run.model, error
+                )),
+                (Some(title), None) => {
+                    output.push_str(&format!("  {} [{}]: -> {}\n", run.engine, run.model, title))
+                }
+                (None, None) => {}
+            }
+        }
+
+        for exercise in &fixture.exercises {
+            output.push_str(&format!(
+                "  {} (top {})",
+                exercise.title,
+                format_kg(exercise.performed_top_weight_kg)
+            ));
+            if let Some(divergence) = exercise.divergence_kg {
+                output.push_str(&format!(", divergence {:.1}kg", divergence));
+            }
+            output.push('\n');
+
+            for prescription in &exercise.prescriptions {
+                output.push_str(&format!(
+                    "    {:<12} {:<32} delta {:>7} {:>7}\n",
+                    prescription.engine,
+                    prescription.sets.join(", "),
+                    prescription
+                        .load_delta_kg
+                        .map_or_else(|| "-".to_string(), |delta| format!("{:+.1}kg", delta)),
+                    prescription
+                        .load_delta_pct
+                        .map_or_else(|| "-".to_string(), |pct| format!("{:+.1}%", pct)),
+                ));
+            }
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+fn top_working_weight(exercise: &Exercise) -> Option<f32> {
+    exercise
+        .working_sets()
+        .filter_map(|set| set.weight_kg)
+        .reduce(f32::max)
+}
+
+fn format_working_sets(exercise: &Exercise) -> Vec<String> {
+    exercise
+        .working_sets()
+        .map(|set| {
+            let reps = set
+                .reps
+                .map_or_else(|| "?".to_string(), |reps| reps.to_string());
+            match set.weight_kg {
+                Some(weight) => format!("{}x{}", weight, reps),
+                None => format!("{} reps", reps),
+            }
+        })
+        .collect()
+}
+
+fn format_kg(weight: Option<f32>) -> String {
+    weight.map_or_else(|| "-".to_string(), |weight| format!("{}kg", weight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::mock_hevy::{exercise, set, workout};
+    use std::collections::HashMap;
+
+    fn squat(weight_kg: f32) -> Exercise {
+        exercise("squat", "Squat", vec![set("normal", Some(weight_kg), 5)])
+    }
+
+    fn response(weight_kg: f32) -> ProgressiveOverloadResponse {
+        ProgressiveOverloadResponse {
+            updated_exercises: vec![squat(weight_kg)],
+            week_number: 3,
+            routine_title: "Day 1 - Week 3".to_string(),
+            stall_adjustments: Vec::new(),
+            bodyweight_exercises: HashMap::new(),
+            rest_adjustments: Vec::new(),
+            parse_warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compare_reports_deltas_and_divergence() {
+        let workout = workout("w1", "Day 1 - Week 2", "r1", vec![squat(100.0)]);
+
+        let report = compare(
+            "squat_day",
+            &workout,
+            vec![
+                ("gemini".to_string(), "m".to_string(), Ok(response(110.0))),
+                (
+                    "rule_based".to_string(),
+                    "r".to_string(),
+                    Ok(response(102.5)),
+                ),
+                (
+                    "other".to_string(),
+                    "o".to_string(),
+                    Err("timeout".to_string()),
+                ),
+            ],
+        );
+
+        let squat = &report.exercises[0];
+        assert_eq!(squat.prescriptions.len(), 2);
+        assert_eq!(squat.prescriptions[0].load_delta_kg, Some(10.0));
+        assert_eq!(squat.prescriptions[0].load_delta_pct, Some(10.0));
+        assert_eq!(squat.prescriptions[1].sets, ["102.5x5"]);
+        assert_eq!(squat.divergence_kg, Some(7.5));
+        assert_eq!(report.runs[2].error.as_deref(), Some("timeout"));
+        assert!(
+            render_table(&SimulationReport {
+                engines: Vec::new(),
+                fixtures: vec![report],
+            })
+            .contains("divergence 7.5kg")
+        );
+    }
+}
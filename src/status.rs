@@ -0,0 +1,155 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::store;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStatus {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub outcome: String,
+    pub workouts_queued: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutineStatus {
+    pub workout_id: String,
+    pub workout_title: String,
+    pub outcome: String,
+    pub processed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorStatus {
+    pub at: DateTime<Utc>,
+    pub workout_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct StatusData {
+    pub last_sync: Option<SyncStatus>,
+    pub last_processed_by_routine: HashMap<String, RoutineStatus>,
+    pub last_error: Option<ErrorStatus>,
+}
+
+/// Operational state shown on `/status`. Persisted so a restart doesn't
+/// blank the dashboard.
+#[derive(Clone)]
+pub struct ServiceStatus {
+    path: PathBuf,
+    started_at: DateTime<Utc>,
+    scheduler_running: Arc<Mutex<bool>>,
+    data: Arc<Mutex<StatusData>>,
+}
+
+impl ServiceStatus {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            started_at: Utc::now(),
+            scheduler_running: Arc::new(Mutex::new(false)),
+            data: Arc::new(Mutex::new(store::load_json(path)?)),
+        })
+    }
+
+    pub fn started_at(&self) -> DateTime<Utc> {
+        self.started_at
+    }
+
+    pub fn set_scheduler_running(&self, running: bool) {
+        *self.scheduler_running.lock().unwrap() = running;
+    }
+
+    pub fn scheduler_running(&self) -> bool {
+        *self.scheduler_running.lock().unwrap()
+    }
+
+    pub fn snapshot(&self) -> StatusData {
+        self.data.lock().unwrap().clone()
+    }
+
+    pub fn record_sync(&self, sync: SyncStatus) {
+        self.update(|data| {
+            if let Some(error) = &sync.error {
+                data.last_error = Some(ErrorStatus {
+                    at: sync.finished_at,
+                    workout_id: None,
+                    message: format!("sync failed: {}", error),
+                });
+            }
+            data.last_sync = Some(sync);
+        });
+    }
+
+    pub fn record_workout(
+        &self,
+        routine_id: &str,
+        workout_id: &str,
+        workout_title: &str,
+        outcome: &str,
+    ) {
+        self.update(|data| {
+            data.last_processed_by_routine.insert(
+                routine_id.to_string(),
+                RoutineStatus {
+                    workout_id: workout_id.to_string(),
+                    workout_title: workout_title.to_string(),
+                    outcome: outcome.to_string(),
+                    processed_at: Utc::now(),
+                },
+            );
+        });
+    }
+
+    pub fn record_error(&self, workout_id: Option<&str>, message: &str) {
+        self.update(|data| {
+            data.last_error = Some(ErrorStatus {
+                at: Utc::now(),
+                workout_id: workout_id.map(str::to_string),
+                message: message.to_string(),
+            });
+        });
+    }
+
+    fn update(&self, change: impl FnOnce(&mut StatusData)) {
+        let mut data = self.data.lock().unwrap();
+        change(&mut data);
+        if let Err(e) = store::save_json(&self.path, &*data) {
+            tracing::warn!(error = %e, "status.persist_failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_is_persisted_across_reopen() {
+        let path = std::env::temp_dir().join(format!("hevy-status-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let status = ServiceStatus::open(&path).unwrap();
+        status.record_workout("r1", "w1", "Day 1 - Week 2", "updated");
+        status.record_sync(SyncStatus {
+            started_at: Utc::now(),
+            finished_at: Utc::now(),
+            outcome: "error".to_string(),
+            workouts_queued: 0,
+            error: Some("timeout".to_string()),
+        });
+
+        let reopened = ServiceStatus::open(&path).unwrap().snapshot();
+        assert_eq!(reopened.last_processed_by_routine["r1"].workout_id, "w1");
+        assert_eq!(reopened.last_sync.unwrap().outcome, "error");
+        assert_eq!(reopened.last_error.unwrap().message, "sync failed: timeout");
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use serde::{Serialize, de::DeserializeOwned};
+use std::fs::{self, File, TryLockError};
+use std::path::Path;
+
+/// Reads a JSON document from `path`, returning `T::default()` when the file
+/// doesn't exist yet.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Writes `value` to `path` via a temporary file and rename so a crash mid-write
+/// never leaves a truncated document behind.
+pub fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let tmp_path = path.with_extension("json.tmp");
+    let contents = serde_json::to_vec_pretty(value)?;
+    fs::write(&tmp_path, contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+/// Verifies `dir` exists (creating it if needed) and accepts writes.
+pub fn check_writable(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let probe = dir.join(".write-probe");
+    fs::write(&probe, b"ok").with_context(|| format!("Failed to write {}", probe.display()))?;
+    fs::remove_file(&probe).with_context(|| format!("Failed to remove {}", probe.display()))?;
+
+    Ok(())
+}
+
+/// An exclusive lock on a data directory, held for as long as the value
+/// lives. Only one process that writes to the directory runs at a time.
+#[derive(Debug)]
+pub struct DataDirLock {
+    _file: File,
+}
+
+impl DataDirLock {
+    pub fn acquire(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(".lock");
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => Ok(Self { _file: file }),
+            Err(TryLockError::WouldBlock) => anyhow::bail!(
+                "{} is in use by another process, stop the server before running this command",
+                dir.display()
+            ),
+            Err(TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("Failed to lock {}", path.display()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dir_lock_is_exclusive() {
+        let dir = std::env::temp_dir().join(format!("hevy-lock-{}", std::process::id()));
+
+        let lock = DataDirLock::acquire(&dir).unwrap();
+        assert!(DataDirLock::acquire(&dir).is_err());
+        drop(lock);
+        assert!(DataDirLock::acquire(&dir).is_ok());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,86 @@
+use axum::http::HeaderMap;
+use opentelemetry::{Context, global, trace::TracerProvider};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use std::collections::HashMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::config::Config;
+
+/// Installs the log subscriber and, when an OTLP endpoint is configured, an
+/// OpenTelemetry layer exporting spans over OTLP/HTTP.
+pub fn init(config: &Config) -> anyhow::Result<Option<SdkTracerProvider>> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = if config.otel_enabled {
+        let exporter = SpanExporter::builder().with_http().build()?;
+        Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(
+                    Resource::builder()
+                        .with_service_name(config.otel_service_name.clone())
+                        .build(),
+                )
+                .build(),
+        )
+    } else {
+        None
+    };
+
+    let otel_layer = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("hevy-progressive-overloader"))
+    });
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(
+            // Logs go to stderr so CLI subcommands can print results on stdout
+            tracing_subscriber::fmt::layer()
+                .with_writer(std::io::stderr)
+                .with_target(false)
+                .compact(),
+        )
+        .with(otel_layer)
+        .init();
+
+    if provider.is_some() {
+        tracing::info!(service_name = %config.otel_service_name, "telemetry.otlp_enabled");
+    }
+
+    Ok(provider)
+}
+
+/// Flushes any buffered spans. Runs on a blocking thread because the exporter
+/// uses a blocking HTTP client.
+pub async fn shutdown(provider: Option<SdkTracerProvider>) {
+    let Some(provider) = provider else {
+        return;
+    };
+
+    let result = tokio::task::spawn_blocking(move || provider.shutdown()).await;
+    if let Ok(Err(e)) = result {
+        tracing::error!(error = %e, "telemetry.shutdown_failed");
+    }
+}
+
+/// Parent context from the W3C `traceparent`/`tracestate` headers of an
+/// incoming request.
+pub fn context_from_headers(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+/// Serializes a span's trace context so it can be stored alongside a job and
+/// picked up again by whichever worker runs it.
+pub fn span_trace_context(span: &tracing::Span) -> HashMap<String, String> {
+    let mut carrier = HashMap::new();
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| propagator.inject_context(&context, &mut carrier));
+    carrier
+}
+
+pub fn context_from_carrier(carrier: &HashMap<String, String>) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(carrier))
+}
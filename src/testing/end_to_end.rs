@@ -0,0 +1,691 @@
+use axum::http::{Method, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+use super::mock_hevy::{self, MockHevy};
+use super::{WEBHOOK_TOKEN, test_config};
+use crate::api::webhooks::{AppState, ProcessOutcome, process_single_workout};
+use crate::audit::AuditQuery;
+use crate::clients::models::common::{ExerciseSet, RepRange};
+use crate::jobs::worker::spawn_workers;
+use crate::services::notes;
+use crate::services::programs::{PROGRAMS_VERSION, Program};
+use crate::services::stalls::{Prescription, PrescriptionRecord};
+use crate::services::substitutions::SubstitutionPolicy;
+use crate::services::update_mode::UpdateMode;
+
+/// Serves the real router on a random local port, returning its base URL.
+fn progression_notes(section: &str) -> String {
+    notes::merge_section(None, section)
+}
+
+async fn serve_app(state: AppState) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, crate::router(state)).await.unwrap();
+    });
+    base_url
+}
+
+async fn wait_until(mut done: impl FnMut() -> bool) {
+    tokio::time::timeout(Duration::from_secs(10), async {
+        while !done() {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("condition not met within 10s");
+}
+
+async fn post_webhook(base_url: &str, workout_id: &str) -> reqwest::StatusCode {
+    reqwest::Client::new()
+        .post(format!("{}/webhook", base_url))
+        .bearer_auth(WEBHOOK_TOKEN)
+        .json(&serde_json::json!({ "id": "evt", "payload": { "workoutId": workout_id } }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_webhook_updates_routine_end_to_end() {
+    let hevy = MockHevy::seeded().await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+    let base_url = serve_app(state.clone()).await;
+
+    let state = Arc::new(state);
+    let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+    spawn_workers(Arc::clone(&state), shutdown_rx);
+
+    assert_eq!(post_webhook(&base_url, "w1").await, reqwest::StatusCode::OK);
+    wait_until(|| !hevy.data().routine_updates.is_empty()).await;
+
+    {
+        let data = hevy.data();
+        let routine = &data.routines["r1"];
+        assert_eq!(routine.title, "Day 1 - Week 3");
+
+        let bench = &routine.exercises[0];
+        assert_eq!(bench.sets[0].weight_kg, Some(40.0), "warmup left alone");
+        assert!(bench.notes.as_deref().unwrap().contains("82.5x7"));
+
+        // One squat set fell short, so only the other one moves up
+        let squat_notes = routine.exercises[1].notes.as_deref().unwrap();
+        assert!(squat_notes.contains("102.5x5") && squat_notes.contains("100x5"));
+    }
+
+    // The audit record is appended after Hevy replies, so it can trail the update
+    wait_until(|| {
+        !state
+            .audit_log
+            .query(&AuditQuery::default())
+            .unwrap()
+            .is_empty()
+    })
+    .await;
+    let audit = state.audit_log.query(&AuditQuery::default()).unwrap();
+    assert_eq!(audit.len(), 1);
+    assert_eq!(audit[0].engine, "rule_based");
+    assert!(audit[0].hevy_response.is_some());
+}
+
+#[tokio::test]
+async fn test_progression_notes_keep_the_lifters_own_notes() {
+    let mut data = mock_hevy::seed();
+    let routine = data.routines.get_mut("r1").unwrap();
+    routine.notes = Some("Belt on for squats".to_string());
+    routine.exercises[0].notes = Some(format!(
+        "elbows tucked\n\n{}\nuse blue band",
+        progression_notes("2 sets\n80x7\n80x7")
+    ));
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    let routine = &data.routines["r1"];
+    assert_eq!(
+        routine.notes.as_deref(),
+        Some("Belt on for squats\n\n--- progression ---\nWeek 3\n--- end progression ---")
+    );
+    assert_eq!(
+        routine.exercises[0].notes.as_deref(),
+        Some(
+            format!(
+                "elbows tucked\n\n{}\nuse blue band",
+                progression_notes("2 sets\n82.5x7\n82.5x7")
+            )
+            .as_str()
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_sets_mode_writes_the_prescription_into_routine_sets() {
+    let hevy = MockHevy::seeded().await;
+    let mut config = test_config(&hevy.base_url);
+    config.update_mode = UpdateMode::Sets;
+    let state = crate::build_state(&config).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    let bench = &data.routines["r1"].exercises[0];
+    let sets: Vec<_> = bench
+        .sets
+        .iter()
+        .map(|set| (set.set_type.as_str(), set.weight_kg, set.reps))
+        .collect();
+    assert_eq!(
+        sets,
+        [
+            ("warmup", Some(40.0), Some(10)),
+            ("normal", Some(82.5), Some(7)),
+            ("normal", Some(82.5), Some(7)),
+        ]
+    );
+    assert_eq!(bench.notes, None, "notes are left alone");
+}
+
+#[tokio::test]
+async fn test_rejects_webhook_without_token() {
+    let hevy = MockHevy::seeded().await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+    let base_url = serve_app(state.clone()).await;
+
+    let status = reqwest::Client::new()
+        .post(format!("{}/webhook", base_url))
+        .json(&serde_json::json!({ "payload": { "workoutId": "w1" } }))
+        .send()
+        .await
+        .unwrap()
+        .status();
+
+    assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(state.job_queue.depth().queued, 0);
+}
+
+#[tokio::test]
+async fn test_failed_update_is_retried_and_audited() {
+    let hevy = MockHevy::seeded().await;
+    hevy.fail_next(
+        Method::PUT,
+        "/v1/routines/r1",
+        StatusCode::INTERNAL_SERVER_ERROR,
+        1,
+    );
+    let state = Arc::new(crate::build_state(&test_config(&hevy.base_url)).unwrap());
+    let (_shutdown_tx, shutdown_rx) = watch::channel(false);
+    spawn_workers(Arc::clone(&state), shutdown_rx);
+
+    state
+        .job_queue
+        .enqueue("w1", crate::jobs::queue::JobSource::Webhook)
+        .unwrap();
+    wait_until(|| !hevy.data().routine_updates.is_empty()).await;
+
+    let put_attempts = hevy
+        .data()
+        .requests
+        .iter()
+        .filter(|request| request.as_str() == "PUT /v1/routines/r1")
+        .count();
+    assert_eq!(put_attempts, 2);
+
+    wait_until(|| state.audit_log.query(&AuditQuery::default()).unwrap().len() == 2).await;
+    let audit = state.audit_log.query(&AuditQuery::default()).unwrap();
+    assert_eq!(audit.len(), 2);
+    assert!(audit[0].error.is_none());
+    assert!(audit[1].error.as_deref().unwrap().contains("500"));
+}
+
+#[tokio::test]
+async fn test_processed_workout_is_not_applied_again_by_sync() {
+    let hevy = MockHevy::seeded().await;
+    let config = test_config(&hevy.base_url);
+
+    let state = crate::build_state(&config).unwrap();
+    crate::cli::process(state, "w1".to_string(), false)
+        .await
+        .unwrap();
+    assert_eq!(hevy.data().routine_updates.len(), 1);
+
+    // A later run starts from what's on disk, as the server or cron would
+    let state = crate::build_state(&config).unwrap();
+    crate::cli::sync(state).await.unwrap();
+
+    assert_eq!(hevy.data().routine_updates.len(), 1);
+}
+
+#[tokio::test]
+async fn test_rate_limited_fetch_surfaces_error() {
+    let hevy = MockHevy::seeded().await;
+    hevy.fail_next(
+        Method::GET,
+        "/v1/workouts/w1",
+        StatusCode::TOO_MANY_REQUESTS,
+        1,
+    );
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let error = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap_err();
+
+    assert!(format!("{:#}", error).contains("429"));
+    assert!(hevy.data().routine_updates.is_empty());
+}
+
+#[tokio::test]
+async fn test_slow_responses_and_workouts_without_routine() {
+    let hevy = MockHevy::seeded().await;
+    hevy.set_delay(Duration::from_millis(200));
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let outcome = process_single_workout(&state, "w2".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::NoRoutine);
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+}
+
+#[tokio::test]
+async fn test_workouts_are_paged() {
+    let mut data = mock_hevy::seed();
+    data.workouts = (0..12)
+        .map(|i| mock_hevy::workout(&format!("w{}", i), "Day 1 - Week 1", "r1", Vec::new()))
+        .collect();
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let first = state.hevy_client.get_workouts(1, 10).await.unwrap();
+    let second = state.hevy_client.get_workouts(2, 10).await.unwrap();
+
+    assert_eq!(first.workouts.len(), 10);
+    assert_eq!(first.page_count, 2);
+    assert_eq!(second.workouts.len(), 2);
+    assert_eq!(second.workouts[1].id, "w11");
+
+    let routines = state.hevy_client.get_routines(1, 10).await.unwrap();
+    assert_eq!(routines.routines[0].id, "r1");
+}
+
+#[tokio::test]
+async fn test_progressions_leave_the_deload_alone() {
+    let mut data = mock_hevy::seed();
+    let routine = data.routines.get_mut("r1").unwrap();
+    routine.title = "Day 1 - Week 7".to_string();
+    routine.exercises.push(mock_hevy::exercise(
+        "lateral",
+        "Lateral Raise (Dumbbell)",
+        vec![mock_hevy::set("normal", Some(25.0), 12); 2],
+    ));
+    let run = mock_hevy::exercise(
+        "run",
+        "Running",
+        vec![mock_hevy::cardio_set("normal", Some(5000), Some(1500))],
+    );
+    routine.exercises.push(run.clone());
+    let dips = mock_hevy::exercise(
+        "dips",
+        "Chest Dip (Weighted)",
+        vec![mock_hevy::set("normal", Some(10.0), 12); 2],
+    );
+    routine.exercises.push(dips.clone());
+    let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+    performed.title = "Day 1 - Week 7".to_string();
+    performed.exercises.push(mock_hevy::exercise(
+        "lateral",
+        "Lateral Raise (Dumbbell)",
+        vec![mock_hevy::set("normal", Some(25.0), 12); 2],
+    ));
+    performed.exercises.push(run);
+    performed.exercises.push(dips);
+    data.exercise_templates.push(serde_json::json!({
+        "id": "dips", "title": "Chest Dip (Weighted)", "type": "bodyweight_reps",
+        "primary_muscle_group": "chest", "is_custom": false
+    }));
+    let hevy = MockHevy::start(data).await;
+    let mut config = test_config(&hevy.base_url);
+    config.double_progression_exercises = vec!["lateral".to_string()];
+    let state = crate::build_state(&config).unwrap();
+    state
+        .history
+        .record_bodyweight(80.0, chrono::Utc::now())
+        .unwrap();
+    // w1 missed a squat rep against the last prescription
+    state
+        .prescriptions
+        .record(&PrescriptionRecord {
+            routine_id: "r1".to_string(),
+            workout_id: "w0".to_string(),
+            exercises: HashMap::from([(
+                "squat".to_string(),
+                Prescription::from_exercise(&mock_hevy::exercise(
+                    "squat",
+                    "Squat (Barbell)",
+                    vec![mock_hevy::set("normal", Some(100.0), 5); 2],
+                )),
+            )]),
+            misses: HashMap::new(),
+        })
+        .unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    let routine = &data.routines["r1"];
+    assert_eq!(routine.title, "Day 1 - Week 8");
+    assert_eq!(
+        routine.exercises[1].notes.as_deref(),
+        Some(progression_notes("2 sets\n60x5\n60x5").as_str())
+    );
+    assert!(state.prescriptions.misses("r1").is_empty());
+    assert_eq!(
+        routine.exercises[2].notes.as_deref(),
+        Some(progression_notes("2 sets\n15x12\n15x12").as_str())
+    );
+    assert_eq!(
+        routine.exercises[3].notes.as_deref(),
+        Some(progression_notes("1 sets\n5km in 25:00 (5:00/km)").as_str())
+    );
+    assert_eq!(
+        routine.exercises[4].notes.as_deref(),
+        Some(progression_notes("2 sets\nBW+5kg x 12\nBW+5kg x 12").as_str())
+    );
+}
+
+#[tokio::test]
+async fn test_double_progression_writes_rep_range() {
+    let range = RepRange {
+        start: Some(8),
+        end: Some(12),
+    };
+    let mut data = mock_hevy::seed();
+    let ranged = |weight_kg, reps| ExerciseSet {
+        rep_range: Some(range.clone()),
+        ..mock_hevy::set("normal", Some(weight_kg), reps)
+    };
+    data.routines
+        .get_mut("r1")
+        .unwrap()
+        .exercises
+        .push(mock_hevy::exercise(
+            "lateral",
+            "Lateral Raise (Dumbbell)",
+            vec![ranged(25.0, 12), ranged(25.0, 12)],
+        ));
+    let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+    performed.exercises.push(mock_hevy::exercise(
+        "lateral",
+        "Lateral Raise (Dumbbell)",
+        vec![
+            mock_hevy::set("normal", Some(25.0), 12),
+            mock_hevy::set("normal", Some(25.0), 12),
+        ],
+    ));
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    let lateral = &data.routines["r1"].exercises[2];
+    assert!(
+        lateral
+            .sets
+            .iter()
+            .all(|set| set.rep_range == Some(range.clone()))
+    );
+    assert_eq!(
+        lateral.notes.as_deref(),
+        Some(progression_notes("2 sets\n27.5x8 (8-12)\n27.5x8 (8-12)").as_str())
+    );
+    // Exercises without a range keep plain rep targets
+    assert!(data.routines["r1"].exercises[0].sets[1].rep_range.is_none());
+}
+
+#[tokio::test]
+async fn test_program_prescribes_and_commits_state_after_update() {
+    let hevy = MockHevy::seeded().await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+    state.programs.assign("r1", Program::Linear).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    let routine = &data.routines["r1"];
+    assert_eq!(routine.title, "Day 1 - Week 2");
+    assert_eq!(
+        routine.exercises[0].notes.as_deref(),
+        Some(progression_notes("3 sets\n82.5x5\n82.5x5\n82.5x5").as_str())
+    );
+    // 100x4 missed the 3x5, so the squat stays put
+    assert_eq!(
+        routine.exercises[1].notes.as_deref(),
+        Some(progression_notes("3 sets\n100x5\n100x5\n100x5").as_str())
+    );
+
+    let (_, program_state) = state.programs.state_for("r1").unwrap();
+    assert_eq!(program_state.lifts["bench"].working_weight_kg, Some(82.5));
+    assert_eq!(program_state.lifts["squat"].failures, 1);
+
+    let records = state.audit_log.query(&AuditQuery::default()).unwrap();
+    assert_eq!(records[0].engine, "program");
+    assert_eq!(records[0].model, format!("linear-v{}", PROGRAMS_VERSION));
+}
+
+#[tokio::test]
+async fn test_missed_reps_repeat_the_prescribed_weight() {
+    let hevy = MockHevy::seeded().await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+    // The last update asked for 2x5 at 100kg; w1 only got 5 and 4
+    let prescribed = mock_hevy::exercise(
+        "squat",
+        "Squat (Barbell)",
+        vec![mock_hevy::set("normal", Some(100.0), 5); 2],
+    );
+    state
+        .prescriptions
+        .record(&PrescriptionRecord {
+            routine_id: "r1".to_string(),
+            workout_id: "w0".to_string(),
+            exercises: HashMap::from([(
+                "squat".to_string(),
+                Prescription::from_exercise(&prescribed),
+            )]),
+            misses: HashMap::new(),
+        })
+        .unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    assert_eq!(
+        data.routines["r1"].exercises[1].notes.as_deref(),
+        Some(
+            progression_notes("2 sets\nMissed reps last session, same weight again\n100x5\n100x5")
+                .as_str()
+        )
+    );
+
+    let recorded = state.prescriptions.snapshot();
+    assert_eq!(recorded.routines["r1"].misses["squat"], 1);
+    assert_eq!(recorded.routines["r1"].workout_id, "w1");
+    assert!(recorded.routines["r1"].exercises.contains_key("bench"));
+
+    let records = state.audit_log.query(&AuditQuery::default()).unwrap();
+    assert_eq!(records[0].parsed_response.stall_adjustments.len(), 1);
+    let adherence = records[0].adherence.as_ref().unwrap();
+    assert_eq!(
+        (adherence.completed_sets, adherence.prescribed_sets),
+        (1, 2)
+    );
+}
+
+#[tokio::test]
+async fn test_runs_progress_by_distance_at_the_same_pace() {
+    let mut data = mock_hevy::seed();
+    let run = mock_hevy::exercise(
+        "run",
+        "Running",
+        vec![mock_hevy::cardio_set("normal", Some(5000), Some(1500))],
+    );
+    let routine = data.routines.get_mut("r1").unwrap();
+    routine.exercises.push(run.clone());
+    let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+    performed.exercises.push(run);
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let data = hevy.data();
+    assert!(
+        data.requests
+            .contains(&"GET /v1/exercise_templates/run".to_string())
+    );
+    assert_eq!(
+        data.routines["r1"].exercises[2].notes.as_deref(),
+        Some(progression_notes("1 sets\n5.5km in 27:30 (5:00/km)").as_str())
+    );
+
+    let records = state.audit_log.query(&AuditQuery::default()).unwrap();
+    let run = &records[0].parsed_response.updated_exercises[2];
+    assert_eq!(run.sets[0].distance_meters, Some(5500));
+    assert_eq!(run.sets[0].duration_seconds, Some(1650));
+    assert_eq!(run.sets[0].reps, None);
+}
+
+#[tokio::test]
+async fn test_weighted_dips_add_load_once_reps_top_out() {
+    let mut data = mock_hevy::seed();
+    let dips = mock_hevy::exercise(
+        "dips",
+        "Chest Dip (Weighted)",
+        vec![mock_hevy::set("normal", Some(10.0), 12); 2],
+    );
+    data.routines
+        .get_mut("r1")
+        .unwrap()
+        .exercises
+        .push(dips.clone());
+    let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+    performed.exercises.push(dips);
+    data.exercise_templates.push(serde_json::json!({
+        "id": "dips", "title": "Chest Dip (Weighted)", "type": "bodyweight_reps",
+        "primary_muscle_group": "chest", "is_custom": false
+    }));
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+    state
+        .history
+        .record_bodyweight(80.0, chrono::Utc::now())
+        .unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    assert_eq!(
+        hevy.data().routines["r1"].exercises[2].notes.as_deref(),
+        Some(progression_notes("2 sets\nBW+12.5kg x 6 (6-12)\nBW+12.5kg x 6 (6-12)").as_str())
+    );
+    let history = state.history.snapshot();
+    let session = &history.exercises["dips"].sessions[0];
+    assert_eq!(session.top_weight_kg, Some(10.0));
+    assert_eq!(session.best_e1rm_kg, Some(90.0 * (1.0 + 12.0 / 30.0)));
+    assert_eq!(session.bodyweight_kg, Some(80.0));
+}
+
+#[tokio::test]
+async fn test_rest_lengthens_going_into_heavy_weeks() {
+    let mut data = mock_hevy::seed();
+    data.routines.get_mut("r1").unwrap().title = "Day 1 - Week 4".to_string();
+    let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+    performed.title = "Day 1 - Week 4".to_string();
+    let hevy = MockHevy::start(data).await;
+    let state = crate::build_state(&test_config(&hevy.base_url)).unwrap();
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let routine = &hevy.data().routines["r1"];
+    assert_eq!(routine.title, "Day 1 - Week 5");
+    assert!(
+        routine
+            .exercises
+            .iter()
+            .all(|exercise| exercise.rest_seconds == Some(150))
+    );
+    let records = state.audit_log.query(&AuditQuery::default()).unwrap();
+    assert_eq!(records[0].parsed_response.rest_adjustments.len(), 2);
+    // Later weeks scale from the build-week rest rather than from 2:30
+    let recorded = state.prescriptions.prescriptions_for("r1");
+    assert_eq!(recorded["bench"].base_rest_seconds, Some(120));
+}
+
+#[tokio::test]
+async fn test_substituted_exercise_follows_policy() {
+    let seeded = || {
+        let mut data = mock_hevy::seed();
+        data.routines.get_mut("r1").unwrap().exercises[1].notes = Some("knees out".to_string());
+        let performed = data.workouts.iter_mut().find(|w| w.id == "w1").unwrap();
+        performed.exercises[1] = mock_hevy::exercise(
+            "leg_press",
+            "Leg Press (Machine)",
+            vec![mock_hevy::set("normal", Some(180.0), 10); 2],
+        );
+        performed.exercises[1].notes = Some("seat at 5".to_string());
+        data.exercise_templates.push(serde_json::json!({
+            "id": "leg_press", "title": "Leg Press (Machine)", "type": "weight_reps",
+            "primary_muscle_group": "quadriceps", "is_custom": false
+        }));
+        data
+    };
+
+    let cases = [
+        (SubstitutionPolicy::Keep, "squat", None),
+        (
+            SubstitutionPolicy::Adopt,
+            "leg_press",
+            Some("Leg Press (Machine)"),
+        ),
+        (
+            SubstitutionPolicy::CarryOver,
+            "squat",
+            Some("Squat (Barbell)"),
+        ),
+    ];
+    for (policy, expected_template_id, expected_title) in cases {
+        let hevy = MockHevy::start(seeded()).await;
+        let mut config = test_config(&hevy.base_url);
+        config.substitution_policy = policy;
+        config.exercise_equivalences = "squat=leg_press".parse().unwrap();
+        let state = crate::build_state(&config).unwrap();
+
+        process_single_workout(&state, "w1".to_string())
+            .await
+            .unwrap();
+
+        let data = hevy.data();
+        let swapped = &data.routines["r1"].exercises[1];
+        assert_eq!(
+            swapped.exercise_template_id, expected_template_id,
+            "{policy}"
+        );
+        match expected_title {
+            // The routine is left alone, so the old sets and notes remain
+            None => {
+                assert_eq!(swapped.notes.as_deref(), Some("knees out"));
+                assert_eq!(swapped.sets[0].weight_kg, Some(100.0));
+            }
+            // The routine's own notes stay, not the ones logged in the workout
+            Some(title) => {
+                assert_eq!(swapped.title, title, "{policy}");
+                let notes = swapped.notes.as_deref().unwrap();
+                assert!(notes.starts_with("knees out\n\n"), "{policy}");
+                assert!(notes.contains("x10"), "{policy}");
+                assert!(!notes.contains("seat at 5"), "{policy}");
+            }
+        }
+
+        let records = state.audit_log.query(&AuditQuery::default()).unwrap();
+        let substitution = &records[0].mismatch.substituted[0];
+        assert_eq!(substitution.routine_template_id, "squat");
+        assert!(substitution.equivalent);
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use crate::clients::llm::LlmClient;
+
+/// Scripted stand-in for `GeminiClient`. Replies are handed out in the order
+/// they were queued and every prompt is kept for inspection.
+#[derive(Default)]
+pub struct FakeLlm {
+    replies: Mutex<VecDeque<Result<String, String>>>,
+    prompts: Mutex<Vec<String>>,
+}
+
+impl FakeLlm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reply(self, text: impl Into<String>) -> Self {
+        self.replies.lock().unwrap().push_back(Ok(text.into()));
+        self
+    }
+
+    pub fn fail(self, message: impl Into<String>) -> Self {
+        self.replies.lock().unwrap().push_back(Err(message.into()));
+        self
+    }
+
+    pub fn prompts(&self) -> Vec<String> {
+        self.prompts.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl LlmClient for FakeLlm {
+    fn model(&self) -> &str {
+        "fake-llm"
+    }
+
+    async fn generate_text(&self, prompt: &str) -> Result<String> {
+        self.prompts.lock().unwrap().push(prompt.to_string());
+        match self.replies.lock().unwrap().pop_front() {
+            Some(Ok(text)) => Ok(text),
+            Some(Err(message)) => Err(anyhow::anyhow!(message)),
+            None => Err(anyhow::anyhow!("FakeLlm has no scripted reply left")),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// Compares `actual` with `testdata/<name>`. Run with `UPDATE_GOLDEN=1` to
+/// (re)write the file instead, then review the diff.
+pub fn assert_golden(name: &str, actual: &str) {
+    let path = testdata_path(name);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "missing golden file {}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert!(
+        expected == actual,
+        "{} is out of date (rerun with UPDATE_GOLDEN=1 if the change is intended)\n--- expected\n{}\n--- actual\n{}",
+        path.display(),
+        expected,
+        actual
+    );
+}
+
+pub fn testdata_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join(name)
+}
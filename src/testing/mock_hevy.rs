@@ -0,0 +1,445 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, Request, State},
+    http::{Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::clients::models::{
+    common::{Exercise, ExerciseSet},
+    requests::{RoutineUpdate, UpdateRoutineRequest},
+    responses::{RoutineResponse, WorkoutResponse},
+};
+
+pub const API_KEY: &str = "mock-api-key";
+
+/// A failure returned instead of the real response for the next `times`
+/// requests matching `method` and `path_prefix`.
+#[derive(Debug, Clone)]
+struct Fault {
+    method: Method,
+    path_prefix: String,
+    status: StatusCode,
+    times: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct MockData {
+    /// Newest first, like the real API.
+    pub workouts: Vec<WorkoutResponse>,
+    pub routines: HashMap<String, RoutineResponse>,
+    pub exercise_templates: Vec<Value>,
+    /// Every routine update received, in order.
+    pub routine_updates: Vec<(String, RoutineUpdate)>,
+    /// `"METHOD /path"` for every request received, in order.
+    pub requests: Vec<String>,
+    faults: Vec<Fault>,
+    delay: Option<Duration>,
+}
+
+type SharedData = Arc<Mutex<MockData>>;
+
+/// In-process stand-in for the Hevy API, listening on a random local port.
+pub struct MockHevy {
+    pub base_url: String,
+    data: SharedData,
+    server: JoinHandle<()>,
+}
+
+impl MockHevy {
+    pub async fn start(data: MockData) -> Self {
+        let data = Arc::new(Mutex::new(data));
+
+        let app = Router::new()
+            .route("/v1/workouts", get(list_workouts))
+            .route("/v1/workouts/{id}", get(get_workout))
+            .route("/v1/routines", get(list_routines))
+            .route("/v1/routines/{id}", get(get_routine).put(update_routine))
+            .route("/v1/exercise_templates", get(list_exercise_templates))
+            .route("/v1/exercise_templates/{id}", get(get_exercise_template))
+            .layer(middleware::from_fn_with_state(
+                Arc::clone(&data),
+                inject_faults,
+            ))
+            .with_state(Arc::clone(&data));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self {
+            base_url,
+            data,
+            server,
+        }
+    }
+
+    /// Starts the server with [`seed`] data.
+    pub async fn seeded() -> Self {
+        Self::start(seed()).await
+    }
+
+    pub fn data(&self) -> std::sync::MutexGuard<'_, MockData> {
+        self.data.lock().unwrap()
+    }
+
+    /// Fails the next `times` requests matching `method` and `path_prefix`
+    /// with `status`.
+    pub fn fail_next(&self, method: Method, path_prefix: &str, status: StatusCode, times: usize) {
+        self.data().faults.push(Fault {
+            method,
+            path_prefix: path_prefix.to_string(),
+            status,
+            times,
+        });
+    }
+
+    /// Delays every response by `delay`.
+    pub fn set_delay(&self, delay: Duration) {
+        self.data().delay = Some(delay);
+    }
+}
+
+impl Drop for MockHevy {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn inject_faults(State(data): State<SharedData>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+
+    let (delay, fault) = {
+        let mut data = data.lock().unwrap();
+        data.requests.push(format!("{} {}", method, path));
+
+        let fault = data.faults.iter_mut().find(|fault| {
+            fault.times > 0 && fault.method == method && path.starts_with(&fault.path_prefix)
+        });
+        let status = fault.map(|fault| {
+            fault.times -= 1;
+            fault.status
+        });
+        (data.delay, status)
+    };
+
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+
+    if request
+        .headers()
+        .get("api-key")
+        .is_none_or(|key| key != API_KEY)
+    {
+        return (StatusCode::UNAUTHORIZED, "invalid api key").into_response();
+    }
+
+    if let Some(status) = fault {
+        return (status, format!("injected {}", status.as_u16())).into_response();
+    }
+
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct PageQuery {
+    page: Option<usize>,
+    #[serde(rename = "pageSize")]
+    page_size: Option<usize>,
+}
+
+fn paginate<T: Clone>(items: &[T], query: &PageQuery) -> (usize, usize, Vec<T>) {
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(5).clamp(1, 10);
+    let page_count = items.len().div_ceil(page_size).max(1);
+    let slice = items
+        .iter()
+        .skip((page - 1) * page_size)
+        .take(page_size)
+        .cloned()
+        .collect();
+    (page, page_count, slice)
+}
+
+async fn list_workouts(
+    State(data): State<SharedData>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let data = data.lock().unwrap();
+    let (page, page_count, workouts) = paginate(&data.workouts, &query);
+    Json(json!({ "page": page, "page_count": page_count, "workouts": workouts }))
+}
+
+async fn get_workout(State(data): State<SharedData>, Path(id): Path<String>) -> Response {
+    let data = data.lock().unwrap();
+    match data.workouts.iter().find(|workout| workout.id == id) {
+        Some(workout) => Json(workout.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn list_routines(
+    State(data): State<SharedData>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let data = data.lock().unwrap();
+    let mut routines: Vec<_> = data.routines.values().cloned().collect();
+    routines.sort_by(|a, b| a.id.cmp(&b.id));
+    let (page, page_count, routines) = paginate(&routines, &query);
+    Json(json!({ "page": page, "page_count": page_count, "routines": routines }))
+}
+
+async fn get_routine(State(data): State<SharedData>, Path(id): Path<String>) -> Response {
+    let data = data.lock().unwrap();
+    match data.routines.get(&id) {
+        Some(routine) => Json(json!({ "routine": routine })).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn update_routine(
+    State(data): State<SharedData>,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateRoutineRequest>,
+) -> Response {
+    let mut guard = data.lock().unwrap();
+    let data = &mut *guard;
+    let update = request.routine;
+
+    let Some(routine) = data.routines.get_mut(&id) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    if let Some(title) = &update.title {
+        routine.title = title.clone();
+    }
+    if let Some(notes) = &update.notes {
+        routine.notes = Some(notes.clone());
+    }
+    if let Some(exercises) = &update.exercises {
+        routine.exercises = exercises
+            .iter()
+            .enumerate()
+            .map(|(index, exercise)| Exercise {
+                index: index as u32,
+                title: routine
+                    .exercises
+                    .iter()
+                    .find(|existing| existing.exercise_template_id == exercise.exercise_template_id)
+                    .map(|existing| existing.title.clone())
+                    .or_else(|| {
+                        data.exercise_templates
+                            .iter()
+                            .find(|template| {
+                                template["id"] == exercise.exercise_template_id.as_str()
+                            })
+                            .and_then(|template| template["title"].as_str())
+                            .map(str::to_string)
+                    })
+                    .unwrap_or_default(),
+                notes: exercise.notes.clone(),
+                exercise_template_id: exercise.exercise_template_id.clone(),
+                superset_id: exercise.superset_id,
+                rest_seconds: exercise.rest_seconds,
+                sets: exercise
+                    .sets
+                    .iter()
+                    .enumerate()
+                    .map(|(index, set)| ExerciseSet {
+                        index: index as u32,
+                        set_type: set.set_type.clone(),
+                        weight_kg: set.weight_kg,
+                        reps: set.reps,
+                        distance_meters: set.distance_meters,
+                        duration_seconds: set.duration_seconds,
+                        rpe: None,
+                        custom_metric: set.custom_metric,
+                        rep_range: set.rep_range.clone(),
+                    })
+                    .collect(),
+            })
+            .collect();
+    }
+
+    let updated = routine.clone();
+    data.routine_updates.push((id, update));
+    Json(json!({ "routine": [updated] })).into_response()
+}
+
+async fn list_exercise_templates(
+    State(data): State<SharedData>,
+    Query(query): Query<PageQuery>,
+) -> impl IntoResponse {
+    let data = data.lock().unwrap();
+    let (page, page_count, templates) = paginate(&data.exercise_templates, &query);
+    Json(json!({ "page": page, "page_count": page_count, "exercise_templates": templates }))
+}
+
+async fn get_exercise_template(State(data): State<SharedData>, Path(id): Path<String>) -> Response {
+    let data = data.lock().unwrap();
+    match data
+        .exercise_templates
+        .iter()
+        .find(|template| template["id"] == id.as_str())
+    {
+        Some(template) => Json(template.clone()).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+pub fn set(set_type: &str, weight_kg: Option<f32>, reps: u32) -> ExerciseSet {
+    ExerciseSet {
+        index: 0,
+        set_type: set_type.to_string(),
+        weight_kg,
+        reps: Some(reps),
+        distance_meters: None,
+        duration_seconds: None,
+        rpe: None,
+        custom_metric: None,
+        rep_range: None,
+    }
+}
+
+pub fn cardio_set(
+    set_type: &str,
+    distance_meters: Option<u32>,
+    duration_seconds: Option<u32>,
+) -> ExerciseSet {
+    ExerciseSet {
+        weight_kg: None,
+        reps: None,
+        distance_meters,
+        duration_seconds,
+        ..set(set_type, None, 0)
+    }
+}
+
+pub fn exercise(template_id: &str, title: &str, sets: Vec<ExerciseSet>) -> Exercise {
+    Exercise {
+        index: 0,
+        title: title.to_string(),
+        notes: None,
+        exercise_template_id: template_id.to_string(),
+        superset_id: None,
+        rest_seconds: Some(120),
+        sets: sets
+            .into_iter()
+            .enumerate()
+            .map(|(index, set)| ExerciseSet {
+                index: index as u32,
+                ..set
+            })
+            .collect(),
+    }
+}
+
+pub fn workout(
+    id: &str,
+    title: &str,
+    routine_id: &str,
+    exercises: Vec<Exercise>,
+) -> WorkoutResponse {
+    let now = chrono::Utc::now().to_rfc3339();
+    WorkoutResponse {
+        id: id.to_string(),
+        title: title.to_string(),
+        routine_id: routine_id.to_string(),
+        description: String::new(),
+        start_time: now.clone(),
+        end_time: now.clone(),
+        updated_at: now.clone(),
+        created_at: now,
+        exercises,
+    }
+}
+
+pub fn routine(id: &str, title: &str, exercises: Vec<Exercise>) -> RoutineResponse {
+    RoutineResponse {
+        id: id.to_string(),
+        title: title.to_string(),
+        notes: None,
+        folder_id: None,
+        updated_at: String::new(),
+        created_at: String::new(),
+        exercises,
+    }
+}
+
+/// Routine `r1` ("Day 1 - Week 2": bench 80x7 and squat 100x5) and workout
+/// `w1` performing it, hitting every bench rep and missing a squat rep.
+/// Workout `w2` has no routine.
+pub fn seed() -> MockData {
+    let routine = routine(
+        "r1",
+        "Day 1 - Week 2",
+        vec![
+            exercise(
+                "bench",
+                "Bench Press (Barbell)",
+                vec![
+                    set("warmup", Some(40.0), 10),
+                    set("normal", Some(80.0), 7),
+                    set("normal", Some(80.0), 7),
+                ],
+            ),
+            exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(100.0), 5), set("normal", Some(100.0), 5)],
+            ),
+        ],
+    );
+
+    let performed = workout(
+        "w1",
+        "Day 1 - Week 2",
+        "r1",
+        vec![
+            exercise(
+                "bench",
+                "Bench Press (Barbell)",
+                vec![
+                    set("warmup", Some(40.0), 10),
+                    set("normal", Some(80.0), 7),
+                    set("normal", Some(80.0), 7),
+                ],
+            ),
+            exercise(
+                "squat",
+                "Squat (Barbell)",
+                vec![set("normal", Some(100.0), 5), set("normal", Some(100.0), 4)],
+            ),
+        ],
+    );
+
+    let freestyle = workout(
+        "w2",
+        "Evening Run",
+        "",
+        vec![exercise("run", "Running", vec![])],
+    );
+
+    MockData {
+        workouts: vec![freestyle, performed],
+        routines: HashMap::from([("r1".to_string(), routine)]),
+        exercise_templates: vec![
+            json!({ "id": "bench", "title": "Bench Press (Barbell)", "type": "weight_reps", "primary_muscle_group": "chest", "is_custom": false }),
+            json!({ "id": "squat", "title": "Squat (Barbell)", "type": "weight_reps", "primary_muscle_group": "quadriceps", "is_custom": false }),
+            json!({ "id": "run", "title": "Running", "type": "distance_duration", "primary_muscle_group": "cardio", "is_custom": false }),
+        ],
+        ..MockData::default()
+    }
+}
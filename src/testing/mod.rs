@@ -0,0 +1,79 @@
+//! Test-only helpers: a fake Hevy API and end-to-end tests that run the real
+//! service against it.
+
+mod end_to_end;
+pub mod fake_llm;
+pub mod golden;
+pub mod mock_hevy;
+mod prompts;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::api::webhooks::AppState;
+use crate::clients::llm::LlmClient;
+use crate::config::{Config, SyncConfig};
+use crate::services::double_progression::RepTarget;
+use crate::services::engine::Engine;
+use crate::services::progressive_overload::ProgressiveOverloadService;
+
+pub const WEBHOOK_TOKEN: &str = "test-token";
+
+/// Config pointing at `hevy_api_url` with a fresh data directory, the
+/// rule-based engine so no LLM is needed, and immediate retries.
+pub fn test_config(hevy_api_url: &str) -> Config {
+    static NEXT_DIR: AtomicUsize = AtomicUsize::new(0);
+    let data_dir: PathBuf = std::env::temp_dir().join(format!(
+        "hevy-e2e-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&data_dir);
+
+    Config {
+        hevy_api_key: mock_hevy::API_KEY.to_string(),
+        hevy_api_url: hevy_api_url.to_string(),
+        webhook_tokens: vec![WEBHOOK_TOKEN.to_string()],
+        webhook_signature_tolerance_secs: 300,
+        gemini_api_key: "unused".to_string(),
+        gemini_model: "unused".to_string(),
+        engine: Engine::RuleBased,
+        data_dir,
+        worker_concurrency: 1,
+        job_max_attempts: 3,
+        job_retry_base_secs: 0,
+        shutdown_timeout_secs: 5,
+        sync: SyncConfig {
+            enabled: false,
+            ..SyncConfig::default()
+        },
+        double_progression_increment_kg: 2.5,
+        stall_threshold: 3,
+        stall_reduce_pct: 10.0,
+        conditioning_max_weekly_increase_pct: 10.0,
+        conditioning_pace_step_pct: 2.0,
+        bodyweight_rep_range: RepTarget { min: 6, max: 12 },
+        bodyweight_increment_kg: 2.5,
+        rest_heavy_increase_pct: 25.0,
+        rest_deload_decrease_pct: 25.0,
+        ..Config::default()
+    }
+}
+
+/// App state for `config` with the LLM engine backed by `llm`.
+pub fn state_with_llm(config: &Config, llm: Arc<dyn LlmClient>) -> AppState {
+    let mut state = crate::build_state(config).unwrap();
+    state.progressive_overload_service = ProgressiveOverloadService::new(
+        llm,
+        state.hevy_client.clone(),
+        crate::double_progression(config),
+        crate::stall_detector(config, state.prescriptions.clone()),
+        state.programs.clone(),
+        Engine::Gemini,
+    )
+    .with_conditioning(crate::conditioning(config))
+    .with_bodyweight(crate::bodyweight_progression(config))
+    .with_rest_periods(crate::rest_periods(config));
+    state
+}
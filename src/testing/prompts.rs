@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use super::fake_llm::FakeLlm;
+use super::golden::{assert_golden, testdata_path};
+use super::mock_hevy::{self, MockData, MockHevy, cardio_set, exercise, set};
+use super::{state_with_llm, test_config};
+use crate::api::webhooks::{ProcessOutcome, process_single_workout};
+use crate::clients::models::responses::{RoutineResponse, WorkoutResponse};
+use crate::services::stalls::{Prescription, PrescriptionRecord};
+
+const FIXED_TIME: &str = "2025-03-03T18:00:00+00:00";
+
+fn workout(id: &str, title: &str) -> WorkoutResponse {
+    let mut workout = mock_hevy::workout(
+        id,
+        title,
+        "r1",
+        vec![
+            exercise(
+                "bench",
+                "Bench Press (Barbell)",
+                vec![
+                    set("warmup", Some(40.0), 10),
+                    set("normal", Some(80.0), 7),
+                    set("normal", Some(80.0), 6),
+                ],
+            ),
+            exercise("pullup", "Pull Up", vec![set("normal", None, 8)]),
+        ],
+    );
+    workout.start_time = FIXED_TIME.to_string();
+    workout.end_time = FIXED_TIME.to_string();
+    workout.created_at = FIXED_TIME.to_string();
+    workout.updated_at = FIXED_TIME.to_string();
+    workout
+}
+
+fn routine(title: &str) -> RoutineResponse {
+    let performed = workout("unused", title);
+    RoutineResponse {
+        id: "r1".to_string(),
+        title: title.to_string(),
+        notes: None,
+        folder_id: None,
+        updated_at: FIXED_TIME.to_string(),
+        created_at: FIXED_TIME.to_string(),
+        exercises: performed.exercises,
+    }
+}
+
+async fn prompt_for(history: Vec<WorkoutResponse>, title: &str) -> String {
+    let mut data = MockData::default();
+    data.workouts = history;
+    let hevy = MockHevy::start(data).await;
+    let state = state_with_llm(&test_config(&hevy.base_url), Arc::new(FakeLlm::new()));
+
+    state
+        .progressive_overload_service
+        .build_progressive_overload_prompt(&workout("current", title), &routine(title))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_prompt_golden_weeks_1_to_7() {
+    for week in 1..=7 {
+        let prompt = prompt_for(Vec::new(), &format!("Day 1 - Week {}", week)).await;
+        assert_golden(&format!("prompts/week_{}.txt", week), &prompt);
+    }
+}
+
+#[tokio::test]
+async fn test_prompt_golden_deload_transitions() {
+    let with_week1 = prompt_for(vec![workout("old-w1", "Day 1 - Week 1")], "Day 1 - Week 8").await;
+    assert_golden("prompts/week_8_week1_reference.txt", &with_week1);
+
+    let with_week7 = prompt_for(vec![workout("old-w7", "Day 1 - Week 7")], "Day 1 - Week 8").await;
+    assert_golden("prompts/week_8_week7_fallback.txt", &with_week7);
+
+    let without_reference = prompt_for(Vec::new(), "Day 1 - Week 8").await;
+    assert_golden("prompts/week_8_no_reference.txt", &without_reference);
+}
+
+#[tokio::test]
+async fn test_prompt_golden_with_adherence() {
+    let hevy = MockHevy::start(MockData::default()).await;
+    let state = state_with_llm(&test_config(&hevy.base_url), Arc::new(FakeLlm::new()));
+    let prescribed = [
+        exercise(
+            "bench",
+            "Bench Press (Barbell)",
+            vec![set("normal", Some(82.5), 7), set("normal", Some(82.5), 7)],
+        ),
+        exercise("pullup", "Pull Up", vec![set("normal", None, 10)]),
+        exercise(
+            "squat",
+            "Squat (Barbell)",
+            vec![set("normal", Some(100.0), 5)],
+        ),
+    ];
+    state
+        .prescriptions
+        .record(&PrescriptionRecord {
+            routine_id: "r1".to_string(),
+            workout_id: "previous".to_string(),
+            exercises: prescribed
+                .iter()
+                .map(|exercise| {
+                    (
+                        exercise.exercise_template_id.clone(),
+                        Prescription::from_exercise(exercise),
+                    )
+                })
+                .collect(),
+            misses: HashMap::new(),
+        })
+        .unwrap();
+
+    let title = "Day 1 - Week 3";
+    let prompt = state
+        .progressive_overload_service
+        .build_progressive_overload_prompt(&workout("current", title), &routine(title))
+        .await
+        .unwrap();
+    assert_golden("prompts/week_3_adherence.txt", &prompt);
+}
+
+#[tokio::test]
+async fn test_prompt_golden_with_cardio() {
+    let hevy = MockHevy::start(MockData::default()).await;
+    let state = state_with_llm(&test_config(&hevy.base_url), Arc::new(FakeLlm::new()));
+    let title = "Day 1 - Week 3";
+    let intervals = exercise(
+        "row",
+        "Rowing Machine",
+        vec![cardio_set("normal", Some(500), Some(110)); 4],
+    );
+    let mut current = workout("current", title);
+    current.exercises.push(intervals.clone());
+    current.exercises.push(exercise(
+        "run",
+        "Running",
+        vec![cardio_set("normal", Some(5000), Some(1500))],
+    ));
+    let mut routine = routine(title);
+    routine.exercises.push(intervals);
+
+    let prompt = state
+        .progressive_overload_service
+        .build_progressive_overload_prompt(&current, &routine)
+        .await
+        .unwrap();
+    assert_golden("prompts/week_3_cardio.txt", &prompt);
+}
+
+#[tokio::test]
+async fn test_prompt_golden_with_superset() {
+    let title = "Day 1 - Week 5";
+    let mut current = workout("current", title);
+    current.exercises.push(exercise(
+        "curl",
+        "Bicep Curl (Dumbbell)",
+        vec![set("normal", Some(12.0), 10)],
+    ));
+    for exercise in &mut current.exercises[1..] {
+        exercise.superset_id = Some(1);
+        exercise.rest_seconds = Some(90);
+    }
+    let routine = RoutineResponse {
+        exercises: current.exercises.clone(),
+        ..routine(title)
+    };
+
+    let hevy = MockHevy::start(MockData::default()).await;
+    let state = state_with_llm(&test_config(&hevy.base_url), Arc::new(FakeLlm::new()));
+    let prompt = state
+        .progressive_overload_service
+        .build_progressive_overload_prompt(&current, &routine)
+        .await
+        .unwrap();
+    assert_golden("prompts/week_5_superset.txt", &prompt);
+}
+
+#[tokio::test]
+async fn test_fake_llm_drives_routine_update() {
+    let reply =
+        std::fs::read_to_string(testdata_path("llm_outputs/fenced_with_prose.txt")).unwrap();
+    let hevy = MockHevy::seeded().await;
+    let llm = Arc::new(FakeLlm::new().fail("503 overloaded").reply(reply));
+    let state = state_with_llm(&test_config(&hevy.base_url), llm.clone());
+
+    let error = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap_err();
+    assert!(format!("{:#}", error).contains("503 overloaded"));
+    assert!(hevy.data().routine_updates.is_empty());
+
+    let outcome = process_single_workout(&state, "w1".to_string())
+        .await
+        .unwrap();
+    assert_eq!(outcome, ProcessOutcome::Updated);
+
+    let prompts = llm.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("CURRENT WEEK: 2"));
+    assert_eq!(hevy.data().routines["r1"].title, "Day 1 - Week 3");
+}